@@ -0,0 +1,213 @@
+//! Incremental writing of sfa files.
+
+use crate::dedupe::Dedupe;
+use crate::delta::Delta;
+use crate::format::EntryFormat;
+use crate::layout::Layout;
+use crate::metadata;
+use crate::toc::{Positioned, Toc};
+use crate::{
+    check_not_empty, encode_image_with_delta, write_deduped_entry, write_entry, EncodeOptions,
+    EntryHeader, SfaError,
+};
+#[cfg(feature = "fs")]
+use crate::{encode_file_with_delta, write_file_entry};
+use std::collections::HashSet;
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// A writer that adds entries to a sfa file one at a time as they
+/// become available, instead of taking all of them at once like
+/// `sfa::encode_to_writer`. The magic text identifier is written
+/// when the builder is created and every entry is written as soon
+/// as it is added, so nothing but the entry being added is kept
+/// in memory.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, ImageOutputFormat};
+/// use sfa::{decode_from_reader_ordered, SfaBuilder};
+///
+/// let frame = std::env::temp_dir().join("sfa_builder.png");
+/// DynamicImage::new_rgba8(4, 4).save(&frame).unwrap();
+///
+/// let mut png = vec![];
+/// DynamicImage::new_rgba8(2, 2)
+///     .write_to(&mut png, ImageOutputFormat::Png)
+///     .unwrap();
+///
+/// let mut builder = SfaBuilder::new(vec![]).unwrap();
+/// builder.add_file("walk_1.png", &frame).unwrap();
+/// builder.add_image("walk_2.png", &DynamicImage::new_rgba8(8, 8)).unwrap();
+/// let size = builder.add_raw_png("walk_3.png", &png).unwrap();
+/// assert_eq!(size, png.len() as u64);
+/// let archive = builder.finish().unwrap();
+///
+/// let decoded = decode_from_reader_ordered(&mut archive.as_slice()).unwrap();
+/// let names: Vec<&str> = decoded.iter().map(|(name, _)| name.as_str()).collect();
+/// assert_eq!(names, ["walk_1.png", "walk_2.png", "walk_3.png"]);
+/// ```
+pub struct SfaBuilder<W: Write> {
+    writer: Positioned<W>,
+    options: EncodeOptions,
+    layout: Layout,
+    names: HashSet<String>,
+    toc: Toc,
+    dedupe: Dedupe,
+    delta: Delta,
+}
+
+impl<W: Write> SfaBuilder<W> {
+    /// Create a new builder with the default options and write the
+    /// magic text identifier to the writer.
+    ///
+    /// # Errors
+    ///
+    /// * Writing to the writer was unsuccessful. (`SfaError::Io`)
+    pub fn new(writer: W) -> Result<SfaBuilder<W>, SfaError> {
+        SfaBuilder::with_options(writer, EncodeOptions::default())
+    }
+
+    /// Create a new builder that encodes the entries with the given
+    /// options and write the magic text identifier to the writer.
+    ///
+    /// # Errors
+    ///
+    /// * Writing to the writer was unsuccessful. (`SfaError::Io`)
+    pub fn with_options(writer: W, options: EncodeOptions) -> Result<SfaBuilder<W>, SfaError> {
+        let layout = options.layout();
+        let mut writer = Positioned::new(writer);
+        layout.write(&mut writer)?;
+        metadata::write_archive_metadata(&mut writer, layout, &options.metadata)?;
+
+        Ok(SfaBuilder {
+            writer,
+            layout,
+            names: HashSet::new(),
+            toc: Toc::new(layout),
+            dedupe: Dedupe::new(options.dedupe),
+            delta: Delta::new(options.delta_frames),
+            options,
+        })
+    }
+
+    /// Add the image of the input file as an entry with the given name.
+    /// Returns the size of the data of the entry.
+    ///
+    /// # Errors
+    ///
+    /// * The name is already used by another entry. (`SfaError::DuplicateName`)
+    /// * The file is not a valid image or can not be read.
+    ///   (`SfaError::InputEntry` with the path of the file)
+    /// * Writing to the writer was unsuccessful. (`SfaError::Io`)
+    #[cfg(feature = "fs")]
+    pub fn add_file<P: AsRef<Path>>(&mut self, name: &str, path: P) -> Result<u64, SfaError> {
+        self.check_name(name)?;
+        let header = if self.options.dedupe || self.options.delta_frames {
+            let (data, format) =
+                encode_file_with_delta(path.as_ref(), &self.options, &mut self.delta)?;
+            self.write_deduped(name, &data, format)?
+        } else {
+            write_file_entry(
+                &mut self.writer,
+                self.layout,
+                name,
+                path.as_ref(),
+                &self.options,
+            )?
+        };
+
+        Ok(self.added(header))
+    }
+
+    /// Add the image as an entry with the given name. Returns the size
+    /// of the data of the entry.
+    ///
+    /// # Errors
+    ///
+    /// * The name is already used by another entry. (`SfaError::DuplicateName`)
+    /// * Encoding the image failed. (`SfaError::Image`)
+    /// * Writing to the writer was unsuccessful. (`SfaError::Io`)
+    pub fn add_image(&mut self, name: &str, im: &image::DynamicImage) -> Result<u64, SfaError> {
+        self.check_name(name)?;
+        let header = if self.options.dedupe || self.options.delta_frames {
+            let (data, format) = encode_image_with_delta(im, &self.options, &mut self.delta)?;
+            self.write_deduped(name, &data, format)?
+        } else {
+            write_entry(&mut self.writer, self.layout, name, im, &self.options)?
+        };
+
+        Ok(self.added(header))
+    }
+
+    /// Add already encoded PNG data as an entry with the given name.
+    /// The data is written as is without being validated, so it has
+    /// to be a valid PNG image for the archive to be decodable.
+    /// With `EncodeOptions::delta_frames` the entry is stored in full
+    /// and so is the entry after it, since the data is not decoded.
+    /// Returns the size of the data of the entry.
+    ///
+    /// # Errors
+    ///
+    /// * The name is already used by another entry. (`SfaError::DuplicateName`)
+    /// * Writing to the writer was unsuccessful. (`SfaError::Io`)
+    pub fn add_raw_png(&mut self, name: &str, data: &[u8]) -> Result<u64, SfaError> {
+        self.check_name(name)?;
+        let header = self.write_deduped(name, data, EntryFormat::Png)?;
+        self.delta.reset();
+
+        Ok(self.added(header))
+    }
+
+    /// Write the table of contents and the entry count if the version of
+    /// the file has them, then flush the writer and return it.
+    ///
+    /// # Errors
+    ///
+    /// * No entries were added and `EncodeOptions::allow_empty` is not
+    ///   set. (`SfaError::EmptyInput`)
+    /// * Writing to the writer was unsuccessful. (`SfaError::Io`)
+    pub fn finish(mut self) -> Result<W, SfaError> {
+        check_not_empty(self.names.len(), &self.options)?;
+        self.toc.finish(&mut self.writer)?;
+        self.writer.flush()?;
+
+        Ok(self.writer.into_inner())
+    }
+
+    /// Record an entry that was just written and return the size of its data.
+    fn added(&mut self, header: EntryHeader) -> u64 {
+        let size = header.size as u64;
+        self.names.insert(header.name.clone());
+        self.toc.add(header, self.writer.position());
+        size
+    }
+
+    /// Write an entry, or an alias if the options enable deduplication
+    /// and an earlier entry has the same data.
+    fn write_deduped(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        format: EntryFormat,
+    ) -> Result<EntryHeader, SfaError> {
+        write_deduped_entry(
+            &mut self.writer,
+            self.layout,
+            name,
+            data,
+            format,
+            &mut self.dedupe,
+        )
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), SfaError> {
+        if self.names.contains(name) {
+            return Err(SfaError::DuplicateName(name.to_owned()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Copying the metadata of input files into the PNG data of entries.
+
+use crate::png::write_chunk;
+use crate::{MetadataPolicy, PNG_SIGNATURE};
+
+/// Marker of the JPEG segments that hold the ICC profile.
+const JPEG_APP2: u8 = 0xe2;
+
+/// The ancillary chunks of an input file that the policy keeps, ready
+/// to be inserted into PNG data. Chunks of PNG files are copied as they
+/// are, the ICC profile of JPEG files is stored as an `iCCP` chunk.
+pub(crate) fn source_chunks(data: &[u8], policy: MetadataPolicy) -> Vec<u8> {
+    let mut chunks: Vec<u8> = vec![];
+    if policy == MetadataPolicy::Strip {
+        return chunks;
+    }
+
+    if data.starts_with(PNG_SIGNATURE) {
+        for (kind, chunk) in png_chunks(&data[PNG_SIGNATURE.len()..]) {
+            if keeps(policy, kind) {
+                chunks.extend_from_slice(chunk);
+            }
+        }
+    } else if let Some(profile) = jpeg_icc_profile(data) {
+        // The profile name, then compression method 0 which is zlib
+        let mut iccp = b"ICC Profile\0\0".to_vec();
+        iccp.extend_from_slice(&deflate::deflate_bytes_zlib(&profile));
+        write_chunk(&mut chunks, b"iCCP", &iccp);
+    }
+
+    chunks
+}
+
+/// Insert chunks into PNG data right after the `IHDR` chunk, where
+/// all the chunks that must come before the image data can go.
+pub(crate) fn insert(mut png: Vec<u8>, chunks: &[u8]) -> Vec<u8> {
+    // Signature, length, type, 13 bytes of data and the CRC
+    let end_of_header = PNG_SIGNATURE.len() + 4 + 4 + 13 + 4;
+    if !chunks.is_empty() && png.len() >= end_of_header {
+        png.splice(end_of_header..end_of_header, chunks.iter().copied());
+    }
+
+    png
+}
+
+/// Whether the policy keeps the chunk of the given type.
+fn keeps(policy: MetadataPolicy, kind: &[u8]) -> bool {
+    match policy {
+        MetadataPolicy::Strip => false,
+        MetadataPolicy::PreserveIcc => kind == b"iCCP",
+        MetadataPolicy::PreserveAll => {
+            matches!(kind, b"iCCP" | b"tEXt" | b"zTXt" | b"iTXt" | b"pHYs")
+        }
+    }
+}
+
+/// The type and the whole data, from the length to the CRC, of each
+/// chunk of PNG data after the signature. Stops at truncated chunks.
+fn png_chunks(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 12 {
+            return None;
+        }
+        let length = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+        let size = length.checked_add(12).filter(|&size| size <= data.len())?;
+
+        let (chunk, rest) = data.split_at(size);
+        data = rest;
+        Some((&chunk[4..8], chunk))
+    })
+}
+
+/// The ICC profile of JPEG data. Large profiles are split across
+/// several `APP2` segments which are numbered starting from 1.
+fn jpeg_icc_profile(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+
+    let mut parts: Vec<(u8, &[u8])> = vec![];
+    let mut rest = &data[2..];
+    while rest.len() >= 4 && rest[0] == 0xff {
+        let marker = rest[1];
+        // The segments with metadata all come before the start of scan
+        if marker == 0xda || marker == 0xd9 {
+            break;
+        }
+        let length = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+        if length < 2 || rest.len() < 2 + length {
+            break;
+        }
+
+        let segment = &rest[4..2 + length];
+        if marker == JPEG_APP2 && segment.len() > 14 && segment.starts_with(b"ICC_PROFILE\0") {
+            parts.push((segment[12], &segment[14..]));
+        }
+        rest = &rest[2 + length..];
+    }
+
+    if parts.is_empty() {
+        return None;
+    }
+    parts.sort_by_key(|(number, _)| *number);
+
+    Some(
+        parts
+            .into_iter()
+            .flat_map(|(_, part)| part)
+            .copied()
+            .collect(),
+    )
+}
@@ -0,0 +1,49 @@
+//! Containers that decoded entries can be collected into.
+
+use crate::{collect_unique, SfaError};
+use image::DynamicImage;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+
+/// A container that `sfa::decode_into` can collect the decoded entries
+/// of a sfa file into.
+///
+/// * `HashMap<String, DynamicImage>` for looking up entries by name.
+/// * `BTreeMap<String, DynamicImage>` for iterating in the order of
+///   the names.
+/// * `Vec<(String, DynamicImage)>` for the order of the entries in
+///   the file, which is the order they were encoded in.
+pub trait FromSfaEntries: Sized {
+    /// Collect the entries, which are given in the order of the file.
+    /// Maps return `SfaError::DuplicateName` for the first name that
+    /// is repeated instead of silently dropping one of the entries.
+    fn from_sfa_entries(entries: Vec<(String, DynamicImage)>) -> Result<Self, SfaError>;
+}
+
+impl FromSfaEntries for HashMap<String, DynamicImage> {
+    fn from_sfa_entries(entries: Vec<(String, DynamicImage)>) -> Result<Self, SfaError> {
+        collect_unique(entries.into_iter().map(Ok))
+    }
+}
+
+impl FromSfaEntries for BTreeMap<String, DynamicImage> {
+    fn from_sfa_entries(entries: Vec<(String, DynamicImage)>) -> Result<Self, SfaError> {
+        let mut results: BTreeMap<String, DynamicImage> = BTreeMap::new();
+        for (name, im) in entries {
+            match results.entry(name) {
+                Entry::Occupied(entry) => return Err(SfaError::DuplicateName(entry.key().clone())),
+                Entry::Vacant(entry) => {
+                    entry.insert(im);
+                }
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+impl FromSfaEntries for Vec<(String, DynamicImage)> {
+    fn from_sfa_entries(entries: Vec<(String, DynamicImage)>) -> Result<Self, SfaError> {
+        Ok(entries)
+    }
+}
@@ -0,0 +1,48 @@
+//! Detection of entries with identical data.
+
+use crate::format::EntryFormat;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Remembers the hashes of the data of the entries written so far
+/// so that entries with the same data can be written as aliases.
+pub(crate) struct Dedupe {
+    enabled: bool,
+    seen: HashMap<Vec<u8>, String>,
+}
+
+impl Dedupe {
+    pub(crate) fn new(enabled: bool) -> Dedupe {
+        Dedupe {
+            enabled,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Returns the name of an earlier entry with the same data and
+    /// format, otherwise the entry is remembered and `None` is returned.
+    /// Always returns `None` if deduplication is not enabled.
+    pub(crate) fn insert(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        format: EntryFormat,
+    ) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update([format.tag()]);
+        hasher.update(data);
+        let hash = hasher.finalize().to_vec();
+
+        match self.seen.get(&hash) {
+            Some(target) => Some(target.clone()),
+            None => {
+                self.seen.insert(hash, name.to_owned());
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Delta frames that only store the region changed since the previous entry.
+//!
+//! The data of a delta entry is the position of the changed region as
+//! two little endian `u32`, x and y, followed by the region as a RGBA
+//! PNG image. Empty data means that nothing changed.
+
+use crate::{decode_error, encode_png, prepare_image, EncodeOptions, SfaError};
+use image::{ColorType, DynamicImage, ImageFormat, RgbaImage};
+
+/// Remembers the previous image while encoding so that the next
+/// one can be stored as a delta of it.
+pub(crate) struct Delta {
+    enabled: bool,
+    previous: Option<(RgbaImage, ColorType)>,
+}
+
+impl Delta {
+    pub(crate) fn new(enabled: bool) -> Delta {
+        Delta {
+            enabled,
+            previous: None,
+        }
+    }
+
+    /// Returns the data of a delta entry for the image relative to the
+    /// previous one, or `None` if it has to be stored in full because
+    /// there is no previous image, the dimensions or color types differ,
+    /// the whole image changed or it has more than 8 bits per channel.
+    /// Always returns `None` if delta frames are not enabled or images
+    /// are trimmed, since delta entries have no trim of their own.
+    pub(crate) fn encode(
+        &mut self,
+        im: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Option<Vec<u8>>, SfaError> {
+        if !self.enabled || options.trim_transparent {
+            return Ok(None);
+        }
+
+        let im = prepare_image(im, options);
+        let current = im.to_rgba8();
+        let data = match &self.previous {
+            Some((previous, color))
+                if is_8_bit(&im)
+                    && *color == im.color()
+                    && previous.dimensions() == current.dimensions() =>
+            {
+                encode_region(previous, &current, options)?
+            }
+            _ => None,
+        };
+        self.previous = Some((current, im.color()));
+
+        Ok(data)
+    }
+
+    /// Forget the previous image, so that the next one is stored in full.
+    pub(crate) fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+/// Delta entries are reconstructed as 8 bit RGBA, so only images
+/// that fit into it without losing precision are stored as deltas.
+fn is_8_bit(im: &DynamicImage) -> bool {
+    matches!(
+        im,
+        DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_)
+            | DynamicImage::ImageBgr8(_)
+            | DynamicImage::ImageBgra8(_)
+    )
+}
+
+/// Encode the bounding box of the pixels that differ between the images.
+fn encode_region(
+    previous: &RgbaImage,
+    current: &RgbaImage,
+    options: &EncodeOptions,
+) -> Result<Option<Vec<u8>>, SfaError> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in current.enumerate_pixels() {
+        if pixel != previous.get_pixel(x, y) {
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+    }
+
+    let (left, top, right, bottom) = match bounds {
+        Some(bounds) => bounds,
+        // Nothing changed
+        None => return Ok(Some(vec![])),
+    };
+    let (width, height) = (right - left + 1, bottom - top + 1);
+    if (width, height) == current.dimensions() {
+        return Ok(None);
+    }
+
+    let region = RgbaImage::from_fn(width, height, |x, y| *current.get_pixel(left + x, top + y));
+    let mut data = vec![];
+    data.extend_from_slice(&left.to_le_bytes());
+    data.extend_from_slice(&top.to_le_bytes());
+    data.extend_from_slice(&encode_png(&DynamicImage::ImageRgba8(region), options)?);
+
+    Ok(Some(data))
+}
+
+/// Reconstruct the image of a delta entry from its data and the image
+/// of the entry before it. The image has the color type of the entry
+/// before it if that has 8 bits per channel, otherwise it is RGBA.
+/// `offset` is the position of the data in the file, for the errors.
+pub(crate) fn apply(
+    name: &str,
+    offset: u64,
+    previous: &DynamicImage,
+    data: &[u8],
+) -> Result<DynamicImage, SfaError> {
+    let mut frame = previous.to_rgba8();
+    if data.is_empty() {
+        return Ok(like(previous, frame));
+    }
+
+    if data.len() < 8 {
+        return Err(SfaError::InvalidDelta(name.to_owned()));
+    }
+    let (position, png) = data.split_at(8);
+    let left = u32::from_le_bytes(position[..4].try_into().unwrap());
+    let top = u32::from_le_bytes(position[4..].try_into().unwrap());
+    let region = image::load_from_memory_with_format(png, ImageFormat::Png)
+        .map_err(|e| decode_error(name, offset, e.into()))?
+        .to_rgba8();
+
+    let (width, height) = frame.dimensions();
+    let (region_width, region_height) = region.dimensions();
+    let fits = |start: u32, size: u32, limit: u32| matches!(start.checked_add(size), Some(end) if end <= limit);
+    if !fits(left, region_width, width) || !fits(top, region_height, height) {
+        return Err(SfaError::InvalidDelta(name.to_owned()));
+    }
+    image::imageops::replace(&mut frame, &region, left, top);
+
+    Ok(like(previous, frame))
+}
+
+/// Convert a reconstructed image to the color type of the previous
+/// image. Encoding only writes delta entries for images of the same
+/// color type, so nothing is lost.
+fn like(previous: &DynamicImage, frame: RgbaImage) -> DynamicImage {
+    let frame = DynamicImage::ImageRgba8(frame);
+    match previous {
+        DynamicImage::ImageLuma8(_) => DynamicImage::ImageLuma8(frame.to_luma8()),
+        DynamicImage::ImageLumaA8(_) => DynamicImage::ImageLumaA8(frame.to_luma_alpha8()),
+        DynamicImage::ImageRgb8(_) => DynamicImage::ImageRgb8(frame.to_rgb8()),
+        DynamicImage::ImageBgr8(_) => DynamicImage::ImageBgr8(frame.to_bgr8()),
+        DynamicImage::ImageBgra8(_) => DynamicImage::ImageBgra8(frame.to_bgra8()),
+        _ => frame,
+    }
+}
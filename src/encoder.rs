@@ -0,0 +1,229 @@
+//! Encoding of sfa files configured with chained method calls.
+
+#[cfg(feature = "fs")]
+use crate::{check_input_files, entry_name};
+use crate::{
+    check_not_empty, check_unique_names, CompressionLevel, EncodeOptions, SfaBuilder, SfaError,
+    StorageFormat,
+};
+#[cfg(feature = "fs")]
+use std::fs;
+#[cfg(feature = "fs")]
+use std::io;
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+/// An input of `sfa::SfaEncoder`.
+enum Input {
+    /// An image file, whose entry is named when it is written so that
+    /// `SfaEncoder::keep_paths` applies no matter when it is set.
+    #[cfg(feature = "fs")]
+    File(PathBuf),
+    /// An in memory image and the name of its entry.
+    Image(String, image::DynamicImage),
+}
+
+/// Collects the options and the entries of a sfa file with chained
+/// method calls and writes the file at the end, which reads better than
+/// filling in `sfa::EncodeOptions` when only a few options are set.
+/// The options that have no method of their own are set with
+/// `SfaEncoder::options`.
+///
+/// Unlike `sfa::SfaBuilder`, which this writes the file with, nothing
+/// is written until `SfaEncoder::write_to` or `SfaEncoder::save` is
+/// called. The names of the entries are checked before that, so nothing
+/// is written if two entries have the same name or an input file is
+/// missing.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_with_metadata, CompressionLevel, SfaEncoder};
+///
+/// let frame = std::env::temp_dir().join("sfa_encoder.png");
+/// DynamicImage::new_rgba8(4, 4).save(&frame).unwrap();
+///
+/// let archive = SfaEncoder::new()
+///     .compression(CompressionLevel::Best)
+///     .dedupe(true)
+///     .metadata("author", "xcodz")
+///     .add_file(&frame)
+///     .add_image("walk_2.png", DynamicImage::new_rgba8(8, 8))
+///     .write_to(vec![])
+///     .unwrap();
+///
+/// let (decoded, metadata) = decode_with_metadata(&mut archive.as_slice()).unwrap();
+/// let mut names: Vec<&str> = decoded.keys().map(String::as_str).collect();
+/// names.sort();
+/// assert_eq!(names, ["sfa_encoder.png", "walk_2.png"]);
+/// assert_eq!(metadata["author"], "xcodz");
+/// ```
+pub struct SfaEncoder {
+    options: EncodeOptions,
+    inputs: Vec<Input>,
+}
+
+impl SfaEncoder {
+    /// Create a new encoder with the default options and no entries.
+    pub fn new() -> SfaEncoder {
+        SfaEncoder {
+            options: EncodeOptions::default(),
+            inputs: vec![],
+        }
+    }
+
+    /// Replace all the options, including the ones set by the other
+    /// methods before this one.
+    pub fn options(mut self, options: EncodeOptions) -> SfaEncoder {
+        self.options = options;
+        self
+    }
+
+    /// Set `EncodeOptions::format`.
+    pub fn format(mut self, format: StorageFormat) -> SfaEncoder {
+        self.options.format = format;
+        self
+    }
+
+    /// Set `EncodeOptions::compression`.
+    pub fn compression(mut self, compression: CompressionLevel) -> SfaEncoder {
+        self.options.compression = compression;
+        self
+    }
+
+    /// Set `EncodeOptions::dedupe`.
+    pub fn dedupe(mut self, dedupe: bool) -> SfaEncoder {
+        self.options.dedupe = dedupe;
+        self
+    }
+
+    /// Set `EncodeOptions::keep_paths` for the entries of input files.
+    pub fn keep_paths(mut self, keep_paths: bool) -> SfaEncoder {
+        self.options.keep_paths = keep_paths;
+        self
+    }
+
+    /// Add a pair to `EncodeOptions::metadata`, replacing the value of
+    /// the key if it was added before.
+    pub fn metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> SfaEncoder {
+        self.options.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Add the image of the input file as an entry. The entry is named
+    /// like the entries of `sfa::encode`, after the file name of the
+    /// path unless `SfaEncoder::keep_paths` is set.
+    #[cfg(feature = "fs")]
+    pub fn add_file<P: AsRef<Path>>(mut self, path: P) -> SfaEncoder {
+        self.inputs.push(Input::File(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Add the image as an entry with the given name.
+    pub fn add_image(mut self, name: &str, im: image::DynamicImage) -> SfaEncoder {
+        self.inputs.push(Input::Image(name.to_owned(), im));
+        self
+    }
+
+    /// Write the sfa file to the writer and return the writer, which
+    /// is flushed.
+    ///
+    /// # Errors
+    ///
+    /// * No entries were added and `EncodeOptions::allow_empty` is not
+    ///   set. (`SfaError::EmptyInput`)
+    /// * Two entries have the same name. (`SfaError::DuplicateName`)
+    /// * An input file is missing, not a regular file or not a valid
+    ///   image. (`SfaError::InputEntry` with the path of the file)
+    /// * Encoding an image failed. (`SfaError::Image`)
+    /// * Writing to the writer was unsuccessful. (`SfaError::Io`)
+    ///
+    /// Nothing is written if there are no entries, two entries have the
+    /// same name or an input file is missing.
+    pub fn write_to<W: Write>(self, writer: W) -> Result<W, SfaError> {
+        let names = self.check()?;
+        self.write_entries(names, writer)
+    }
+
+    /// Write the sfa file to disk. The file is not created if there
+    /// are no entries, two entries have the same name or an input file
+    /// is missing.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same as those of `SfaEncoder::write_to`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use sfa::{SfaEncoder, StorageFormat};
+    ///
+    /// SfaEncoder::new()
+    ///     .format(StorageFormat::Png)
+    ///     .add_file("sp1.png")
+    ///     .add_file("sp2.png")
+    ///     .save("sp.sfa")
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "fs")]
+    pub fn save<P: AsRef<Path>>(self, path: P) -> Result<(), SfaError> {
+        let names = self.check()?;
+        let file = fs::File::create(path)?;
+        self.write_entries(names, io::BufWriter::new(file))?;
+
+        Ok(())
+    }
+
+    /// Get the names of the entries and check everything that can be
+    /// checked before anything is written.
+    fn check(&self) -> Result<Vec<String>, SfaError> {
+        check_not_empty(self.inputs.len(), &self.options)?;
+
+        let names: Vec<String> = self
+            .inputs
+            .iter()
+            .map(|input| match input {
+                #[cfg(feature = "fs")]
+                Input::File(path) => entry_name(path, &self.options),
+                Input::Image(name, _) => name.clone(),
+            })
+            .collect();
+        check_unique_names(names.iter().map(String::as_str))?;
+
+        #[cfg(feature = "fs")]
+        {
+            let files: Vec<&PathBuf> = self
+                .inputs
+                .iter()
+                .filter_map(|input| match input {
+                    Input::File(path) => Some(path),
+                    _ => None,
+                })
+                .collect();
+            check_input_files(&files)?;
+        }
+
+        Ok(names)
+    }
+
+    fn write_entries<W: Write>(self, names: Vec<String>, writer: W) -> Result<W, SfaError> {
+        let mut builder = SfaBuilder::with_options(writer, self.options)?;
+        for (name, input) in names.iter().zip(self.inputs) {
+            match input {
+                #[cfg(feature = "fs")]
+                Input::File(path) => builder.add_file(name, path)?,
+                Input::Image(_, im) => builder.add_image(name, &im)?,
+            };
+        }
+
+        builder.finish()
+    }
+}
+
+impl Default for SfaEncoder {
+    fn default() -> SfaEncoder {
+        SfaEncoder::new()
+    }
+}
@@ -0,0 +1,192 @@
+//! Formats of the image data stored in the entries of sfa files.
+
+use crate::{metadata, SfaError};
+#[cfg(feature = "qoi")]
+use image::error::{DecodingError, ImageFormatHint};
+#[cfg(feature = "qoi")]
+use image::ImageError;
+use image::ImageFormat;
+
+/// Format of the data of a single entry. Files with the `SFA;` magic
+/// only contain PNG data, from `SFA1;` onwards each entry has a tag
+/// byte after its header with one of these formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EntryFormat {
+    Png,
+    Jpeg,
+    WebP,
+    Gif,
+    Bmp,
+    Tiff,
+    Tga,
+    Ico,
+    Pnm,
+    Farbfeld,
+    Hdr,
+    Dds,
+    Avif,
+    Qoi,
+    /// Not image data but the name of an earlier entry with the same data.
+    Alias,
+    /// The region that changed since the previous entry, see `crate::delta`.
+    Delta,
+    /// An image with its transparent borders removed, see `crate::metadata`.
+    Trimmed,
+    /// Not an entry but the number of entries at the end of the file.
+    End,
+    /// Not an entry but the table of contents, see `crate::toc`.
+    Toc,
+}
+
+impl EntryFormat {
+    /// The byte written to the file for this format. These values
+    /// are part of the file format and must never change.
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            EntryFormat::Png => 0,
+            EntryFormat::Jpeg => 1,
+            EntryFormat::WebP => 2,
+            EntryFormat::Gif => 3,
+            EntryFormat::Bmp => 4,
+            EntryFormat::Tiff => 5,
+            EntryFormat::Tga => 6,
+            EntryFormat::Ico => 7,
+            EntryFormat::Pnm => 8,
+            EntryFormat::Farbfeld => 9,
+            EntryFormat::Hdr => 10,
+            EntryFormat::Dds => 11,
+            EntryFormat::Avif => 12,
+            EntryFormat::Qoi => 13,
+            EntryFormat::Alias => 128,
+            EntryFormat::Delta => 129,
+            EntryFormat::Trimmed => 130,
+            EntryFormat::End => 131,
+            EntryFormat::Toc => 132,
+        }
+    }
+
+    /// The first version of the file format that has this format.
+    pub(crate) fn min_version(self) -> u8 {
+        match self {
+            EntryFormat::Alias => 2,
+            EntryFormat::Delta => 3,
+            EntryFormat::Trimmed => 5,
+            EntryFormat::End => 7,
+            EntryFormat::Toc => 8,
+            _ => 1,
+        }
+    }
+
+    /// Get the format for a tag byte read from a file.
+    pub(crate) fn from_tag(tag: u8) -> Result<EntryFormat, SfaError> {
+        Ok(match tag {
+            0 => EntryFormat::Png,
+            1 => EntryFormat::Jpeg,
+            2 => EntryFormat::WebP,
+            3 => EntryFormat::Gif,
+            4 => EntryFormat::Bmp,
+            5 => EntryFormat::Tiff,
+            6 => EntryFormat::Tga,
+            7 => EntryFormat::Ico,
+            8 => EntryFormat::Pnm,
+            9 => EntryFormat::Farbfeld,
+            10 => EntryFormat::Hdr,
+            11 => EntryFormat::Dds,
+            12 => EntryFormat::Avif,
+            13 => EntryFormat::Qoi,
+            128 => EntryFormat::Alias,
+            129 => EntryFormat::Delta,
+            130 => EntryFormat::Trimmed,
+            131 => EntryFormat::End,
+            132 => EntryFormat::Toc,
+            _ => return Err(SfaError::UnknownFormatTag(tag)),
+        })
+    }
+
+    /// Get the format that stores data of the given `image` format.
+    /// `None` is returned for formats unknown to this version of sfa.
+    pub(crate) fn from_image_format(format: ImageFormat) -> Option<EntryFormat> {
+        Some(match format {
+            ImageFormat::Png => EntryFormat::Png,
+            ImageFormat::Jpeg => EntryFormat::Jpeg,
+            ImageFormat::WebP => EntryFormat::WebP,
+            ImageFormat::Gif => EntryFormat::Gif,
+            ImageFormat::Bmp => EntryFormat::Bmp,
+            ImageFormat::Tiff => EntryFormat::Tiff,
+            ImageFormat::Tga => EntryFormat::Tga,
+            ImageFormat::Ico => EntryFormat::Ico,
+            ImageFormat::Pnm => EntryFormat::Pnm,
+            ImageFormat::Farbfeld => EntryFormat::Farbfeld,
+            ImageFormat::Hdr => EntryFormat::Hdr,
+            ImageFormat::Dds => EntryFormat::Dds,
+            ImageFormat::Avif => EntryFormat::Avif,
+            _ => return None,
+        })
+    }
+
+    /// The `image` format used to load the data. `None` is returned
+    /// for formats that the `image` crate does not support.
+    pub(crate) fn image_format(self) -> Option<ImageFormat> {
+        Some(match self {
+            EntryFormat::Png => ImageFormat::Png,
+            EntryFormat::Jpeg => ImageFormat::Jpeg,
+            EntryFormat::WebP => ImageFormat::WebP,
+            EntryFormat::Gif => ImageFormat::Gif,
+            EntryFormat::Bmp => ImageFormat::Bmp,
+            EntryFormat::Tiff => ImageFormat::Tiff,
+            EntryFormat::Tga => ImageFormat::Tga,
+            EntryFormat::Ico => ImageFormat::Ico,
+            EntryFormat::Pnm => ImageFormat::Pnm,
+            EntryFormat::Farbfeld => ImageFormat::Farbfeld,
+            EntryFormat::Hdr => ImageFormat::Hdr,
+            EntryFormat::Dds => ImageFormat::Dds,
+            EntryFormat::Avif => ImageFormat::Avif,
+            EntryFormat::Qoi
+            | EntryFormat::Alias
+            | EntryFormat::Delta
+            | EntryFormat::Trimmed
+            | EntryFormat::End
+            | EntryFormat::Toc => return None,
+        })
+    }
+
+    /// Decode the data of an entry stored in this format.
+    pub(crate) fn load(self, data: &[u8]) -> Result<image::DynamicImage, SfaError> {
+        if self == EntryFormat::Qoi {
+            return load_qoi(data);
+        }
+
+        if self == EntryFormat::Trimmed {
+            let (_, format, data) = metadata::split_trimmed(data)?;
+            return format.load(data);
+        }
+
+        match self.image_format() {
+            Some(format) => Ok(image::load_from_memory_with_format(data, format)?),
+            // Aliases and deltas are resolved by the readers and never loaded
+            None => Err(SfaError::UnknownFormatTag(self.tag())),
+        }
+    }
+}
+
+/// Decode QOI data, which is not supported by the `image` crate.
+#[cfg(feature = "qoi")]
+fn load_qoi(data: &[u8]) -> Result<image::DynamicImage, SfaError> {
+    let format = || ImageFormatHint::Name(String::from("QOI"));
+    let (header, pixels) = qoi::decode_to_vec(data)
+        .map_err(|e| ImageError::Decoding(DecodingError::new(format(), e)))?;
+
+    let im = match header.channels {
+        qoi::Channels::Rgb => image::RgbImage::from_raw(header.width, header.height, pixels)
+            .map(image::DynamicImage::ImageRgb8),
+        qoi::Channels::Rgba => image::RgbaImage::from_raw(header.width, header.height, pixels)
+            .map(image::DynamicImage::ImageRgba8),
+    };
+
+    im.ok_or_else(|| ImageError::Decoding(DecodingError::from_format_hint(format())).into())
+}
+
+#[cfg(not(feature = "qoi"))]
+fn load_qoi(_data: &[u8]) -> Result<image::DynamicImage, SfaError> {
+    Err(SfaError::FeatureDisabled("qoi"))
+}
@@ -0,0 +1,288 @@
+//! Random access to the entries of sfa files.
+
+use crate::format::EntryFormat;
+use crate::toc::read_toc;
+use crate::{
+    alias_target, dimensions_last, encode_png, extract_last, raw_image_data, read_magic,
+    read_payload_at, seek_payload, EncodeOptions, EntryHeader, Headers, SfaError,
+};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+/// The positions of all the entries of a sfa file, for decoding
+/// single entries by name over and over without scanning the headers
+/// again each time like `sfa::extract_one` does. Only the headers are
+/// kept in memory, the data is read from the file when an entry is
+/// loaded, so this works well as the backing store of a cache.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use image::DynamicImage;
+/// use sfa::{encode_images_to_writer, SfaIndex};
+///
+/// let frames = vec![
+///     (String::from("idle_01.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("idle_02.png"), DynamicImage::new_rgba8(8, 8)),
+/// ];
+/// let mut archive = vec![];
+/// encode_images_to_writer(frames, &mut archive).unwrap();
+///
+/// let mut reader = Cursor::new(archive);
+/// let index = SfaIndex::build(&mut reader).unwrap();
+/// assert_eq!(index.names().collect::<Vec<_>>(), ["idle_01.png", "idle_02.png"]);
+///
+/// let (offset, size) = index.get("idle_02.png").unwrap();
+/// assert_eq!(&reader.get_ref()[offset as usize..][..4], b"\x89PNG");
+/// assert!(size > 0);
+///
+/// assert_eq!(index.dimensions(&mut reader, "idle_02.png").unwrap(), Some((8, 8)));
+/// for _ in 0..3 {
+///     let frame = index.load(&mut reader, "idle_02.png").unwrap().unwrap();
+///     assert_eq!(frame.to_rgba8().dimensions(), (8, 8));
+/// }
+/// assert!(index.load(&mut reader, "idle_03.png").unwrap().is_none());
+/// ```
+pub struct SfaIndex {
+    /// Headers of the entries and the positions of their data, in the
+    /// order of the file.
+    entries: Vec<(EntryHeader, u64)>,
+    /// Position in `entries` of the first entry with each name.
+    names: HashMap<String, usize>,
+}
+
+impl SfaIndex {
+    /// Scan the headers of all the entries of a sfa file and record
+    /// where their data is. The data itself is skipped by seeking.
+    ///
+    /// Files written with `EncodeOptions::table_of_contents` are not
+    /// scanned, the headers are read from the table of contents at the
+    /// end of the file instead. The file has to end where the reader
+    /// ends for it to be found.
+    ///
+    /// # Errors
+    ///
+    /// * Reading from the reader was unsuccessful. (`SfaError::Io`)
+    /// * The file does not comply with the sfa format.
+    ///   (`SfaError::MissingMagic`, `SfaError::UnsupportedVersion`,
+    ///   `SfaError::UnexpectedEof`, `SfaError::TruncatedHeader`,
+    ///   `SfaError::TrailingData`, `SfaError::InvalidSize`,
+    ///   `SfaError::InvalidName` or `SfaError::InvalidTableOfContents`)
+    /// * The format tag of an entry is not known.
+    ///   (`SfaError::UnknownFormatTag`)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use image::DynamicImage;
+    /// use sfa::{
+    ///     decode_from_slice, encode_images_to_vec, EncodeOptions, SfaBuilder, SfaError, SfaIndex,
+    /// };
+    ///
+    /// let options = EncodeOptions {
+    ///     table_of_contents: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    /// builder.add_image("idle.png", &DynamicImage::new_rgba8(4, 4)).unwrap();
+    /// builder.add_image("walk.png", &DynamicImage::new_rgba8(8, 8)).unwrap();
+    /// let archive = builder.finish().unwrap();
+    /// assert!(archive.starts_with(b"SFA9;\x0c"));
+    ///
+    /// // Scanning the file skips the table of contents
+    /// assert_eq!(decode_from_slice(&archive).unwrap().len(), 2);
+    ///
+    /// // A table of contents that is not where the end of the file says
+    /// // is rejected instead of being read from any position
+    /// let mut broken = archive.clone();
+    /// let end = broken.len();
+    /// broken[end - 8..].copy_from_slice(&u64::MAX.to_le_bytes());
+    /// let result = SfaIndex::build(&mut Cursor::new(broken));
+    /// assert!(matches!(result, Err(SfaError::InvalidTableOfContents)));
+    ///
+    /// // Files without one are scanned
+    /// let frames = vec![
+    ///     (String::from("idle.png"), DynamicImage::new_rgba8(4, 4)),
+    ///     (String::from("walk.png"), DynamicImage::new_rgba8(8, 8)),
+    /// ];
+    /// let legacy = encode_images_to_vec(frames).unwrap();
+    ///
+    /// for archive in [archive, legacy] {
+    ///     let mut reader = Cursor::new(archive);
+    ///     let index = SfaIndex::build(&mut reader).unwrap();
+    ///     assert_eq!(index.names().collect::<Vec<_>>(), ["idle.png", "walk.png"]);
+    ///     let frame = index.load(&mut reader, "walk.png").unwrap().unwrap();
+    ///     assert_eq!(frame.to_rgba8().dimensions(), (8, 8));
+    /// }
+    /// ```
+    pub fn build<R: Read + Seek>(reader: &mut R) -> Result<SfaIndex, SfaError> {
+        let start = reader.stream_position()?;
+        let layout = read_magic(reader)?;
+
+        let mut entries: Vec<(EntryHeader, u64)> = vec![];
+        if layout.table_of_contents() {
+            entries = read_toc(reader, start, layout)?;
+        } else {
+            let mut headers = Headers::new(layout);
+            while let Some(header) = headers.next(reader)? {
+                let position = reader.stream_position()?;
+                seek_payload(reader, header.size)?;
+                entries.push((header, position));
+            }
+        }
+
+        // Like `sfa::extract_one`, the first entry with a name wins
+        let mut names: HashMap<String, usize> = HashMap::new();
+        for (i, (header, _)) in entries.iter().enumerate() {
+            names.entry(header.name.clone()).or_insert(i);
+        }
+
+        Ok(SfaIndex { entries, names })
+    }
+
+    /// Decode the entry with the given name from the reader that the
+    /// index was built from. `None` is returned if there is no such entry.
+    ///
+    /// # Errors
+    ///
+    /// * Reading from the reader was unsuccessful. (`SfaError::Io`)
+    /// * The data of the entry is shorter than its size.
+    ///   (`SfaError::InvalidSize`)
+    /// * The entry is not a valid image. (`SfaError::DecodeEntry`)
+    /// * The entry is an alias or delta entry that does not fit the
+    ///   entries before it. (`SfaError::InvalidAlias`, `SfaError::InvalidDelta`
+    ///   or `SfaError::BrokenDeltaBase`)
+    pub fn load<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        name: &str,
+    ) -> Result<Option<image::DynamicImage>, SfaError> {
+        match self.names.get(name) {
+            Some(&i) => extract_last(reader, &self.entries[..=i]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Find the width and height of the entry with the given name from
+    /// the reader that the index was built from, reading only the start
+    /// of its data like `sfa::entry_dimensions` does. `None` is returned
+    /// if there is no such entry.
+    ///
+    /// # Errors
+    ///
+    /// * Reading from the reader was unsuccessful. (`SfaError::Io`)
+    /// * The entry is an alias or delta entry that does not fit the entries
+    ///   before it. (`SfaError::InvalidAlias` or `SfaError::InvalidDelta`)
+    /// * The entry had to be decoded and is not a valid image.
+    ///   (`SfaError::DecodeEntry`)
+    pub fn dimensions<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        name: &str,
+    ) -> Result<Option<(u32, u32)>, SfaError> {
+        match self.names.get(name) {
+            Some(&i) => dimensions_last(reader, &self.entries[..=i]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the stored image data of the entry with the given name from
+    /// the reader that the index was built from, without decoding it.
+    /// `None` is returned if there is no such entry. The data is the same
+    /// as `sfa::decode_raw_from_reader` returns for the entry, so delta
+    /// entries are decoded and encoded as PNG.
+    ///
+    /// # Errors
+    ///
+    /// * Reading from the reader was unsuccessful. (`SfaError::Io`)
+    /// * The data of the entry is shorter than its size.
+    ///   (`SfaError::InvalidSize`)
+    /// * The PNG data of the entry does not start with the PNG signature.
+    ///   (`SfaError::DecodeEntry`)
+    /// * The entry is an alias or delta entry that does not fit the
+    ///   entries before it. (`SfaError::InvalidAlias`, `SfaError::InvalidDelta`
+    ///   or `SfaError::BrokenDeltaBase`)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::BufReader;
+    /// use image::DynamicImage;
+    /// use sfa::{encode_images_to_vec, SfaIndex};
+    ///
+    /// let frames = vec![(String::from("idle.png"), DynamicImage::new_rgba8(4, 4))];
+    /// let path = std::env::temp_dir().join("sfa_index_load_raw.sfa");
+    /// fs::write(&path, encode_images_to_vec(frames).unwrap()).unwrap();
+    ///
+    /// let mut reader = BufReader::new(fs::File::open(&path).unwrap());
+    /// let index = SfaIndex::build(&mut reader).unwrap();
+    /// assert!(index.contains("idle.png"));
+    ///
+    /// let png = index.load_raw(&mut reader, "idle.png").unwrap().unwrap();
+    /// assert!(png.starts_with(b"\x89PNG"));
+    /// assert!(index.load_raw(&mut reader, "walk.png").unwrap().is_none());
+    /// ```
+    pub fn load_raw<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        name: &str,
+    ) -> Result<Option<Vec<u8>>, SfaError> {
+        let i = match self.names.get(name) {
+            Some(&i) => i,
+            None => return Ok(None),
+        };
+        let entries = &self.entries[..=i];
+
+        let mut buffer: Vec<u8> = vec![];
+        let (header, position) = &entries[i];
+        let (header, position) = match header.format {
+            // Delta entries do not store a whole image
+            EntryFormat::Delta => {
+                let im = extract_last(reader, entries)?;
+                return Ok(Some(encode_png(&im, &EncodeOptions::default())?));
+            }
+            EntryFormat::Alias => {
+                read_payload_at(reader, header, *position, &mut buffer)?;
+                let (earlier, position) = alias_target(&entries[..i], header, &buffer)?;
+                (earlier, position)
+            }
+            _ => (header, position),
+        };
+        read_payload_at(reader, header, *position, &mut buffer)?;
+        let (_, data) = raw_image_data(&header.name, *position, header.format, &buffer)?;
+
+        Ok(Some(data.to_vec()))
+    }
+
+    /// Whether there is an entry with the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// The position of the data of the entry with the given name from the
+    /// start of the reader, and the size of the data.
+    pub fn get(&self, name: &str) -> Option<(u64, usize)> {
+        self.names.get(name).map(|&i| {
+            let (header, position) = &self.entries[i];
+            (*position, header.size)
+        })
+    }
+
+    /// The names of the entries in the order of the file.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(header, _)| header.name.as_str())
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the file has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
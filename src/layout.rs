@@ -0,0 +1,166 @@
+//! The layout of a sfa file, which is what the magic text at its start
+//! says about the parts of the file besides the entries.
+//!
+//! Versions 1 to 8 of the file format each added a format tag or a part
+//! of the file, and have everything the versions before them have. A
+//! file of version 7 has checksums and a metadata block even if it was
+//! only written for its count of entries. Version 9 knows all the format
+//! tags and is followed by a byte of flags that turn on each of the
+//! parts on its own. Only version 9 is written, the others are only
+//! read.
+
+use crate::format::EntryFormat;
+use crate::{read_byte, SfaError};
+use std::io::{self, Read, Write};
+
+/// The newest version of the file format that can be read.
+const LATEST_VERSION: u8 = 9;
+
+/// The version of a file, and the parts that it has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Layout {
+    version: u8,
+    flags: u8,
+}
+
+impl Layout {
+    /// Entries have a CRC32 of their data after the format tag.
+    pub(crate) const CHECKSUMS: u8 = 1;
+    /// The magic text is followed by a block of key value pairs.
+    pub(crate) const METADATA: u8 = 2;
+    /// The file ends with the number of entries.
+    pub(crate) const COUNT: u8 = 4;
+    /// The number of entries is preceded by a table of contents. Only
+    /// valid along with `COUNT`, which has its position.
+    pub(crate) const TABLE_OF_CONTENTS: u8 = 8;
+
+    /// The layout of a file of a version before 9, which has all the
+    /// parts that were added up to that version.
+    pub(crate) fn legacy(version: u8) -> Layout {
+        let mut flags = 0;
+        if version >= 4 {
+            flags |= Layout::CHECKSUMS;
+        }
+        if version >= 6 {
+            flags |= Layout::METADATA;
+        }
+        if version >= 7 {
+            flags |= Layout::COUNT;
+        }
+        if version >= 8 {
+            flags |= Layout::TABLE_OF_CONTENTS;
+        }
+
+        Layout { version, flags }
+    }
+
+    /// The layout of a file of version 9 with the given flags.
+    pub(crate) fn with_flags(flags: u8) -> Layout {
+        Layout {
+            version: LATEST_VERSION,
+            flags,
+        }
+    }
+
+    /// The layout that can hold the entries and the parts of both
+    /// layouts, for files made from the entries of other files.
+    pub(crate) fn union(self, other: Layout) -> Layout {
+        match self.version.max(other.version) {
+            LATEST_VERSION => Layout::with_flags(self.flags | other.flags),
+            version => Layout::legacy(version),
+        }
+    }
+
+    /// Whether entries have a format tag after their header.
+    pub(crate) fn tags(self) -> bool {
+        self.version >= 1
+    }
+
+    /// Whether entries can have the given format. Formats added in a
+    /// later version are unknown to earlier ones.
+    pub(crate) fn knows(self, format: EntryFormat) -> bool {
+        format.min_version() <= self.version
+    }
+
+    pub(crate) fn checksums(self) -> bool {
+        self.flags & Layout::CHECKSUMS != 0
+    }
+
+    pub(crate) fn metadata(self) -> bool {
+        self.flags & Layout::METADATA != 0
+    }
+
+    pub(crate) fn count(self) -> bool {
+        self.flags & Layout::COUNT != 0
+    }
+
+    pub(crate) fn table_of_contents(self) -> bool {
+        self.flags & Layout::TABLE_OF_CONTENTS != 0
+    }
+
+    /// Write the magic text identifier. Version 0 is written as `SFA;`
+    /// like the files of the first versions of this crate, and version 9
+    /// is followed by the flags. Only files made from the entries of
+    /// other files, such as the parts of `sfa::split`, keep a version
+    /// before 9.
+    pub(crate) fn write<W: Write>(self, writer: &mut W) -> Result<(), SfaError> {
+        match self.version {
+            0 => writer.write_all(b"SFA;")?,
+            LATEST_VERSION => writer.write_all(&[b'S', b'F', b'A', b'9', b';', self.flags])?,
+            version => writer.write_all(format!("SFA{};", version).as_bytes())?,
+        }
+
+        Ok(())
+    }
+
+    /// Read and validate the magic text identifier at the start of a sfa
+    /// file. `SFA;` is version 0, later versions are written as a decimal
+    /// number before the `;`, e.g. `SFA1;`.
+    pub(crate) fn read<R: Read>(reader: &mut R) -> Result<Layout, SfaError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => SfaError::MissingMagic,
+            _ => SfaError::Io(e),
+        })?;
+
+        if &magic[..3] != b"SFA" {
+            return Err(SfaError::MissingMagic);
+        } else if magic[3] == b';' {
+            return Ok(Layout::legacy(0));
+        }
+
+        // At most 3 digits fit into a `u8`
+        let mut digits = vec![magic[3]];
+        loop {
+            match read_byte(reader)? {
+                Some(b';') => break,
+                Some(b) if digits.len() < 3 => digits.push(b),
+                _ => return Err(SfaError::MissingMagic),
+            }
+        }
+
+        let version: u8 = std::str::from_utf8(&digits)
+            .ok()
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse().ok())
+            .ok_or(SfaError::MissingMagic)?;
+
+        match version {
+            1..=8 => Ok(Layout::legacy(version)),
+            LATEST_VERSION => {
+                let flags = read_byte(reader)?.ok_or(SfaError::MissingMagic)?;
+                let known = Layout::CHECKSUMS
+                    | Layout::METADATA
+                    | Layout::COUNT
+                    | Layout::TABLE_OF_CONTENTS;
+                let layout = Layout::with_flags(flags);
+                if flags & !known != 0 || (layout.table_of_contents() && !layout.count()) {
+                    return Err(SfaError::UnknownFlags(flags));
+                }
+
+                Ok(layout)
+            }
+            _ => Err(SfaError::UnsupportedVersion(version)),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Images that are decoded when they are first needed.
+
+use crate::delta;
+use crate::format::EntryFormat;
+use crate::metadata::split_trimmed;
+use crate::{load_entry, SfaError, PNG_SIGNATURE};
+use image::{DynamicImage, GenericImageView};
+use std::cell::OnceCell;
+use std::rc::Rc;
+
+/// The image of an entry that is decoded the first time it is needed
+/// and kept after that, as returned by `sfa::decode_lazy`. Only the
+/// stored data of the entry is kept until then. Clones share the data
+/// and the decoded image, and so do aliases and the entries they refer to.
+///
+/// The decoded image is cached without locking, so `LazyImage` can not
+/// be sent to other threads. Decode the images that other threads need
+/// with `LazyImage::image` and send clones of those instead.
+#[derive(Clone)]
+pub struct LazyImage {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    name: String,
+    source: Source,
+    image: OnceCell<DynamicImage>,
+}
+
+/// What the image of an entry is decoded from.
+enum Source {
+    /// The format, offset and data of an entry that stores a whole image.
+    Entry(EntryFormat, u64, Vec<u8>),
+    /// The offset and data of a delta entry and the image of the entry
+    /// before it.
+    Delta {
+        previous: LazyImage,
+        offset: u64,
+        data: Vec<u8>,
+    },
+}
+
+impl LazyImage {
+    /// The offset of the data in the file is kept for the errors.
+    pub(crate) fn entry(
+        name: String,
+        format: EntryFormat,
+        offset: u64,
+        data: Vec<u8>,
+    ) -> LazyImage {
+        LazyImage::new(name, Source::Entry(format, offset, data))
+    }
+
+    pub(crate) fn delta(
+        name: String,
+        previous: LazyImage,
+        offset: u64,
+        data: Vec<u8>,
+    ) -> LazyImage {
+        LazyImage::new(
+            name,
+            Source::Delta {
+                previous,
+                offset,
+                data,
+            },
+        )
+    }
+
+    fn new(name: String, source: Source) -> LazyImage {
+        LazyImage {
+            inner: Rc::new(Inner {
+                name,
+                source,
+                image: OnceCell::new(),
+            }),
+        }
+    }
+
+    /// The image of the entry, which is decoded on the first call. Delta
+    /// entries also decode the entries before them that they are based on.
+    ///
+    /// # Errors
+    ///
+    /// * The entry is not a valid image. (`SfaError::DecodeEntry`)
+    /// * The entry is a delta entry that does not fit the entry before it.
+    ///   (`SfaError::InvalidDelta`)
+    ///
+    /// Nothing is cached if decoding fails, so the error is returned
+    /// again by the next call.
+    pub fn image(&self) -> Result<&DynamicImage, SfaError> {
+        if let Some(im) = self.inner.image.get() {
+            return Ok(im);
+        }
+
+        // The delta entries before this one are decoded oldest first, so
+        // that a long chain of them does not recurse deeply
+        let mut chain: Vec<&LazyImage> = vec![];
+        let mut current = self;
+        while let Source::Delta { previous, .. } = &current.inner.source {
+            if previous.inner.image.get().is_some() {
+                break;
+            }
+            chain.push(previous);
+            current = previous;
+        }
+        for lazy in chain.into_iter().rev() {
+            lazy.image()?;
+        }
+
+        let im = match &self.inner.source {
+            Source::Entry(format, offset, data) => {
+                load_entry(&self.inner.name, *offset, *format, data)?
+            }
+            Source::Delta {
+                previous,
+                offset,
+                data,
+            } => delta::apply(&self.inner.name, *offset, previous.image()?, data)?,
+        };
+
+        Ok(self.inner.image.get_or_init(|| im))
+    }
+
+    /// The width and height of the image. They are read from the header
+    /// of PNG data and from the data of trimmed entries, other formats
+    /// are decoded to find them out.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same as those of `LazyImage::image`, for the
+    /// entries that have to be decoded.
+    pub fn dimensions(&self) -> Result<(u32, u32), SfaError> {
+        // Delta entries have the dimensions of the entry before them
+        let mut current = self;
+        while let Source::Delta { previous, .. } = &current.inner.source {
+            if let Some(im) = current.inner.image.get() {
+                return Ok(im.dimensions());
+            }
+            current = previous;
+        }
+
+        if let Source::Entry(format, _, data) = &current.inner.source {
+            match format {
+                EntryFormat::Png => {
+                    if let Some(dimensions) = png_dimensions(data) {
+                        return Ok(dimensions);
+                    }
+                }
+                EntryFormat::Trimmed => {
+                    let (trim, _, _) = split_trimmed(data)?;
+                    return Ok((trim.width, trim.height));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(current.image()?.dimensions())
+    }
+
+    /// Whether the image has been decoded already.
+    pub fn is_decoded(&self) -> bool {
+        self.inner.image.get().is_some()
+    }
+}
+
+/// The width and height in the `IHDR` chunk of PNG data, which comes
+/// right after the signature.
+pub(crate) fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if !data.starts_with(PNG_SIGNATURE) || data.len() < 24 || &data[12..16] != b"IHDR" {
+        return None;
+    }
+
+    let value = |i: usize| u32::from_be_bytes(data[i..i + 4].try_into().unwrap());
+    Some((value(16), value(20)))
+}
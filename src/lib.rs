@@ -2,40 +2,410 @@
 //! it says 'Assets', It only really supports images
 //! You can use this library to encode and decode
 //! such files for, say animatable sprites. It converts
-//! the given images to PNG for storage.
+//! the given images to PNG for storage unless another
+//! format is chosen in `EncodeOptions`.
+//!
+//! # Names
+//!
+//! Input files are stored under their file name, so packing
+//! `assets/sprites/run_1.png` creates an entry named `run_1.png`
+//! and that is the key in the `HashMap` returned by `sfa::decode`.
+//! With `EncodeOptions::keep_paths` the path is stored as given
+//! instead. Functions like `sfa::encode_named` take the names
+//! explicitly.
+//!
+//! The names of the entries of an archive are unique. All the
+//! encoding functions return `SfaError::DuplicateName` instead
+//! of writing an archive with the same name more than once.
+//! Archives written by other tools can still contain duplicate
+//! names, the decoding functions that return a `HashMap` fail
+//! with `SfaError::DuplicateName` for those so that no entry is
+//! lost silently. The ones that take `sfa::DecodeOptions` keep the
+//! first or the last of them instead if `DecodeOptions::duplicates`
+//! says so. `sfa::decode_ordered` and the other functions that keep
+//! the order of the entries return all of them.
+//!
+//! ```
+//! use image::{DynamicImage, ImageOutputFormat};
+//! use sfa::{decode_from_reader, decode_from_reader_ordered, encode_to_writer, SfaError};
+//!
+//! let frame = std::env::temp_dir().join("sfa_names.png");
+//! DynamicImage::new_rgba8(4, 4).save(&frame).unwrap();
+//!
+//! let result = encode_to_writer(&[&frame, &frame], &mut vec![]);
+//! assert!(matches!(result, Err(SfaError::DuplicateName(_))));
+//!
+//! let mut png = vec![];
+//! DynamicImage::new_rgba8(4, 4)
+//!     .write_to(&mut png, ImageOutputFormat::Png)
+//!     .unwrap();
+//! let mut archive = b"SFA;".to_vec();
+//! for _ in 0..2 {
+//!     archive.extend_from_slice(format!("a.png:{}:", png.len()).as_bytes());
+//!     archive.extend_from_slice(&png);
+//! }
+//!
+//! let result = decode_from_reader(&mut archive.as_slice());
+//! assert!(matches!(result, Err(SfaError::DuplicateName(name)) if name == "a.png"));
+//! assert_eq!(decode_from_reader_ordered(&mut archive.as_slice()).unwrap().len(), 2);
+//! ```
+//!
+//! # Empty archives
+//!
+//! Encoding no images at all is most likely a mistake, like a glob
+//! that did not match any files, so all the encoding functions fail
+//! with `SfaError::EmptyInput` unless `EncodeOptions::allow_empty`
+//! is set. Archives end with the number of entries, so an empty one
+//! decodes to no images while one that was cut short right after the
+//! magic text fails with `SfaError::UnexpectedEof`. Files of the older
+//! versions without the count that are just the magic text decode to no
+//! images. A file that ends in the middle of the header of an entry is
+//! cut short and fails with `SfaError::TruncatedHeader`.
+//!
+//! ```
+//! use sfa::{decode_from_reader, encode_images_to_vec, SfaError};
+//! # use image::DynamicImage;
+//!
+//! let frames: Vec<(String, DynamicImage)> = vec![];
+//! assert!(matches!(encode_images_to_vec(frames), Err(SfaError::EmptyInput)));
+//!
+//! let result = decode_from_reader(&mut b"SFA9;\x04".as_ref());
+//! assert!(matches!(result, Err(SfaError::UnexpectedEof)));
+//! assert!(decode_from_reader(&mut b"SFA;".as_ref()).unwrap().is_empty());
+//! assert!(decode_from_reader(&mut b"SFA1;".as_ref()).unwrap().is_empty());
+//!
+//! for (truncated, read) in [(b"SFA;a.pn".as_ref(), 4), (b"SFA;a.png:", 6), (b"SFA1;a.png:3", 7)] {
+//!     let result = decode_from_reader(&mut truncated.as_ref());
+//!     assert!(matches!(result, Err(SfaError::TruncatedHeader(n)) if n == read));
+//! }
+//! ```
+//!
+//! # Trailing data
+//!
+//! Decoding never drops bytes after the last entry. Anything after the
+//! count of entries at the end of the file fails with
+//! `SfaError::TrailingData`, the number of bytes and a preview of them.
+//! In files of the older versions without the count, stray bytes that
+//! do not make up a whole header fail with `SfaError::TruncatedHeader`
+//! and the number of bytes, while bytes that happen to be a whole entry
+//! are read as one. With `DecodeOptions::strict`, the stray bytes of
+//! these files fail with `SfaError::TrailingData` too.
+//!
+//! ```
+//! use sfa::{decode_from_slice, encode_images_to_vec, SfaError};
+//! # use image::DynamicImage;
+//!
+//! let frames = vec![(String::from("a.png"), DynamicImage::new_rgba8(2, 2))];
+//! let mut archive = encode_images_to_vec(frames).unwrap();
+//! archive.extend_from_slice(b"a.png:3:abc");
+//! let result = decode_from_slice(&archive);
+//! assert!(matches!(result, Err(SfaError::TrailingData { count: 11, .. })));
+//!
+//! // A file without the count
+//! let result = decode_from_slice(b"SFA;junk");
+//! assert!(matches!(result, Err(SfaError::TruncatedHeader(4))));
+//! ```
+//!
+//! # Color types
+//!
+//! Entries stored as PNG, which is the default, decode to the same
+//! `DynamicImage` variant that was encoded, including the 16 bit ones
+//! like `ImageLuma16` and `ImageRgba16`. `ImageBgr8` and `ImageBgra8`
+//! are the only exceptions because PNG has no BGR color types, they
+//! decode to `ImageRgb8` and `ImageRgba8` with the same pixels.
+//!
+//! The following conversions lose information:
+//!
+//! * `StorageFormat::Jpeg` stores `ImageLuma8` and `ImageRgb8` only,
+//!   so other images lose their alpha channel or their 16 bit samples.
+//! * `StorageFormat::WebP`, `StorageFormat::WebPLossless` and
+//!   `StorageFormat::Qoi` store 8 bit RGB or RGBA, so gray images decode
+//!   as RGB and 16 bit samples are reduced.
+//! * `EncodeOptions::force_color` and `EncodeOptions::quantize` with
+//!   `QuantizeOptions::force` change the colors on purpose.
+//!
+//! ```
+//! use image::{DynamicImage, ImageBuffer, Luma, LumaA, Rgb, Rgba};
+//! use sfa::{decode_from_slice, encode_images_to_vec};
+//!
+//! let gradient = ImageBuffer::from_fn(4, 4, |x, _| Luma([x as u16 * 4000]));
+//! let frames = vec![
+//!     ("l16.png", DynamicImage::ImageLuma16(gradient)),
+//!     ("la16.png", DynamicImage::ImageLumaA16(ImageBuffer::from_pixel(4, 4, LumaA([1, 65535])))),
+//!     ("rgb16.png", DynamicImage::ImageRgb16(ImageBuffer::from_pixel(4, 4, Rgb([1, 2, 3])))),
+//!     ("rgba16.png", DynamicImage::ImageRgba16(ImageBuffer::from_pixel(4, 4, Rgba([1, 2, 3, 4])))),
+//!     ("l8.png", DynamicImage::ImageLuma8(ImageBuffer::from_pixel(4, 4, Luma([7])))),
+//! ];
+//! let archive = encode_images_to_vec(frames.iter().map(|(name, im)| (name, im))).unwrap();
+//!
+//! let decoded = decode_from_slice(&archive).unwrap();
+//! for (name, im) in &frames {
+//!     assert_eq!(decoded[*name].color(), im.color());
+//!     assert_eq!(decoded[*name].as_bytes(), im.as_bytes());
+//! }
+//! ```
+//!
+//! # Without a filesystem
+//!
+//! The functions that read input files or write output files by
+//! their path are only available with the `fs` feature, which is
+//! enabled by default. Without it, the crate builds for targets like
+//! `wasm32-unknown-unknown` where there is no filesystem. Archives are
+//! then written from in memory images with `sfa::encode_images_to_vec`
+//! or `sfa::SfaBuilder`, and read with `sfa::decode_from_slice` or any
+//! of the functions that take a reader.
+//!
+//! # Format
+//!
+//! A sfa file starts with the magic text `SFA;` followed by
+//! the entries one after another. Every entry is written as
+//! `name:size:` followed by `size` bytes of PNG data. Names
+//! that contain a `:` are written as `length:=name:size:`
+//! instead, where `length` is the number of bytes of the name.
+//!
+//! Later versions of the file format start with the version before the
+//! `;` instead, e.g. `SFA1;`, and versions newer than the ones known to
+//! this crate are rejected. Version 1 added a single byte after the
+//! header of each entry which tells the format of its data, e.g. `0` for
+//! PNG and `1` for JPEG, for `EncodeOptions::keep_original_format` and
+//! the `StorageFormat`s other than PNG. The size does not include this
+//! byte.
+//!
+//! ```
+//! use image::{DynamicImage, ImageOutputFormat};
+//! use sfa::{decode_from_reader, SfaError};
+//!
+//! let mut png = vec![];
+//! DynamicImage::new_rgba8(2, 2)
+//!     .write_to(&mut png, ImageOutputFormat::Png)
+//!     .unwrap();
+//!
+//! // The same entry without and with a format tag
+//! let mut v0 = format!("SFA;a.png:{}:", png.len()).into_bytes();
+//! v0.extend_from_slice(&png);
+//! let mut v1 = format!("SFA1;a.png:{}:", png.len()).into_bytes();
+//! v1.push(0);
+//! v1.extend_from_slice(&png);
+//!
+//! let old = decode_from_reader(&mut v0.as_slice()).unwrap();
+//! let new = decode_from_reader(&mut v1.as_slice()).unwrap();
+//! assert_eq!(old["a.png"].to_rgba8(), new["a.png"].to_rgba8());
+//!
+//! // Tags that are not known yet are rejected
+//! v1[format!("SFA1;a.png:{}:", png.len()).len()] = 255;
+//! let result = decode_from_reader(&mut v1.as_slice());
+//! assert!(matches!(result, Err(SfaError::UnknownFormatTag(255))));
+//!
+//! let result = decode_from_reader(&mut b"SFA200;".as_ref());
+//! assert!(matches!(result, Err(SfaError::UnsupportedVersion(200))));
+//! ```
+//!
+//! Version 2 (`SFA2;`) added the tag `128` for aliases, which
+//! `EncodeOptions::dedupe` writes. The data of an alias is
+//! the name of an earlier entry whose image it shares, which
+//! decoding resolves as if the image had been stored again.
+//!
+//! ```
+//! use image::{DynamicImage, ImageOutputFormat};
+//! use sfa::{decode_from_reader_ordered, SfaError};
+//!
+//! let mut png = vec![];
+//! DynamicImage::new_rgba8(2, 2)
+//!     .write_to(&mut png, ImageOutputFormat::Png)
+//!     .unwrap();
+//!
+//! let mut archive = format!("SFA2;a.png:{}:", png.len()).into_bytes();
+//! archive.push(0);
+//! archive.extend_from_slice(&png);
+//! archive.extend_from_slice(b"b.png:5:\x80a.png");
+//!
+//! let decoded = decode_from_reader_ordered(&mut archive.as_slice()).unwrap();
+//! assert_eq!(decoded[1].0, "b.png");
+//! assert_eq!(decoded[1].1.to_rgba8(), decoded[0].1.to_rgba8());
+//!
+//! // Aliases can only refer to earlier entries
+//! let result = decode_from_reader_ordered(&mut b"SFA2;b.png:5:\x80a.png".as_ref());
+//! assert!(matches!(result, Err(SfaError::InvalidAlias { .. })));
+//! ```
+//!
+//! Version 3 (`SFA3;`) added the tag `129` for delta entries, which
+//! `EncodeOptions::delta_frames` writes. Their data is the x and
+//! y position of the region that changed since the previous entry as
+//! little endian `u32`, followed by that region as a PNG image. The
+//! data is empty if nothing changed.
+//!
+//! Version 4 (`SFA4;`) added the CRC32 of the data of every entry as a
+//! big endian `u32` after the format tag. The size of the entry does not
+//! include it.
+//!
+//! Version 5 (`SFA5;`) added the tag `130` for trimmed entries. Their
+//! data is the size of the original image and the position of the
+//! trimmed image in it as little endian `u32`, width, height, x and y,
+//! followed by the tag and the data of the trimmed image.
+//!
+//! Version 6 (`SFA6;`) added a block of key value pairs after the magic.
+//! The block starts with its size in bytes and a `:`, so that readers can
+//! skip it, followed by the key and value of each pair as a little endian
+//! `u32` length and that many bytes of UTF-8.
+//!
+//! Version 7 (`SFA7;`) added the number of entries at the end of the
+//! file, as an entry without a name with the tag `131` whose data is the
+//! count as a little endian `u32`.
+//!
+//! Version 8 (`SFA8;`) added the tag `132` for the table of contents, an
+//! entry without a name before the count. For every entry, its data has
+//! the length of the name as a little endian `u32` and the name, the
+//! format tag, the position of the data from the start of the file and
+//! its size as little endian `u64` and the checksum. The count is followed
+//! by the position of the table of contents as a little endian `u64`, so
+//! that it is in the last 8 bytes of the file.
+//!
+//! Each of the versions 4 to 8 has everything the versions before it
+//! have, so a file of version 7 has checksums and a metadata block even
+//! if it only needed the count. All the versions are still read, but new
+//! archives are always written in version 9 (`SFA9;`). It knows all the
+//! tags and is followed by a byte of flags that turn on each part on its
+//! own: `1` for the checksums, `2` for the metadata block, `4` for the
+//! count and `8` for the table of contents, which needs the count. The
+//! count is always written, the others are turned on by
+//! `EncodeOptions::checksums`, `EncodeOptions::metadata` and
+//! `EncodeOptions::table_of_contents`. Flags that are not known fail with
+//! `SfaError::UnknownFlags`.
+//!
+//! ```
+//! use sfa::{decode_from_slice, EncodeOptions, SfaBuilder, SfaError};
+//!
+//! let options = EncodeOptions {
+//!     allow_empty: true,
+//!     ..Default::default()
+//! };
+//! let archive = SfaBuilder::with_options(vec![], options).unwrap().finish().unwrap();
+//! // Only the count, without checksums or a metadata block
+//! assert_eq!(archive, b"SFA9;\x04:4:\x83\x00\x00\x00\x00");
+//! assert!(decode_from_slice(&archive).unwrap().is_empty());
+//!
+//! for flags in [b"SFA9;\x10", b"SFA9;\x08"] {
+//!     let result = decode_from_slice(flags);
+//!     assert!(matches!(result, Err(SfaError::UnknownFlags(_))));
+//! }
+//! ```
 
 #![allow(dead_code)]
 
-use image::{ImageFormat, ImageOutputFormat};
-use std::collections::HashMap;
+mod builder;
+mod chunks;
+mod container;
+mod dedupe;
+mod delta;
+mod encoder;
+mod format;
+mod index;
+mod layout;
+mod lazy;
+mod metadata;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod options;
+mod orientation;
+mod palette;
+mod png;
+mod reader;
+mod spill;
+mod toc;
+
+/// The version of the `image` crate that the images of this crate are
+/// from. Using it instead of depending on `image` directly makes sure that
+/// the types match.
+///
+/// ```
+/// use sfa::image::DynamicImage;
+/// use sfa::{decode_from_slice, encode_images_to_vec};
+///
+/// let frames = vec![(String::from("idle.png"), DynamicImage::new_rgba8(4, 4))];
+/// let archive = encode_images_to_vec(frames).unwrap();
+/// let decoded: DynamicImage = decode_from_slice(&archive).unwrap().remove("idle.png").unwrap();
+/// assert_eq!(decoded.to_rgba8().dimensions(), (4, 4));
+/// ```
+pub use image;
+
+pub use builder::SfaBuilder;
+pub use container::FromSfaEntries;
+pub use encoder::SfaEncoder;
+pub use index::SfaIndex;
+pub use lazy::LazyImage;
+pub use metadata::{EntryMetadata, Trim};
+#[cfg(feature = "mmap")]
+pub use mmap::SfaMmap;
+pub use options::{
+    ColorTarget, CompressionLevel, DecodeOptions, DuplicatePolicy, EncodeOptions, ErrorPolicy,
+    MetadataPolicy, PngFilter, QuantizeOptions, ResizeFilter, ResizeSpec, StorageFormat,
+};
+pub use reader::{DecodeWarning, EntryAction, Frames, SfaEntries, SfaReader};
+
+use dedupe::Dedupe;
+use delta::Delta;
+use format::EntryFormat;
+use layout::Layout;
+use reader::AliasTargets;
+use spill::SpillBuffer;
+use toc::Positioned;
+#[cfg(feature = "fs")]
+use toc::Toc;
+
+use image::codecs::png::PngEncoder;
+use image::error::{DecodingError, ImageFormatHint};
+use image::{GenericImageView, ImageFormat, ImageOutputFormat};
+use std::borrow::{Borrow, Cow};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{self, Read, Write};
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
+/// The first eight bytes of every PNG file.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Number of bytes after the end of a file that `SfaError::TrailingData`
+/// keeps.
+const TRAILING_PREVIEW: usize = 16;
+
 /// Encode the given input image files
-/// into a sfa file. sfa file stores the images
-/// in PNG format only thus you might loose some
-/// quality while saving in JPEG or lossfully
-/// formatted images. Thus using a lossless format
-/// for input is recommended.
+/// into a sfa file. The images are converted to
+/// PNG, so lossy inputs like JPEG are stored with
+/// the quality they were decoded at and grow in size.
+/// Use `sfa::encode_with_options` to store them as
+/// they are with `EncodeOptions::keep_original_format`
+/// or in another `StorageFormat`. The entries are
+/// written in the same order as `input_files`.
+///
+/// Please refer to `sfa::encode_to_writer` for more insight because
+/// this is a wrapper over that function.
 ///
 /// # Arguments
 ///
-/// * `input_files` - It is a reference to a slice of `&str` objects.
+/// * `input_files` - Anything that can be iterated over to get Path-like
+///   objects, such as `&[&str]`, `Vec<String>` or `Vec<PathBuf>`. The
+///   file name of each path is used as the name of the entry, see
+///   `EncodeOptions::keep_paths` for keeping the whole path. Names
+///   that are not valid UTF-8 have the invalid parts replaced with `�`.
 /// * `output_file` - It is a file that is created and written to.
 ///   Regardless of file extension the output format is always SFA.
 ///   It accepts any Path-like object as `output_file` path.
 ///
 /// # Errors
 ///
-/// The errors are dynamic and are generated in one of the
-/// following conditions.
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
 ///
 /// * If the file can not be created due to some OS Error
 /// * If buffered writer fails to write some information due
 ///   to some OS error.
+/// * If there are no input files (`SfaError::EmptyInput`).
+/// * If two input files have the same name (`SfaError::DuplicateName`).
 /// * If provided input_files are not valid images or if there
-///   is some error while reading them.
+///   is some error while reading them. The error is wrapped in
+///   `SfaError::InputEntry` with the path of the failing file and
+///   nothing is written if the file is missing or not a regular file.
 /// * If there are problems with writing the image in PNG format
 ///   to an in memory buffer.
 ///
@@ -48,198 +418,5463 @@ use std::path::Path;
 ///
 /// encode(&my_sprite, "sp.sfa");
 /// ```
-pub fn encode<T: AsRef<Path>>(
-    input_files: &[&str],
+///
+/// Paths from `std::fs::read_dir` can be used directly
+///
+/// ```no_run
+/// use std::fs;
+/// use std::path::PathBuf;
+/// use sfa::encode;
+///
+/// let frames: Vec<PathBuf> = fs::read_dir("frames")
+///     .unwrap()
+///     .map(|entry| entry.unwrap().path())
+///     .collect();
+///
+/// encode(&frames, "frames.sfa").unwrap();
+/// ```
+///
+/// So can owned names, without converting them to `&str` first
+///
+/// ```no_run
+/// use sfa::encode;
+///
+/// let frames: Vec<String> = (1..=3).map(|i| format!("walk_{}.png", i)).collect();
+///
+/// encode(frames, "walk.sfa").unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn encode<I, P, T>(input_files: I, output_file: T) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    encode_with_options(input_files, output_file, &EncodeOptions::default())
+}
+
+/// Encode the given input image files into a sfa file using the
+/// given options. This is the same as `sfa::encode` except that the
+/// way the images are written can be customized, for example the
+/// compression level of the PNG data.
+///
+/// # Arguments
+///
+/// * `input_files` - Anything that can be iterated over to get Path-like
+///   objects, such as `&[&str]`, `Vec<String>` or `Vec<PathBuf>`. The
+///   file name of each path is used as the name of the entry, see
+///   `EncodeOptions::keep_paths` for keeping the whole path. Names
+///   that are not valid UTF-8 have the invalid parts replaced with `�`.
+/// * `output_file` - It is a file that is created and written to.
+///   Regardless of file extension the output format is always SFA.
+///   It accepts any Path-like object as `output_file` path.
+/// * `options` - A reference to `sfa::EncodeOptions`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If the file can not be created due to some OS Error
+/// * If buffered writer fails to write some information due
+///   to some OS error.
+/// * If there are no input files and `EncodeOptions::allow_empty` is not set
+///   (`SfaError::EmptyInput`).
+/// * If two input files have the same name (`SfaError::DuplicateName`).
+/// * If provided input_files are not valid images or if there
+///   is some error while reading them. The error is wrapped in
+///   `SfaError::InputEntry` with the path of the failing file and
+///   nothing is written if the file is missing or not a regular file.
+/// * If there are problems with writing the image in the format
+///   chosen by `options` to an in memory buffer.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::{encode_with_options, CompressionLevel, EncodeOptions};
+///
+/// let options = EncodeOptions {
+///     compression: CompressionLevel::Best,
+///     ..Default::default()
+/// };
+///
+/// encode_with_options(&["sp1.png", "sp2.png"], "sp.sfa", &options).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn encode_with_options<I, P, T>(
+    input_files: I,
     output_file: T,
-) -> Result<(), Box<dyn std::error::Error>> {
+    options: &EncodeOptions,
+) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    // Nothing is created if there is nothing to write
+    let input_files: Vec<P> = input_files.into_iter().collect();
+    check_not_empty(input_files.len(), options)?;
+
     let file = fs::File::create(output_file)?;
     let mut file_writer = io::BufWriter::new(file);
 
-    file_writer.write_all(b"SFA;")?;
+    encode_to_writer_with_options(input_files, &mut file_writer, options)?;
+    file_writer.flush()?;
 
-    for x in input_files {
-        let im = image::open(*x)?;
+    Ok(())
+}
+
+/// Encode the given input image files into a writer object
+/// that implements the trait `std::io::Write`. You might want
+/// to use this function for writing to TcpStreams or some in
+/// memory buffer. The writer is not flushed, so in case of
+/// buffered writers you have to flush it yourself.
+///
+/// Most of the times you only want to write to disk and is thus
+/// recommended to use `sfa::encode` instead for convenience.
+///
+/// # Arguments
+///
+/// * `input_files` - Anything that can be iterated over to get Path-like
+///   objects, such as `&[&str]`, `Vec<String>` or `Vec<PathBuf>`. The
+///   file name of each path is used as the name of the entry, see
+///   `EncodeOptions::keep_paths` for keeping the whole path. Names
+///   that are not valid UTF-8 have the invalid parts replaced with `�`.
+/// * `writer` - An object that implements the trait `io::Write`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If the writer fails to write some information due
+///   to some OS error.
+/// * If there are no input files (`SfaError::EmptyInput`).
+/// * If two input files have the same name (`SfaError::DuplicateName`).
+///   Nothing is written in this case.
+/// * If provided input_files are not valid images or if there
+///   is some error while reading them. The error is wrapped in
+///   `SfaError::InputEntry` with the path of the failing file and
+///   nothing is written if the file is missing or not a regular file.
+/// * If there are problems with writing the image in PNG format
+///   to an in memory buffer.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use image::DynamicImage;
+/// use sfa::{decode_from_reader, encode_to_writer};
+///
+/// let frame = std::env::temp_dir().join("sfa_encode_to_writer.png");
+/// DynamicImage::new_rgba8(4, 4).save(&frame).unwrap();
+/// let frame = frame.to_str().unwrap();
+///
+/// let mut archive = Cursor::new(Vec::new());
+/// encode_to_writer(&[frame], &mut archive).unwrap();
+///
+/// archive.set_position(0);
+/// let decoded = decode_from_reader(&mut archive).unwrap();
+/// assert!(decoded.contains_key("sfa_encode_to_writer.png"));
+/// ```
+#[cfg(feature = "fs")]
+pub fn encode_to_writer<I, P, W>(input_files: I, writer: &mut W) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    W: Write,
+{
+    encode_to_writer_with_options(input_files, writer, &EncodeOptions::default())
+}
 
-        let mut temprorary_buffer: Vec<u8> = vec![];
-        let mut temprorary_buffer_file = io::Cursor::new(&mut temprorary_buffer);
-        let mut temprorary_buffer_file_writer = io::BufWriter::new(&mut temprorary_buffer_file);
+/// Encode the given input image files into a writer object using the
+/// given options. This is the same as `sfa::encode_to_writer` except
+/// that the way the images are written can be customized.
+///
+/// # Arguments
+///
+/// * `input_files` - Anything that can be iterated over to get Path-like
+///   objects, such as `&[&str]`, `Vec<String>` or `Vec<PathBuf>`.
+/// * `writer` - An object that implements the trait `io::Write`.
+/// * `options` - A reference to `sfa::EncodeOptions`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If the writer fails to write some information due
+///   to some OS error.
+/// * If there are no input files and `EncodeOptions::allow_empty` is not set
+///   (`SfaError::EmptyInput`).
+/// * If two input files have the same name (`SfaError::DuplicateName`).
+///   Nothing is written in this case.
+/// * If provided input_files are not valid images or if there
+///   is some error while reading them. The error is wrapped in
+///   `SfaError::InputEntry` with the path of the failing file and
+///   nothing is written if the file is missing or not a regular file.
+/// * If there are problems with writing the image in the format
+///   chosen by `options` to an in memory buffer.
+///
+/// # Examples
+///
+/// Higher compression produces smaller archives
+///
+/// ```
+/// use image::{DynamicImage, Rgb, RgbImage};
+/// use sfa::{encode_to_writer_with_options, CompressionLevel, EncodeOptions};
+///
+/// let gradient = RgbImage::from_fn(256, 256, |x, y| Rgb([x as u8, y as u8, 128]));
+/// let frame = std::env::temp_dir().join("sfa_compression_gradient.png");
+/// DynamicImage::ImageRgb8(gradient).save(&frame).unwrap();
+///
+/// let mut fast = vec![];
+/// let options = EncodeOptions {
+///     compression: CompressionLevel::Fast,
+///     ..Default::default()
+/// };
+/// encode_to_writer_with_options(&[&frame], &mut fast, &options).unwrap();
+///
+/// let mut best = vec![];
+/// let options = EncodeOptions {
+///     compression: CompressionLevel::Best,
+///     ..Default::default()
+/// };
+/// encode_to_writer_with_options(&[&frame], &mut best, &options).unwrap();
+///
+/// assert!(best.len() <= fast.len());
+/// ```
+///
+/// Failures name the input file that caused them
+///
+/// ```
+/// use sfa::{encode_to_writer_with_options, EncodeOptions, SfaError};
+///
+/// let directory = std::env::temp_dir();
+/// let mut archive = vec![];
+/// let result = encode_to_writer_with_options(&[&directory], &mut archive, &EncodeOptions::default());
+///
+/// match result {
+///     Err(SfaError::InputEntry { name, .. }) => assert_eq!(name, directory.to_string_lossy()),
+///     _ => panic!("expected the directory to be rejected"),
+/// }
+/// assert!(archive.is_empty());
+/// ```
+#[cfg(feature = "fs")]
+pub fn encode_to_writer_with_options<I, P, W>(
+    input_files: I,
+    writer: &mut W,
+    options: &EncodeOptions,
+) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    W: Write,
+{
+    encode_to_writer_with_progress(input_files, writer, options, |_, _| {})
+}
 
-        // Write data in PNG format to a Vec<u8>
-        im.write_to(&mut temprorary_buffer_file_writer, ImageOutputFormat::Png)?;
+/// Encode the given input image files into a sfa file and report the
+/// progress after each entry is written, for example to show a
+/// progress bar while packing hundreds of frames.
+///
+/// Please refer to `sfa::encode_to_writer_with_progress` for more
+/// insight because this is a wrapper over that function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::{encode_with_progress, EncodeOptions};
+///
+/// let frames: Vec<String> = (1..=500).map(|i| format!("frame_{}.png", i)).collect();
+/// encode_with_progress(&frames, "sp.sfa", &EncodeOptions::default(), |done, total| {
+///     eprint!("\rpacked {}/{}", done, total);
+/// })
+/// .unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn encode_with_progress<I, P, T, F>(
+    input_files: I,
+    output_file: T,
+    options: &EncodeOptions,
+    on_progress: F,
+) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    T: AsRef<Path>,
+    F: FnMut(usize, usize),
+{
+    let input_files: Vec<P> = input_files.into_iter().collect();
+    check_not_empty(input_files.len(), options)?;
 
-        // Keep the memory signature as low as possible so drop all unused things
-        drop(temprorary_buffer_file_writer);
-        drop(temprorary_buffer_file);
-        drop(im);
+    let file = fs::File::create(output_file)?;
+    let mut file_writer = io::BufWriter::new(file);
 
-        // Write the size of the data as well as name of the file with the data itself
-        file_writer.write_all(format!("{}:{}:", x, temprorary_buffer.len()).as_bytes())?;
-        file_writer.write_all(&temprorary_buffer)?;
-    }
+    encode_to_writer_with_progress(input_files, &mut file_writer, options, on_progress)?;
     file_writer.flush()?;
 
     Ok(())
 }
 
-/// Decodes an SFA file from disk and returns a result of dynamic Error
-/// or the expected `HashMap<String, image::DynamicImage>`. The values
-/// can then be converted for use with other graphics generating libraries.
-///
-/// Please refer to `sfa::decode_from_reader` for more insight because
-/// this is a wrapper over that function.
+/// Encode the given input image files into a writer object using the
+/// given options and report the progress after each entry is written.
+/// This is the same as `sfa::encode_to_writer_with_options` otherwise.
 ///
 /// # Arguments
 ///
-/// * `file` - A path like object that refers to the file to read for decoding. (`AsRef<Path>`)
+/// * `input_files` - Anything that can be iterated over to get Path-like
+///   objects, such as `&[&str]`, `Vec<String>` or `Vec<PathBuf>`.
+/// * `writer` - An object that implements the trait `io::Write`.
+/// * `options` - A reference to `sfa::EncodeOptions`.
+/// * `on_progress` - Called with the number of entries written so far
+///   and the total number of entries, after each entry.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::encode_to_writer_with_options`.
 ///
 /// # Examples
 ///
-/// ```no_run
-/// # use std::collections::HashMap;
-/// use sfa::decode;
+/// ```
 /// use image::DynamicImage;
+/// use sfa::{encode_to_writer_with_progress, EncodeOptions};
 ///
-/// let my_sprite: HashMap<String, DynamicImage> = decode("sp.sfa").unwrap();
+/// let mut frames = vec![];
+/// for i in 1..=3 {
+///     let frame = std::env::temp_dir().join(format!("sfa_progress_{}.png", i));
+///     DynamicImage::new_rgba8(4, 4).save(&frame).unwrap();
+///     frames.push(frame);
+/// }
 ///
-/// let my_sprite_frame_1 = &my_sprite["sp1.png"];
+/// let mut calls = vec![];
+/// let mut archive = vec![];
+/// let options = EncodeOptions::default();
+/// encode_to_writer_with_progress(&frames, &mut archive, &options, |done, total| {
+///     calls.push((done, total))
+/// })
+/// .unwrap();
+///
+/// assert_eq!(calls, [(1, 3), (2, 3), (3, 3)]);
 /// ```
-pub fn decode<P: AsRef<Path>>(
-    file: P,
-) -> Result<HashMap<String, image::DynamicImage>, Box<dyn std::error::Error>> {
-    let file = fs::File::open(file)?;
-    let mut file = io::BufReader::new(file);
+#[cfg(feature = "fs")]
+pub fn encode_to_writer_with_progress<I, P, W, F>(
+    input_files: I,
+    writer: &mut W,
+    options: &EncodeOptions,
+    mut on_progress: F,
+) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    W: Write,
+    F: FnMut(usize, usize),
+{
+    let input_files: Vec<P> = input_files.into_iter().collect();
+    check_not_empty(input_files.len(), options)?;
+    let names = entry_names(&input_files, options)?;
+    check_input_files(&input_files)?;
 
-    decode_from_reader(&mut file)
+    let layout = options.layout();
+    let mut writer = Positioned::new(writer);
+    layout.write(&mut writer)?;
+    metadata::write_archive_metadata(&mut writer, layout, &options.metadata)?;
+
+    let mut toc = Toc::new(layout);
+    let mut dedupe = Dedupe::new(options.dedupe);
+    let mut delta = Delta::new(options.delta_frames);
+    for (i, (x, name)) in input_files.iter().zip(&names).enumerate() {
+        let header = if options.dedupe || options.delta_frames {
+            let (data, format) = encode_file_with_delta(x.as_ref(), options, &mut delta)?;
+            write_deduped_entry(&mut writer, layout, name, &data, format, &mut dedupe)?
+        } else {
+            write_file_entry(&mut writer, layout, name, x.as_ref(), options)?
+        };
+        toc.add(header, writer.position());
+        on_progress(i + 1, names.len());
+    }
+    toc.finish(&mut writer)?;
+
+    Ok(())
 }
 
-/// Decodes sfa file from a reader object that implements
-/// the trait `std::io::Read`. It returns a in memory HashMap
-/// with keys being `String` objects which are the original names
-/// of files and the values are `image::DynamicImage` which are always
-/// in PNG format. You might want to use this function for reading
-/// from TcpStreams or some in memory buffer. It does not matter
-/// if the stream is buffered or not because all the data is read
-/// in one go. So for network streams the data will be downloaded
-/// first and then processed.
+/// Encode the given input image files into a sfa file using all the
+/// cores of the machine. Only available with the `rayon` feature
+/// (also enabled by the `parallel` feature).
 ///
-/// Most of the times you only want to read from disk and is thus
-/// recommended to use `sfa::decode` instead for convenience.
+/// Please refer to `sfa::encode_to_writer_parallel` for more insight
+/// because this is a wrapper over that function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::{encode_parallel, EncodeOptions};
+///
+/// let frames: Vec<String> = (1..=500).map(|i| format!("frame_{}.png", i)).collect();
+/// encode_parallel(&frames, "sp.sfa", &EncodeOptions::default()).unwrap();
+/// ```
+#[cfg(all(feature = "fs", feature = "rayon"))]
+pub fn encode_parallel<I, P, T>(
+    input_files: I,
+    output_file: T,
+    options: &EncodeOptions,
+) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path> + Sync,
+    T: AsRef<Path>,
+{
+    let input_files: Vec<P> = input_files.into_iter().collect();
+    check_not_empty(input_files.len(), options)?;
+
+    let file = fs::File::create(output_file)?;
+    let mut file_writer = io::BufWriter::new(file);
+
+    encode_to_writer_parallel(input_files, &mut file_writer, options)?;
+    file_writer.flush()?;
+
+    Ok(())
+}
+
+/// Encode the given input image files into a writer object using all
+/// the cores of the machine. The input files are read and encoded in
+/// parallel and the entries are then written in the order of the input
+/// files, so the output is the same as with
+/// `sfa::encode_to_writer_with_options`. Only available with the
+/// `rayon` feature (also enabled by the `parallel` feature).
+///
+/// Unlike the serial functions, the encoded data of all the entries
+/// is kept in memory until it is written.
 ///
 /// # Arguments
 ///
-/// * `reader` - An object that implements the trait `io::Read`.
+/// * `input_files` - Anything that can be iterated over to get Path-like
+///   objects, such as `&[&str]`, `Vec<String>` or `Vec<PathBuf>`.
+/// * `writer` - An object that implements the trait `io::Write`.
+/// * `options` - A reference to `sfa::EncodeOptions`.
 ///
 /// # Errors
 ///
-/// Errors are dynamic and can be returned in either one of these
-/// situations.
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
 ///
-/// * Reading from the reader was unsuccessful (Maybe due to timeout).
-/// * The file does not comply with the sfa format.
+/// * If the writer fails to write some information due
+///   to some OS error.
+/// * If there are no input files and `EncodeOptions::allow_empty` is not set
+///   (`SfaError::EmptyInput`).
+/// * If two input files have the same name (`SfaError::DuplicateName`).
+///   Nothing is written in this case.
+/// * If provided input_files are not valid images or if there
+///   is some error while reading them. The error is wrapped in
+///   `SfaError::InputEntry` with the path of the failing file and
+///   nothing is written in this case.
+/// * If there are problems with writing the image in the format
+///   chosen by `options` to an in memory buffer.
 ///
 /// # Examples
 ///
-/// ```no_run
-/// # use std::collections::HashMap;
-/// # use std::fs;
-/// use std::io::Read;
+/// ```
 /// use image::DynamicImage;
-/// use sfa::decode_from_reader;
+/// use sfa::{encode_to_writer, encode_to_writer_parallel, list_entries, EncodeOptions, SfaError};
 ///
-/// let mut my_sprite_sfa_file = fs::File::open("sp1.sfa").unwrap();
+/// let frames: Vec<String> = (1..=8)
+///     .map(|i| {
+///         let path = std::env::temp_dir().join(format!("sfa_parallel_{}.png", i));
+///         DynamicImage::new_rgba8(i, i).save(&path).unwrap();
+///         path.to_str().unwrap().to_owned()
+///     })
+///     .collect();
 ///
-/// let my_sprite: HashMap<String, DynamicImage> = decode_from_reader(&mut my_sprite_sfa_file).unwrap();
+/// let mut archive = vec![];
+/// encode_to_writer_parallel(&frames, &mut archive, &EncodeOptions::default()).unwrap();
+///
+/// let names: Vec<String> = list_entries(&mut archive.as_slice())
+///     .unwrap()
+///     .into_iter()
+///     .map(|(name, _)| name)
+///     .collect();
+/// let expected: Vec<String> = (1..=8).map(|i| format!("sfa_parallel_{}.png", i)).collect();
+/// assert_eq!(names, expected);
+///
+/// // The output is byte for byte the same as the serial one
+/// let mut serial = vec![];
+/// encode_to_writer(&frames, &mut serial).unwrap();
+/// assert_eq!(archive, serial);
+///
+/// let missing = vec![String::from("sfa_parallel_missing.png")];
+/// let result = encode_to_writer_parallel(&missing, &mut vec![], &EncodeOptions::default());
+/// assert!(matches!(result, Err(SfaError::InputEntry { name, .. }) if name == missing[0]));
 /// ```
-pub fn decode_from_reader<F: Read>(
-    reader: &mut F,
-) -> Result<HashMap<String, image::DynamicImage>, Box<dyn std::error::Error>> {
-    let mut buffer: Vec<u8> = vec![];
-    reader.read_to_end(&mut buffer)?;
+#[cfg(all(feature = "fs", feature = "rayon"))]
+pub fn encode_to_writer_parallel<I, P, W>(
+    input_files: I,
+    writer: &mut W,
+    options: &EncodeOptions,
+) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path> + Sync,
+    W: Write,
+{
+    use rayon::prelude::*;
 
-    let mut results: HashMap<String, image::DynamicImage> = HashMap::new();
-    let mut consuming_iterator = buffer.into_iter();
-    let mut contents: String = String::new();
-
-    'parseloop_magic: loop {
-        match consuming_iterator.next() {
-            Some(b) => {
-                contents.push(b as char);
-                if contents == "SFA;" {
-                    break 'parseloop_magic;
-                } else if contents.len() >= 4 {
-                    return Err(Error::new(String::from(
-                        "Magic Text Identifier not found after parsing 4 letters of the file",
-                    ))
-                    .into());
-                }
-            }
-            None => {
-                return Err(Error::new(String::from(
-                    "Reached EOF before the magic string was seen.",
-                ))
-                .into())
-            }
-        }
-    }
-
-    contents = String::new();
-    let mut name = String::new();
-    let mut size: usize;
-    let mut cmode = "n";
-
-    while let Some(b) = consuming_iterator.next() {
-        contents.push(b as char);
-        if b as char == ':' && cmode == "n" {
-            name = String::from(&contents[0..contents.len() - 1]);
-            contents = String::new();
-            cmode = "s";
-        } else if b as char == ':' && cmode == "s" {
-            size = String::from(&contents[0..contents.len() - 1]).parse()?;
-            contents = String::new();
-            cmode = "n";
-            let mut im_contents: Vec<u8> = Vec::with_capacity(size);
-            for _ in 0..size {
-                match consuming_iterator.next() {
-                    Some(b) => {
-                        im_contents.push(b);
-                    }
-                    None => {
-                        return Err(Error::new(String::from(
-                            "Reached EOF before all file content was retrieved",
-                        ))
-                        .into());
-                    }
-                }
+    let input_files: Vec<P> = input_files.into_iter().collect();
+    check_not_empty(input_files.len(), options)?;
+    let names = entry_names(&input_files, options)?;
+    check_input_files(&input_files)?;
+    // Collecting an indexed parallel iterator keeps the input order
+    let entries: Vec<(Vec<u8>, EntryFormat)> = input_files
+        .par_iter()
+        .enumerate()
+        .map(|(i, x)| {
+            // Delta frames need the previous image, which every task
+            // decodes again so that they do not depend on each other
+            let mut delta = Delta::new(options.delta_frames);
+            if options.delta_frames && i > 0 {
+                delta.encode(&open_image(input_files[i - 1].as_ref(), options)?, options)?;
             }
-            let im_contents = image::load_from_memory_with_format(&im_contents, ImageFormat::Png)?;
-            results.insert(name.to_owned(), im_contents);
-        }
+            encode_file_with_delta(x.as_ref(), options, &mut delta)
+        })
+        .collect::<Result<_, _>>()?;
+
+    let layout = options.layout();
+    let mut writer = Positioned::new(writer);
+    layout.write(&mut writer)?;
+    metadata::write_archive_metadata(&mut writer, layout, &options.metadata)?;
+
+    let mut toc = Toc::new(layout);
+    let mut dedupe = Dedupe::new(options.dedupe);
+    for (name, (data, format)) in names.iter().zip(&entries) {
+        let header = write_deduped_entry(&mut writer, layout, name, data, *format, &mut dedupe)?;
+        toc.add(header, writer.position());
     }
+    toc.finish(&mut writer)?;
 
-    Ok(results)
+    Ok(())
 }
 
-/// Error for handling custom errors by this
-/// crate. This error is only returned when this
-/// crate encounter's SFA format specific errors.
+/// Encode the given input image files into an in memory sfa
+/// archive and return its bytes. This is useful for embedding
+/// sfa archives into other containers. It is the counterpart of
+/// `sfa::decode_from_slice`. Use `sfa::encode_images_to_vec` for
+/// images that are already in memory.
 ///
-/// The field contained is `s` which is a owned `String`
-/// that describes the error.
-#[derive(Debug)]
-struct Error {
-    s: String,
-}
+/// Please refer to `sfa::encode_to_writer` for more insight because
+/// this is a wrapper over that function.
+///
+/// # Arguments
+///
+/// * `input_files` - Anything that can be iterated over to get Path-like
+///   objects, such as `&[&str]`, `Vec<String>` or `Vec<PathBuf>`. The
+///   file name of each path is used as the name of the entry, see
+///   `EncodeOptions::keep_paths` for keeping the whole path. Names
+///   that are not valid UTF-8 have the invalid parts replaced with `�`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If there are no input files (`SfaError::EmptyInput`).
+/// * If two input files have the same name (`SfaError::DuplicateName`).
+///   Nothing is written in this case.
+/// * If provided input_files are not valid images or if there
+///   is some error while reading them. The error is wrapped in
+///   `SfaError::InputEntry` with the path of the failing file and
+///   nothing is written if the file is missing or not a regular file.
+/// * If there are problems with writing the image in PNG format
+///   to an in memory buffer.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_reader, encode_to_vec};
+///
+/// let frame = std::env::temp_dir().join("sfa_encode_to_vec.png");
+/// DynamicImage::new_rgba8(4, 4).save(&frame).unwrap();
+/// let frame = frame.to_str().unwrap();
+///
+/// let archive = encode_to_vec(&[frame]).unwrap();
+/// assert!(archive.starts_with(b"SFA9;"));
+///
+/// let decoded = decode_from_reader(&mut archive.as_slice()).unwrap();
+/// assert_eq!(decoded.keys().collect::<Vec<_>>(), ["sfa_encode_to_vec.png"]);
+/// ```
+#[cfg(feature = "fs")]
+pub fn encode_to_vec<I, P>(input_files: I) -> Result<Vec<u8>, SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut buffer: Vec<u8> = vec![];
+    encode_to_writer(input_files, &mut buffer)?;
 
-impl Error {
-    fn new(s: String) -> Error {
-        Error { s }
-    }
+    Ok(buffer)
 }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        write!(formatter, "{}", self.s)
-    }
+/// Encode the given input image files into a sfa file with
+/// explicitly given entry names. This is useful when the path of
+/// a file is not the name that should be stored in the archive, for
+/// example `assets/player/idle/0001.png` can be stored as `idle_1`.
+/// It also makes it possible to pack files with the same file name
+/// from different directories under distinct names.
+///
+/// # Arguments
+///
+/// * `entries` - Anything that can be iterated over to get pairs of
+///   names and Path-like objects. The first element of the pair is the
+///   name that is stored in the archive and the second element is the
+///   file that is read.
+/// * `output_file` - It is a file that is created and written to.
+///   Regardless of file extension the output format is always SFA.
+///   It accepts any Path-like object as `output_file` path.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If there are no entries (`SfaError::EmptyInput`).
+/// * If the same name is given more than once (`SfaError::DuplicateName`).
+///   This is checked before the output file is created.
+/// * If the file can not be created due to some OS Error
+/// * If buffered writer fails to write some information due
+///   to some OS error.
+/// * If provided files are not valid images or if there
+///   is some error while reading them. The error is wrapped in
+///   `SfaError::InputEntry` with the path of the failing file and
+///   nothing is written if the file is missing or not a regular file.
+/// * If there are problems with writing the image in PNG format
+///   to an in memory buffer.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use sfa::encode_named;
+///
+/// let entries = [
+///     ("idle_1", Path::new("assets/player/idle/0001.png")),
+///     ("run_1", Path::new("assets/player/run/0001.png")),
+/// ];
+///
+/// encode_named(entries, "player.sfa").unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn encode_named<I, N, P, T>(entries: I, output_file: T) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = (N, P)>,
+    N: AsRef<str>,
+    P: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    let entries: Vec<(N, P)> = entries.into_iter().collect();
+    check_not_empty(entries.len(), &EncodeOptions::default())?;
+    check_unique_names(entries.iter().map(|(name, _)| name.as_ref()))?;
+
+    let file = fs::File::create(output_file)?;
+    let mut file_writer = io::BufWriter::new(file);
+
+    encode_named_to_writer(entries, &mut file_writer)?;
+    file_writer.flush()?;
+
+    Ok(())
 }
 
-impl std::error::Error for Error {}
+/// Encode the given input image files into a writer object with
+/// explicitly given entry names. This is the writer counterpart of
+/// `sfa::encode_named`.
+///
+/// # Arguments
+///
+/// * `entries` - Anything that can be iterated over to get pairs of
+///   names and Path-like objects. The first element of the pair is the
+///   name that is stored in the archive and the second element is the
+///   file that is read.
+/// * `writer` - An object that implements the trait `io::Write`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If there are no entries (`SfaError::EmptyInput`).
+/// * If the same name is given more than once (`SfaError::DuplicateName`).
+///   This is checked before anything is written.
+/// * If the writer fails to write some information due
+///   to some OS error.
+/// * If provided files are not valid images or if there
+///   is some error while reading them. The error is wrapped in
+///   `SfaError::InputEntry` with the path of the failing file and
+///   nothing is written if the file is missing or not a regular file.
+/// * If there are problems with writing the image in PNG format
+///   to an in memory buffer.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use sfa::encode_named_to_writer;
+///
+/// let entries = [("idle_1", Path::new("assets/player/idle/0001.png"))];
+///
+/// let mut archive = vec![];
+/// encode_named_to_writer(entries, &mut archive).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn encode_named_to_writer<I, N, P, W>(entries: I, writer: &mut W) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = (N, P)>,
+    N: AsRef<str>,
+    P: AsRef<Path>,
+    W: Write,
+{
+    let entries: Vec<(N, P)> = entries.into_iter().collect();
+    check_not_empty(entries.len(), &EncodeOptions::default())?;
+    check_unique_names(entries.iter().map(|(name, _)| name.as_ref()))?;
+    check_input_files(&entries.iter().map(|(_, path)| path).collect::<Vec<_>>())?;
+
+    let layout = EncodeOptions::default().layout();
+    layout.write(writer)?;
+
+    for (name, path) in &entries {
+        let im = open_image(path.as_ref(), &EncodeOptions::default())?;
+        write_entry(
+            writer,
+            layout,
+            name.as_ref(),
+            &im,
+            &EncodeOptions::default(),
+        )?;
+    }
+    write_end(writer, layout, entries.len(), None)?;
+
+    Ok(())
+}
+
+/// Get the names of the entries of the input files and make sure that
+/// they are unique. The name is the file name of the path unless the
+/// options say to keep the whole path.
+fn entry_names<P: AsRef<Path>>(
+    input_files: &[P],
+    options: &EncodeOptions,
+) -> Result<Vec<String>, SfaError> {
+    let names: Vec<String> = input_files
+        .iter()
+        .map(|x| entry_name(x.as_ref(), options))
+        .collect();
+    check_unique_names(names.iter().map(String::as_str))?;
+
+    Ok(names)
+}
+
+/// The name of the entry of an input file, which is the file name of
+/// the path unless the options say to keep the whole path.
+fn entry_name(path: &Path, options: &EncodeOptions) -> String {
+    let name = match path.file_name() {
+        Some(file_name) if !options.keep_paths => file_name,
+        _ => path.as_os_str(),
+    };
+    name.to_string_lossy().into_owned()
+}
+
+/// Make sure that every input file exists and is a regular file before
+/// anything is written, so that a directory in a long list of input
+/// files does not leave a half written archive behind.
+fn check_input_files<P: AsRef<Path>>(input_files: &[P]) -> Result<(), SfaError> {
+    for x in input_files {
+        let x = x.as_ref();
+        let is_file = fs::metadata(x)
+            .map_err(|e| input_error(x, e.into()))?
+            .is_file();
+        if !is_file {
+            let e = io::Error::new(io::ErrorKind::InvalidInput, "Not a regular file");
+            return Err(input_error(x, e.into()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Wrap an error that occurred while reading or decoding an input file
+/// in `SfaError::InputEntry` with the path of the file.
+fn input_error(path: &Path, error: SfaError) -> SfaError {
+    SfaError::InputEntry {
+        name: path.to_string_lossy().into_owned(),
+        source: Box::new(error),
+    }
+}
+
+/// Returns `SfaError::DuplicateName` for the first name that is repeated.
+fn check_unique_names<'a, I: IntoIterator<Item = &'a str>>(names: I) -> Result<(), SfaError> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(name) {
+            return Err(SfaError::DuplicateName(name.to_owned()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Encode the given in memory images into a sfa file.
+/// This is the same as `sfa::encode` except that the images
+/// do not have to be read from disk, which is useful for
+/// procedurally generated sprites.
+///
+/// The entries are written in the order of iteration. So a `Vec`
+/// of pairs keeps the order while a `HashMap` does not have a
+/// defined order.
+///
+/// # Arguments
+///
+/// * `images` - Anything that can be iterated over to get pairs of
+///   names and `image::DynamicImage` objects (owned or borrowed), such
+///   as `Vec<(String, DynamicImage)>` or `&HashMap<String, DynamicImage>`.
+/// * `output_file` - It is a file that is created and written to.
+///   Regardless of file extension the output format is always SFA.
+///   It accepts any Path-like object as `output_file` path.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If there are no entries (`SfaError::EmptyInput`).
+/// * If the same name is given more than once (`SfaError::DuplicateName`).
+/// * If the file can not be created due to some OS Error
+/// * If buffered writer fails to write some information due
+///   to some OS error.
+/// * If there are problems with writing the image in PNG format
+///   to an in memory buffer.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use image::DynamicImage;
+/// use sfa::{decode, encode_images};
+///
+/// let mut frames = HashMap::new();
+/// frames.insert(String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4));
+/// frames.insert(String::from("frame_2.png"), DynamicImage::new_rgba8(8, 8));
+///
+/// let path = std::env::temp_dir().join("sfa_encode_images.sfa");
+/// encode_images(&frames, &path).unwrap();
+///
+/// let decoded = decode(&path).unwrap();
+/// assert_eq!(decoded.len(), 2);
+/// assert_eq!(decoded["frame_2.png"].to_rgba8(), frames["frame_2.png"].to_rgba8());
+/// ```
+///
+/// Owned pairs can be used to keep the order of the entries
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_ordered, encode_images};
+///
+/// let frames = vec![
+///     (String::from("frame_2.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4)),
+/// ];
+///
+/// let path = std::env::temp_dir().join("sfa_encode_images_ordered.sfa");
+/// encode_images(frames, &path).unwrap();
+///
+/// let decoded = decode_ordered(&path).unwrap();
+/// assert_eq!(decoded[0].0, "frame_2.png");
+/// assert_eq!(decoded[1].0, "frame_1.png");
+/// ```
+///
+/// Names can contain any character including `:` and non ASCII characters
+///
+/// ```
+/// use std::collections::HashMap;
+/// use image::DynamicImage;
+/// use sfa::{decode, encode_images};
+///
+/// let mut frames = HashMap::new();
+/// frames.insert(String::from("weird:name:1.png"), DynamicImage::new_rgba8(4, 4));
+/// frames.insert(String::from("C:\\sprites\\a.png"), DynamicImage::new_rgba8(4, 4));
+/// frames.insert(String::from("спрайт.png"), DynamicImage::new_rgba8(4, 4));
+/// frames.insert(String::from("猫.png"), DynamicImage::new_rgba8(4, 4));
+///
+/// let path = std::env::temp_dir().join("sfa_encode_images_colons.sfa");
+/// encode_images(&frames, &path).unwrap();
+///
+/// let decoded = decode(&path).unwrap();
+/// assert!(decoded.contains_key("weird:name:1.png"));
+/// assert!(decoded.contains_key("C:\\sprites\\a.png"));
+/// assert!(decoded.contains_key("спрайт.png"));
+/// assert!(decoded.contains_key("猫.png"));
+/// ```
+#[cfg(feature = "fs")]
+pub fn encode_images<I, N, M, T>(images: I, output_file: T) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = (N, M)>,
+    N: AsRef<str>,
+    M: Borrow<image::DynamicImage>,
+    T: AsRef<Path>,
+{
+    let images: Vec<(N, M)> = images.into_iter().collect();
+    check_not_empty(images.len(), &EncodeOptions::default())?;
+
+    let file = fs::File::create(output_file)?;
+    let mut file_writer = io::BufWriter::new(file);
+
+    encode_images_to_writer(images, &mut file_writer)?;
+    file_writer.flush()?;
+
+    Ok(())
+}
+
+/// Encode the given in memory images into a writer object. This is
+/// the writer counterpart of `sfa::encode_images`, which makes it
+/// possible to build archives in memory without touching the disk.
+///
+/// # Arguments
+///
+/// * `images` - Anything that can be iterated over to get pairs of
+///   names and `image::DynamicImage` objects (owned or borrowed).
+/// * `writer` - An object that implements the trait `io::Write`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If there are no entries (`SfaError::EmptyInput`).
+/// * If the same name is given more than once (`SfaError::DuplicateName`).
+///   Nothing is written in this case.
+/// * If the writer fails to write some information due
+///   to some OS error.
+/// * If there are problems with writing the image in PNG format
+///   to an in memory buffer.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_reader, encode_images_to_writer};
+///
+/// let frames = vec![(String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4))];
+///
+/// let mut archive = vec![];
+/// encode_images_to_writer(frames, &mut archive).unwrap();
+///
+/// let decoded = decode_from_reader(&mut archive.as_slice()).unwrap();
+/// assert!(decoded.contains_key("frame_1.png"));
+/// ```
+pub fn encode_images_to_writer<I, N, M, W>(images: I, writer: &mut W) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = (N, M)>,
+    N: AsRef<str>,
+    M: Borrow<image::DynamicImage>,
+    W: Write,
+{
+    let images: Vec<(N, M)> = images.into_iter().collect();
+    check_not_empty(images.len(), &EncodeOptions::default())?;
+    check_unique_names(images.iter().map(|(name, _)| name.as_ref()))?;
+
+    let layout = EncodeOptions::default().layout();
+    layout.write(writer)?;
+
+    let count = images.len();
+    for (name, im) in images {
+        write_entry(
+            writer,
+            layout,
+            name.as_ref(),
+            im.borrow(),
+            &EncodeOptions::default(),
+        )?;
+    }
+    write_end(writer, layout, count, None)?;
+
+    Ok(())
+}
+
+/// Encode the given in memory images into an in memory sfa archive
+/// and return its bytes. Nothing touches the filesystem, so this works
+/// where there is none, like in the browser with WebAssembly.
+///
+/// Please refer to `sfa::encode_images_to_writer` for more insight
+/// because this is a wrapper over that function.
+///
+/// # Arguments
+///
+/// * `images` - Anything that can be iterated over to get pairs of
+///   names and `image::DynamicImage` objects (owned or borrowed).
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If there are no images (`SfaError::EmptyInput`).
+/// * If the same name is given more than once (`SfaError::DuplicateName`).
+/// * If there are problems with writing the image in PNG format
+///   to an in memory buffer.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_slice, encode_images_to_vec};
+///
+/// let frames: &[(String, DynamicImage)] = &[
+///     (String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("frame_2.png"), DynamicImage::new_rgba8(4, 4)),
+/// ];
+///
+/// let archive = encode_images_to_vec(frames.iter().map(|(name, im)| (name, im))).unwrap();
+/// assert!(archive.starts_with(b"SFA9;"));
+///
+/// let decoded = decode_from_slice(&archive).unwrap();
+/// assert_eq!(decoded.len(), 2);
+/// ```
+pub fn encode_images_to_vec<I, N, M>(images: I) -> Result<Vec<u8>, SfaError>
+where
+    I: IntoIterator<Item = (N, M)>,
+    N: AsRef<str>,
+    M: Borrow<image::DynamicImage>,
+{
+    let mut buffer: Vec<u8> = vec![];
+    encode_images_to_writer(images, &mut buffer)?;
+
+    Ok(buffer)
+}
+
+/// Encode images read from reader objects into a writer object.
+/// This is useful when the images do not come from files, for example
+/// when they are downloaded over the network. The data read from each
+/// reader can be of any image format supported by the `image` crate,
+/// it is converted to PNG before being written.
+///
+/// # Arguments
+///
+/// * `entries` - Anything that can be iterated over to get pairs of
+///   names and objects that implement the trait `io::Read`.
+/// * `writer` - An object that implements the trait `io::Write`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If there are no entries (`SfaError::EmptyInput`).
+/// * If the same name is given more than once (`SfaError::DuplicateName`).
+///   Nothing is written in this case.
+/// * If reading an entry fails or if the data is not a valid image.
+///   (`SfaError::InputEntry` which contains the name of the entry)
+/// * If the writer fails to write some information due
+///   to some OS error.
+/// * If there are problems with writing the image in PNG format
+///   to an in memory buffer.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use image::{DynamicImage, ImageOutputFormat};
+/// use sfa::{decode_from_reader, encode_from_readers, SfaError};
+///
+/// let mut png = vec![];
+/// DynamicImage::new_rgba8(4, 4).write_to(&mut png, ImageOutputFormat::Png).unwrap();
+/// let mut jpeg = vec![];
+/// DynamicImage::new_rgb8(4, 4).write_to(&mut jpeg, ImageOutputFormat::Jpeg(90)).unwrap();
+///
+/// let entries = vec![
+///     (String::from("a.png"), Cursor::new(png)),
+///     (String::from("b.jpg"), Cursor::new(jpeg)),
+/// ];
+///
+/// let mut archive = vec![];
+/// encode_from_readers(entries, &mut archive).unwrap();
+///
+/// let decoded = decode_from_reader(&mut archive.as_slice()).unwrap();
+/// assert_eq!(decoded.len(), 2);
+///
+/// // Failures report the name of the entry
+/// let entries = vec![(String::from("broken.png"), Cursor::new(b"not an image".to_vec()))];
+/// match encode_from_readers(entries, &mut vec![]) {
+///     Err(SfaError::InputEntry { name, .. }) => assert_eq!(name, "broken.png"),
+///     _ => panic!("expected the entry to fail"),
+/// }
+/// ```
+pub fn encode_from_readers<I, R, W>(entries: I, writer: &mut W) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = (String, R)>,
+    R: Read,
+    W: Write,
+{
+    let entries: Vec<(String, R)> = entries.into_iter().collect();
+    check_not_empty(entries.len(), &EncodeOptions::default())?;
+    check_unique_names(entries.iter().map(|(name, _)| name.as_str()))?;
+
+    let layout = EncodeOptions::default().layout();
+    layout.write(writer)?;
+
+    let count = entries.len();
+    let mut input_buffer: Vec<u8> = vec![];
+    for (name, mut reader) in entries {
+        input_buffer.clear();
+        let im = match reader.read_to_end(&mut input_buffer) {
+            Ok(_) => image::guess_format(&input_buffer)
+                .map_err(SfaError::from)
+                .and_then(|format| load_upright(&input_buffer, format, &EncodeOptions::default())),
+            Err(e) => Err(SfaError::from(e)),
+        };
+        let im = im.map_err(|e| SfaError::InputEntry {
+            name: name.clone(),
+            source: Box::new(e),
+        })?;
+        write_entry(writer, layout, &name, &im, &EncodeOptions::default())?;
+    }
+    write_end(writer, layout, count, None)?;
+
+    Ok(())
+}
+
+/// Append the given input image files to an existing sfa file.
+/// The entries already in the file are neither decoded nor rewritten,
+/// only their headers are scanned to make sure that no name is used
+/// twice. The new entries are simply written at the end of the file,
+/// in place of the entry count of files that have one. This makes it
+/// cheap to build sprite sheets incrementally.
+///
+/// If one of the input files fails to be read, the entries written
+/// before it stay in the archive. Archives with an entry count are left
+/// without one then, so decoding them fails as if they were cut short.
+///
+/// # Arguments
+///
+/// * `archive` - A Path-like object that refers to an existing sfa file.
+/// * `new_files` - Anything that can be iterated over to get Path-like
+///   objects. The file name of each path is used as the name of the entry.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and are generated
+/// in one of the following conditions.
+///
+/// * If the archive can not be opened due to some OS Error.
+/// * If the archive does not start with the magic text
+///   (`SfaError::MissingMagic`). Nothing is written in this case.
+/// * If the name of a new file is already used by an entry of the archive
+///   or by another new file (`SfaError::DuplicateName`). Nothing is written
+///   in this case.
+/// * If an entry of the archive is cut off (`SfaError::InvalidSize`).
+///   Nothing is written in this case.
+/// * If provided new_files are not valid images or if there
+///   is some error while reading them. The error is wrapped in
+///   `SfaError::InputEntry` with the path of the failing file and
+///   nothing is written if the file is missing or not a regular file.
+/// * If there are problems with writing the image in PNG format
+///   to an in memory buffer.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{append, decode, encode, SfaError};
+///
+/// let dir = std::env::temp_dir();
+/// let frames: Vec<String> = (1..=3)
+///     .map(|i| {
+///         let path = dir.join(format!("sfa_append_{}.png", i));
+///         DynamicImage::new_rgba8(4, 4).save(&path).unwrap();
+///         path.to_str().unwrap().to_owned()
+///     })
+///     .collect();
+///
+/// let archive = dir.join("sfa_append.sfa");
+/// encode(&frames[..2], &archive).unwrap();
+/// append(&archive, &frames[2..]).unwrap();
+///
+/// let decoded = decode(&archive).unwrap();
+/// assert_eq!(decoded.len(), 3);
+/// for i in 1..=3 {
+///     assert!(decoded.contains_key(&format!("sfa_append_{}.png", i)));
+/// }
+///
+/// let result = append(&archive, &frames[..1]);
+/// assert!(matches!(result, Err(SfaError::DuplicateName(name)) if name == "sfa_append_1.png"));
+///
+/// // A truncated archive is left as it is
+/// let truncated = dir.join("sfa_append_truncated.sfa");
+/// let bytes = std::fs::read(&archive).unwrap();
+/// std::fs::write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+/// let result = append(&truncated, &frames[2..]);
+/// assert!(matches!(result, Err(SfaError::InvalidSize(_))));
+/// assert_eq!(std::fs::read(&truncated).unwrap().len(), bytes.len() / 2);
+/// ```
+#[cfg(feature = "fs")]
+pub fn append<T, I, P>(archive: T, new_files: I) -> Result<(), SfaError>
+where
+    T: AsRef<Path>,
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(archive)?;
+    let mut file_reader = io::BufReader::new(file);
+    // New entries have to match the version of the entries already in the file
+    let layout = read_magic(&mut file_reader)?;
+
+    let mut names: HashSet<String> = HashSet::new();
+    let mut headers = Headers::new(layout);
+    let mut toc = Toc::new(layout);
+    // The new entries replace the table of contents and the entry count
+    // at the end of the file
+    let mut end = file_reader.stream_position()?;
+    while let Some(header) = headers.next(&mut file_reader)? {
+        seek_payload(&mut file_reader, header.size)?;
+        names.insert(header.name.clone());
+        end = file_reader.stream_position()?;
+        toc.add(header, end);
+    }
+
+    let new_files: Vec<P> = new_files.into_iter().collect();
+    let new_names = entry_names(&new_files, &EncodeOptions::default())?;
+    for name in &new_names {
+        if !names.insert(name.clone()) {
+            return Err(SfaError::DuplicateName(name.clone()));
+        }
+    }
+
+    check_input_files(&new_files)?;
+
+    let mut file = file_reader.into_inner();
+    file.seek(SeekFrom::Start(end))?;
+    let mut file_writer = Positioned::starting_at(io::BufWriter::new(file), end);
+
+    for (x, name) in new_files.iter().zip(&new_names) {
+        let im = open_image(x.as_ref(), &EncodeOptions::default())?;
+        let header = write_entry(
+            &mut file_writer,
+            layout,
+            name,
+            &im,
+            &EncodeOptions::default(),
+        )?;
+        toc.add(header, file_writer.position());
+    }
+    toc.finish(&mut file_writer)?;
+    file_writer.flush()?;
+
+    Ok(())
+}
+
+/// Merge several sfa files into a single one. The entries are copied
+/// one at a time in the order of the files without being decoded, so
+/// only a single entry is kept in memory and nothing is lost. The new
+/// file has the format tags and the parts, such as checksums, of all the
+/// inputs, and the metadata of the archives is merged, with the first file that
+/// has a key winning.
+///
+/// The headers of all the inputs are scanned before anything is
+/// written. The merged file is written next to `output` with `.tmp`
+/// appended to its name and replaces `output` once it is complete, so
+/// `output` can also be one of the inputs.
+///
+/// # Arguments
+///
+/// * `inputs` - Anything that can be iterated over to get Path-like
+///   objects of sfa files.
+/// * `output` - Path-like object of the file to write the merged archive to.
+/// * `prefix` - Whether to prefix the name of every entry with the file
+///   stem of its input and a `/`, such as `walk/frame_1.png` for the
+///   entries of `walk.sfa`, so that inputs can use the same names.
+///   Aliases are changed to refer to the prefixed names.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Writing the output file was unsuccessful. (`SfaError::Io`)
+/// * An input can not be read or does not comply with the sfa format.
+///   The error is wrapped in `SfaError::InputEntry` with the path of
+///   the input. Nothing is written if this is found while scanning the
+///   headers.
+/// * Two entries have the same name. (`SfaError::DuplicateName`)
+///   Nothing is written in this case.
+/// * The inputs have no entries. (`SfaError::EmptyInput`)
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_ordered, encode_images, merge, EncodeOptions, SfaBuilder, SfaError};
+///
+/// let dir = std::env::temp_dir();
+/// let walk = dir.join("walk.sfa");
+/// let frames = vec![
+///     (String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("frame_2.png"), DynamicImage::new_rgba8(8, 8)),
+/// ];
+/// encode_images(frames, &walk).unwrap();
+///
+/// // The second frame of this one is stored as an alias to the first
+/// let run = dir.join("run.sfa");
+/// let options = EncodeOptions {
+///     dedupe: true,
+///     ..Default::default()
+/// };
+/// let file = std::fs::File::create(&run).unwrap();
+/// let mut builder = SfaBuilder::with_options(file, options).unwrap();
+/// builder.add_image("frame_1.png", &DynamicImage::new_rgba8(2, 2)).unwrap();
+/// builder.add_image("frame_2.png", &DynamicImage::new_rgba8(2, 2)).unwrap();
+/// builder.finish().unwrap();
+///
+/// let character = dir.join("character.sfa");
+/// merge(&[&walk, &run], &character, true).unwrap();
+///
+/// let decoded = decode_ordered(&character).unwrap();
+/// let names: Vec<&str> = decoded.iter().map(|(name, _)| name.as_str()).collect();
+/// assert_eq!(
+///     names,
+///     ["walk/frame_1.png", "walk/frame_2.png", "run/frame_1.png", "run/frame_2.png"]
+/// );
+/// assert_eq!(decoded[3].1.to_rgba8().dimensions(), (2, 2));
+///
+/// let result = merge(&[&walk, &run], &character, false);
+/// assert!(matches!(result, Err(SfaError::DuplicateName(name)) if name == "frame_1.png"));
+/// ```
+#[cfg(feature = "fs")]
+pub fn merge<I, P, T>(inputs: I, output: T, prefix: bool) -> Result<(), SfaError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    let inputs: Vec<P> = inputs.into_iter().collect();
+    let prefixes: Vec<String> = inputs
+        .iter()
+        .map(|input| merge_prefix(input.as_ref(), prefix))
+        .collect();
+
+    let mut layout = Layout::legacy(0);
+    let mut archive_metadata: HashMap<String, String> = HashMap::new();
+    let mut names: HashSet<String> = HashSet::new();
+    for (input, prefix) in inputs.iter().zip(&prefixes) {
+        let input = input.as_ref();
+        let (input_layout, input_metadata, input_names) =
+            scan_merge_input(input).map_err(|e| input_error(input, e))?;
+        layout = layout.union(input_layout);
+        for (key, value) in input_metadata {
+            archive_metadata.entry(key).or_insert(value);
+        }
+        for name in input_names {
+            let name = format!("{}{}", prefix, name);
+            if !names.insert(name.clone()) {
+                return Err(SfaError::DuplicateName(name));
+            }
+        }
+    }
+    check_not_empty(names.len(), &EncodeOptions::default())?;
+
+    let mut temporary = output.as_ref().as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = std::path::PathBuf::from(temporary);
+    let inputs: Vec<(&Path, &str)> = inputs
+        .iter()
+        .map(AsRef::as_ref)
+        .zip(prefixes.iter().map(String::as_str))
+        .collect();
+    if let Err(e) = merge_to(&inputs, &temporary, layout, &archive_metadata) {
+        // The error is more useful than one from cleaning up after it
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+    fs::rename(&temporary, output)?;
+
+    Ok(())
+}
+
+/// The prefix of the names of the entries of an input of `sfa::merge`.
+#[cfg(feature = "fs")]
+fn merge_prefix(path: &Path, prefix: bool) -> String {
+    match path.file_stem() {
+        Some(stem) if prefix => format!("{}/", stem.to_string_lossy()),
+        _ => String::new(),
+    }
+}
+
+/// Read the layout, the metadata of the archive and the names of the
+/// entries of an input of `sfa::merge`. The data is skipped by seeking.
+#[cfg(feature = "fs")]
+#[allow(clippy::type_complexity)]
+fn scan_merge_input(
+    path: &Path,
+) -> Result<(Layout, HashMap<String, String>, Vec<String>), SfaError> {
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+    let layout = Layout::read(&mut reader)?;
+    let archive_metadata = metadata::read_archive_metadata(&mut reader, layout)?;
+
+    let mut names: Vec<String> = vec![];
+    let mut headers = Headers::new(layout);
+    while let Some(header) = headers.next(&mut reader)? {
+        seek_payload(&mut reader, header.size)?;
+        names.push(header.name);
+    }
+
+    Ok((layout, archive_metadata, names))
+}
+
+/// Copy the entries of the inputs of `sfa::merge` to a new file, adding
+/// the prefixes to their names and to the names aliases refer to.
+#[cfg(feature = "fs")]
+fn merge_to(
+    inputs: &[(&Path, &str)],
+    path: &Path,
+    layout: Layout,
+    archive_metadata: &HashMap<String, String>,
+) -> Result<(), SfaError> {
+    let file = fs::File::create(path)?;
+    let mut writer = Positioned::new(io::BufWriter::new(file));
+    layout.write(&mut writer)?;
+    metadata::write_archive_metadata(&mut writer, layout, archive_metadata)?;
+
+    let mut toc = Toc::new(layout);
+    let mut buffer: Vec<u8> = vec![];
+    for &(input, prefix) in inputs {
+        let mut reader = io::BufReader::new(fs::File::open(input)?);
+        let input_layout = read_magic(&mut reader).map_err(|e| input_error(input, e))?;
+        let mut headers = Headers::new(input_layout);
+        while let Some(header) = headers
+            .next(&mut reader)
+            .map_err(|e| input_error(input, e))?
+        {
+            read_payload(&mut reader, &header, &mut buffer).map_err(|e| input_error(input, e))?;
+            if header.format == EntryFormat::Alias {
+                buffer.splice(0..0, prefix.bytes());
+            }
+
+            let name = format!("{}{}", prefix, header.name);
+            let header = write_raw_entry(&mut writer, layout, &name, &buffer, header.format)?;
+            toc.add(header, writer.position());
+        }
+    }
+    toc.finish(&mut writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Split a sfa file into smaller ones with up to `chunk` entries each,
+/// so that a large set of images can be delivered and loaded in pieces.
+/// The parts are written to `out_dir` as `part_000.sfa`, `part_001.sfa`
+/// and so on, in the order of the entries, which keep their names. Each
+/// part has the version and the metadata of the input.
+///
+/// The entries are copied without being decoded wherever possible. Each
+/// part can be decoded on its own, so an alias of an entry in an earlier
+/// part is replaced by a copy of that entry, and a delta entry at the
+/// start of a part is decoded and stored as a whole image. The data of
+/// the entries that aliases can refer to is kept in memory for this.
+///
+/// The parts are written with `.tmp` appended to their names and only
+/// get their final names once all of them are complete. Returns the
+/// paths of the parts, which are none if the input has no entries.
+///
+/// # Arguments
+///
+/// * `input` - Path-like object of the sfa file to split.
+/// * `out_dir` - Path-like object of the directory to write the parts
+///   to, which is created if it does not exist.
+/// * `chunk` - The largest number of entries of a part.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * `chunk` is 0. (`SfaError::EmptyChunk`)
+/// * Reading or writing the files was unsuccessful. (`SfaError::Io`)
+/// * The input file does not comply with the sfa format, or a delta
+///   entry at the start of a part can not be decoded. No part is left
+///   behind in this case.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_ordered, split, EncodeOptions, SfaBuilder};
+///
+/// let dir = std::env::temp_dir().join("sfa_split");
+/// let path = std::env::temp_dir().join("sfa_split.sfa");
+///
+/// // The fourth frame is stored as an alias to the first one
+/// let sizes = [1, 2, 3, 1, 5];
+/// let options = EncodeOptions {
+///     dedupe: true,
+///     ..Default::default()
+/// };
+/// let file = std::fs::File::create(&path).unwrap();
+/// let mut builder = SfaBuilder::with_options(file, options).unwrap();
+/// for (i, size) in sizes.iter().enumerate() {
+///     let im = DynamicImage::new_rgba8(*size, *size);
+///     builder.add_image(&format!("walk_{}.png", i), &im).unwrap();
+/// }
+/// builder.finish().unwrap();
+///
+/// let parts = split(&path, &dir, 2).unwrap();
+/// assert_eq!(parts.len(), 3);
+/// assert!(parts[2].ends_with("part_002.sfa"));
+///
+/// let mut names: Vec<Vec<String>> = vec![];
+/// for part in &parts {
+///     let decoded = decode_ordered(part).unwrap();
+///     names.push(decoded.into_iter().map(|(name, _)| name).collect());
+/// }
+/// assert_eq!(
+///     names,
+///     [
+///         vec!["walk_0.png", "walk_1.png"],
+///         vec!["walk_2.png", "walk_3.png"],
+///         vec!["walk_4.png"],
+///     ]
+/// );
+///
+/// // The alias in the second part got a copy of the first frame
+/// let second = decode_ordered(&parts[1]).unwrap();
+/// assert_eq!(second[1].1.to_rgba8().dimensions(), (1, 1));
+///
+/// assert!(matches!(split(&path, &dir, 0), Err(sfa::SfaError::EmptyChunk)));
+/// ```
+#[cfg(feature = "fs")]
+pub fn split<P, Q>(input: P, out_dir: Q, chunk: usize) -> Result<Vec<std::path::PathBuf>, SfaError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    if chunk == 0 {
+        return Err(SfaError::EmptyChunk);
+    }
+
+    let out_dir = out_dir.as_ref();
+    fs::create_dir_all(out_dir)?;
+    let file = fs::File::open(input)?;
+    let mut reader = Positioned::new(io::BufReader::new(file));
+
+    let mut temporaries: Vec<std::path::PathBuf> = vec![];
+    if let Err(e) = split_to(&mut reader, out_dir, chunk, &mut temporaries) {
+        // The error is more useful than one from cleaning up after it
+        for temporary in &temporaries {
+            let _ = fs::remove_file(temporary);
+        }
+        return Err(e);
+    }
+
+    let mut parts: Vec<std::path::PathBuf> = vec![];
+    for temporary in temporaries {
+        let part = temporary.with_extension("");
+        fs::rename(&temporary, &part)?;
+        parts.push(part);
+    }
+
+    Ok(parts)
+}
+
+/// Write the parts of `sfa::split` with `.tmp` appended to their names,
+/// adding the path of each part to `temporaries` before it is created.
+#[cfg(feature = "fs")]
+fn split_to<R: Read>(
+    reader: &mut Positioned<R>,
+    out_dir: &Path,
+    chunk: usize,
+    temporaries: &mut Vec<std::path::PathBuf>,
+) -> Result<(), SfaError> {
+    let layout = Layout::read(reader)?;
+    let archive_metadata = metadata::read_archive_metadata(reader, layout)?;
+
+    let mut part: Option<(Positioned<io::BufWriter<fs::File>>, Toc)> = None;
+    // Part, format, offset and data of the entries that aliases can refer to
+    let mut targets: AliasTargets<(usize, EntryFormat, u64, Vec<u8>)> = AliasTargets::new();
+    // Name, format, offset and data of the last entry stored in full and
+    // the delta entries after it, for delta entries that start a part
+    let mut chain: Vec<(String, EntryFormat, u64, Vec<u8>)> = vec![];
+    let mut buffer: Vec<u8> = vec![];
+    let mut headers = Headers::new(layout);
+    while let Some(header) = headers.next(reader)? {
+        let offset = reader.position();
+        read_payload(reader, &header, &mut buffer)?;
+
+        let index = (headers.count - 1) / chunk;
+        let first = (headers.count - 1) % chunk == 0;
+        if first {
+            if let Some((mut writer, toc)) = part.take() {
+                toc.finish(&mut writer)?;
+                writer.flush()?;
+            }
+            let path = out_dir.join(format!("part_{:03}.sfa.tmp", index));
+            temporaries.push(path.clone());
+            let mut writer = Positioned::new(io::BufWriter::new(fs::File::create(path)?));
+            layout.write(&mut writer)?;
+            metadata::write_archive_metadata(&mut writer, layout, &archive_metadata)?;
+            part = Some((writer, Toc::new(layout)));
+        }
+        let (writer, toc) = part.as_mut().unwrap();
+
+        let written = match header.format {
+            EntryFormat::Alias => {
+                let (target, (target_part, format, target_offset, data)) =
+                    targets.resolve(&header, &buffer)?;
+                if layout.knows(EntryFormat::Delta) {
+                    chain = vec![(target, *format, *target_offset, data.clone())];
+                }
+                match *target_part == index {
+                    true => write_raw_entry(writer, layout, &header.name, &buffer, header.format)?,
+                    false => write_raw_entry(writer, layout, &header.name, data, *format)?,
+                }
+            }
+            EntryFormat::Delta => {
+                if chain.is_empty() {
+                    return Err(SfaError::InvalidDelta(header.name));
+                }
+                chain.push((header.name.clone(), header.format, offset, buffer.clone()));
+                match first {
+                    true => {
+                        let im = decode_chain(&chain)?;
+                        write_entry(writer, layout, &header.name, &im, &EncodeOptions::default())?
+                    }
+                    false => write_raw_entry(writer, layout, &header.name, &buffer, header.format)?,
+                }
+            }
+            format => {
+                if layout.knows(EntryFormat::Alias) {
+                    targets.add(&header, (index, format, offset, buffer.clone()));
+                }
+                if layout.knows(EntryFormat::Delta) {
+                    chain = vec![(header.name.clone(), format, offset, buffer.clone())];
+                }
+                write_raw_entry(writer, layout, &header.name, &buffer, format)?
+            }
+        };
+        toc.add(written, writer.position());
+    }
+
+    if let Some((mut writer, toc)) = part {
+        toc.finish(&mut writer)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Decode the last of the entries of a chain of an entry stored in full
+/// and the delta entries after it.
+#[cfg(feature = "fs")]
+fn decode_chain(
+    chain: &[(String, EntryFormat, u64, Vec<u8>)],
+) -> Result<image::DynamicImage, SfaError> {
+    let ((name, format, offset, data), deltas) = chain.split_first().unwrap();
+    let mut im = load_entry(name, *offset, *format, data)?;
+    for (name, _, offset, data) in deltas {
+        im = delta::apply(name, *offset, &im, data)?;
+    }
+
+    Ok(im)
+}
+
+/// Remove the entries with the given name from a sfa file. The other
+/// entries are copied without being decoded, so their data stays exactly
+/// as it was. Returns whether an entry was removed, and the file is not
+/// touched if there was none with the name.
+///
+/// The entries that were based on a removed entry are kept decodable.
+/// The first alias that refers to it gets a copy of its data and the
+/// aliases after that refer to the first one, and a delta entry right
+/// after it is decoded and stored as a whole image.
+///
+/// The headers of the file are scanned before anything is written. The
+/// new file is written next to it with `.tmp` appended to its name and
+/// replaces it once it is complete.
+///
+/// # Arguments
+///
+/// * `archive` - A Path-like object that refers to an existing sfa file.
+/// * `name` - The name of the entry to remove.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading or writing the files was unsuccessful. (`SfaError::Io`)
+/// * The file does not comply with the sfa format, or a delta entry
+///   after the removed entry can not be decoded. The file is left as it
+///   was in this case.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_raw, encode_images, remove};
+///
+/// let frames = vec![
+///     (String::from("idle.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("walk.png"), DynamicImage::new_rgba8(8, 8)),
+///     (String::from("jump.png"), DynamicImage::new_rgba8(2, 2)),
+/// ];
+/// let path = std::env::temp_dir().join("sfa_remove.sfa");
+/// encode_images(frames, &path).unwrap();
+/// let before = decode_raw(&path).unwrap();
+///
+/// assert!(remove(&path, "walk.png").unwrap());
+/// let after = decode_raw(&path).unwrap();
+/// assert_eq!(after.len(), 2);
+/// assert_eq!(after["idle.png"], before["idle.png"]);
+/// assert_eq!(after["jump.png"], before["jump.png"]);
+///
+/// // Nothing is written if there is no entry with the name
+/// assert!(!remove(&path, "walk.png").unwrap());
+/// ```
+#[cfg(feature = "fs")]
+pub fn remove<P: AsRef<Path>>(archive: P, name: &str) -> Result<bool, SfaError> {
+    let archive = archive.as_ref();
+    let mut reader = io::BufReader::new(fs::File::open(archive)?);
+    let layout = read_magic(&mut reader)?;
+
+    let mut found = false;
+    // Whether a delta entry follows a removed entry, which needs the
+    // entries before it to be decoded
+    let mut decode = false;
+    let mut previous_removed = false;
+    let mut headers = Headers::new(layout);
+    while let Some(header) = headers.next(&mut reader)? {
+        seek_payload(&mut reader, header.size)?;
+        decode |= previous_removed && header.format == EntryFormat::Delta;
+        previous_removed = header.name == name;
+        found |= previous_removed;
+    }
+    if !found {
+        return Ok(false);
+    }
+
+    let mut temporary = archive.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = std::path::PathBuf::from(temporary);
+    if let Err(e) = remove_to(archive, &temporary, name, decode) {
+        // The error is more useful than one from cleaning up after it
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+    fs::rename(&temporary, archive)?;
+
+    Ok(true)
+}
+
+/// Copy the entries of the input of `sfa::remove` to a new file, leaving
+/// out the ones with the given name. The data of the entries is only
+/// kept for decoding delta entries if `decode` is set.
+#[cfg(feature = "fs")]
+fn remove_to(input: &Path, path: &Path, name: &str, decode: bool) -> Result<(), SfaError> {
+    let file = fs::File::open(input)?;
+    let mut reader = Positioned::new(io::BufReader::new(file));
+    let layout = Layout::read(&mut reader)?;
+    let archive_metadata = metadata::read_archive_metadata(&mut reader, layout)?;
+
+    let file = fs::File::create(path)?;
+    let mut writer = Positioned::new(io::BufWriter::new(file));
+    layout.write(&mut writer)?;
+    metadata::write_archive_metadata(&mut writer, layout, &archive_metadata)?;
+
+    let mut toc = Toc::new(layout);
+    // Format and data of the removed entry for the aliases that refer to
+    // it, and the name of the alias that got a copy of them
+    let mut removed: Option<(EntryFormat, Vec<u8>)> = None;
+    let mut replacement: Option<String> = None;
+    // Format, offset and data of the entries that aliases can refer to,
+    // and the chain of the entries that the next delta entry is based on
+    let mut targets: AliasTargets<(EntryFormat, u64, Vec<u8>)> = AliasTargets::new();
+    let mut chain: Vec<(String, EntryFormat, u64, Vec<u8>)> = vec![];
+    let mut previous_removed = false;
+    let mut buffer: Vec<u8> = vec![];
+    let mut headers = Headers::new(layout);
+    while let Some(header) = headers.next(&mut reader)? {
+        let offset = reader.position();
+        read_payload(&mut reader, &header, &mut buffer)?;
+        let skip = header.name == name;
+
+        let written = match header.format {
+            EntryFormat::Alias => {
+                let target = match decode {
+                    true => {
+                        let (target, (format, offset, data)) = targets.resolve(&header, &buffer)?;
+                        chain = vec![(target.clone(), *format, *offset, data.clone())];
+                        target
+                    }
+                    false => parse_name(buffer.clone())?,
+                };
+                match (skip, &replacement, &removed) {
+                    (true, _, _) => None,
+                    (false, _, _) if target != name => Some(write_raw_entry(
+                        &mut writer,
+                        layout,
+                        &header.name,
+                        &buffer,
+                        header.format,
+                    )?),
+                    (false, Some(replacement), _) => Some(write_raw_entry(
+                        &mut writer,
+                        layout,
+                        &header.name,
+                        replacement.as_bytes(),
+                        EntryFormat::Alias,
+                    )?),
+                    (false, None, Some((format, data))) => {
+                        let written =
+                            write_raw_entry(&mut writer, layout, &header.name, data, *format)?;
+                        replacement = Some(header.name.clone());
+                        Some(written)
+                    }
+                    (false, None, None) => return Err(invalid_alias(header.name, target)),
+                }
+            }
+            EntryFormat::Delta => {
+                if decode {
+                    if chain.is_empty() {
+                        return Err(SfaError::InvalidDelta(header.name));
+                    }
+                    chain.push((header.name.clone(), header.format, offset, buffer.clone()));
+                }
+                match (skip, previous_removed) {
+                    (true, _) => None,
+                    // The entry it is based on is gone, so it is stored whole
+                    (false, true) => {
+                        let im = decode_chain(&chain)?;
+                        let options = EncodeOptions::default();
+                        Some(write_entry(
+                            &mut writer,
+                            layout,
+                            &header.name,
+                            &im,
+                            &options,
+                        )?)
+                    }
+                    (false, false) => Some(write_raw_entry(
+                        &mut writer,
+                        layout,
+                        &header.name,
+                        &buffer,
+                        header.format,
+                    )?),
+                }
+            }
+            format => {
+                if decode {
+                    targets.add(&header, (format, offset, buffer.clone()));
+                    chain = vec![(header.name.clone(), format, offset, buffer.clone())];
+                }
+                match skip {
+                    true => {
+                        removed = Some((format, buffer.clone()));
+                        replacement = None;
+                        None
+                    }
+                    false => Some(write_raw_entry(
+                        &mut writer,
+                        layout,
+                        &header.name,
+                        &buffer,
+                        format,
+                    )?),
+                }
+            }
+        };
+        if let Some(written) = written {
+            toc.add(written, writer.position());
+        }
+        previous_removed = skip;
+    }
+    toc.finish(&mut writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Re-encode all the entries of a sfa file with the given options, for
+/// example to store them in another format or compress them better. The
+/// entries are decoded and written one at a time, keeping their names and
+/// order. The metadata of the archive is kept unless the options have
+/// metadata of their own.
+///
+/// The new archive is written next to `output` with `.tmp` appended to
+/// its name and replaces `output` once it is complete, so `input` and
+/// `output` can be the same file.
+///
+/// # Arguments
+///
+/// * `input` - Path-like object of the sfa file to re-encode.
+/// * `output` - Path-like object of the file to write the new archive to.
+/// * `options` - A reference to `sfa::EncodeOptions`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading or writing the files was unsuccessful. (`SfaError::Io`)
+/// * The input file can not be decoded, see `sfa::SfaReader::next_frame`.
+///   `output` is left as it was in this case.
+/// * The input file has no entries and `EncodeOptions::allow_empty` is
+///   not set. (`SfaError::EmptyInput`)
+/// * Two entries of the input file have the same name.
+///   (`SfaError::DuplicateName`)
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_ordered, encode_images, resave, CompressionLevel, EncodeOptions};
+///
+/// let frames: Vec<(String, DynamicImage)> = (1..=3)
+///     .map(|i| (format!("run_{}.png", i), DynamicImage::new_rgba8(i, i)))
+///     .collect();
+/// let path = std::env::temp_dir().join("sfa_resave.sfa");
+/// encode_images(frames, &path).unwrap();
+///
+/// let options = EncodeOptions {
+///     compression: CompressionLevel::Best,
+///     checksums: true,
+///     ..Default::default()
+/// };
+/// resave(&path, &path, &options).unwrap();
+/// assert!(std::fs::read(&path).unwrap().starts_with(b"SFA9;\x05"));
+///
+/// let decoded = decode_ordered(&path).unwrap();
+/// let names: Vec<&str> = decoded.iter().map(|(name, _)| name.as_str()).collect();
+/// assert_eq!(names, ["run_1.png", "run_2.png", "run_3.png"]);
+/// assert_eq!(decoded[2].1.to_rgba8().dimensions(), (3, 3));
+/// ```
+#[cfg(feature = "fs")]
+pub fn resave<P, Q>(input: P, output: Q, options: &EncodeOptions) -> Result<(), SfaError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let file = fs::File::open(input)?;
+    let mut file_reader = io::BufReader::new(file);
+    let layout = Layout::read(&mut file_reader)?;
+    let archive_metadata = metadata::read_archive_metadata(&mut file_reader, layout)?;
+    file_reader.seek(SeekFrom::Start(0))?;
+    let mut reader = SfaReader::new(file_reader)?;
+
+    let mut options = options.clone();
+    if options.metadata.is_empty() {
+        options.metadata = archive_metadata;
+    }
+
+    let mut temporary = output.as_ref().as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = std::path::PathBuf::from(temporary);
+    if let Err(e) = resave_to(&mut reader, &temporary, options) {
+        // The error is more useful than one from cleaning up after it
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+    fs::rename(&temporary, output)?;
+
+    Ok(())
+}
+
+/// Write the remaining entries of the reader to a new file with the
+/// given options.
+#[cfg(feature = "fs")]
+fn resave_to<R: Read>(
+    reader: &mut SfaReader<R>,
+    path: &Path,
+    options: EncodeOptions,
+) -> Result<(), SfaError> {
+    let file = fs::File::create(path)?;
+    let mut builder = SfaBuilder::with_options(io::BufWriter::new(file), options)?;
+    while let Some((name, im)) = reader.next_frame()? {
+        builder.add_image(&name, &im)?;
+    }
+    builder.finish()?;
+
+    Ok(())
+}
+
+/// Write a single entry to the writer. The image is converted to
+/// PNG in an in memory buffer first because the size of the data
+/// has to be written before the data itself. Returns the header of
+/// the entry.
+fn write_entry<W: Write>(
+    writer: &mut W,
+    layout: Layout,
+    name: &str,
+    im: &image::DynamicImage,
+    options: &EncodeOptions,
+) -> Result<EntryHeader, SfaError> {
+    if let Some(limit) = options.buffer_limit {
+        if options.format == StorageFormat::Png
+            && !options.interlaced
+            && options.quantize.is_none()
+            && !options.trim_transparent
+        {
+            let mut temprorary_buffer = SpillBuffer::new(limit);
+            write_png(&prepare_image(im, options), options, &mut temprorary_buffer)?;
+            temprorary_buffer.flush()?;
+            let header = write_entry_header(
+                writer,
+                layout,
+                name,
+                temprorary_buffer.len(),
+                EntryFormat::Png,
+                temprorary_buffer.checksum(),
+            )?;
+            temprorary_buffer.copy_to(writer)?;
+            return Ok(header);
+        }
+    }
+
+    let (temprorary_buffer, format) = encode_image(im, options)?;
+    write_raw_entry(writer, layout, name, &temprorary_buffer, format)
+}
+
+/// Write a single entry with already encoded data to the writer. The
+/// format and checksum are only written for versions of the file format
+/// that have them. Returns the header of the entry.
+fn write_raw_entry<W: Write>(
+    writer: &mut W,
+    layout: Layout,
+    name: &str,
+    data: &[u8],
+    format: EntryFormat,
+) -> Result<EntryHeader, SfaError> {
+    let checksum = crc32fast::hash(data);
+    let header = write_entry_header(writer, layout, name, data.len() as u64, format, checksum)?;
+    writer.write_all(data)?;
+
+    Ok(header)
+}
+
+/// Write a single entry with already encoded data to the writer, or
+/// an alias to an earlier entry with the same data if it is a
+/// duplicate. Delta entries are never aliased nor aliased to, since
+/// their data only has a meaning next to the entry before them.
+/// Returns the header of the entry.
+fn write_deduped_entry<W: Write>(
+    writer: &mut W,
+    layout: Layout,
+    name: &str,
+    data: &[u8],
+    format: EntryFormat,
+    dedupe: &mut Dedupe,
+) -> Result<EntryHeader, SfaError> {
+    if format == EntryFormat::Delta {
+        return write_raw_entry(writer, layout, name, data, format);
+    }
+
+    match dedupe.insert(name, data, format) {
+        Some(target) => {
+            write_raw_entry(writer, layout, name, target.as_bytes(), EntryFormat::Alias)
+        }
+        None => write_raw_entry(writer, layout, name, data, format),
+    }
+}
+
+/// Write the header of an entry whose data follows it. `checksum` is
+/// the CRC32 of the data. Returns the header as it is read back.
+fn write_entry_header<W: Write>(
+    writer: &mut W,
+    layout: Layout,
+    name: &str,
+    size: u64,
+    format: EntryFormat,
+    checksum: u32,
+) -> Result<EntryHeader, SfaError> {
+    // Write the size of the data as well as name of the file
+    writer.write_all(format!("{}:{}:", frame_name(name), size).as_bytes())?;
+    if layout.tags() {
+        writer.write_all(&[format.tag()])?;
+    }
+    if layout.checksums() {
+        writer.write_all(&checksum.to_be_bytes())?;
+    }
+
+    Ok(EntryHeader {
+        name: name.to_owned(),
+        size: size as usize,
+        format,
+        checksum: match layout.checksums() {
+            true => Some(checksum),
+            false => None,
+        },
+    })
+}
+
+/// Write the image of the input file to the writer. Returns the header
+/// of the entry.
+fn write_file_entry<W: Write>(
+    writer: &mut W,
+    layout: Layout,
+    name: &str,
+    path: &Path,
+    options: &EncodeOptions,
+) -> Result<EntryHeader, SfaError> {
+    // Metadata is only copied while the data of the file is at hand
+    if options.keep_original_format
+        || options.reuse_png_bytes
+        || options.metadata_policy != MetadataPolicy::Strip
+    {
+        let (data, format) = encode_file(path, options)?;
+        return write_raw_entry(writer, layout, name, &data, format);
+    }
+
+    let im = open_image(path, options)?;
+    write_entry(writer, layout, name, &im, options)
+}
+
+/// Encode the image of the input file into the data of an entry. If
+/// enabled in the options, the file is copied as is instead of being
+/// re-encoded.
+fn encode_file(path: &Path, options: &EncodeOptions) -> Result<(Vec<u8>, EntryFormat), SfaError> {
+    encode_input_file(path, options).map_err(|e| input_error(path, e))
+}
+
+/// Encode the image of the input file like `encode_file` without
+/// naming the file in the errors.
+fn encode_input_file(
+    path: &Path,
+    options: &EncodeOptions,
+) -> Result<(Vec<u8>, EntryFormat), SfaError> {
+    let data = fs::read(path)?;
+    if options.keep_original_format {
+        let format = input_format(&data, path)?;
+        // Make sure that the file is valid before copying it
+        let im = image::load_from_memory_with_format(&data, format)?;
+        // Decoders ignore the orientation, so turned images can not be copied
+        let orientation = exif_orientation(&data, options);
+        return match EntryFormat::from_image_format(format) {
+            Some(format) if orientation == 1 && is_stored_as_is(&im, options) => Ok((data, format)),
+            _ => {
+                let encoded = encode_image(&orientation::apply(im, orientation), options)?;
+                Ok(keep_metadata(encoded, &data, options))
+            }
+        };
+    }
+
+    if options.reuse_png_bytes && data.starts_with(PNG_SIGNATURE) {
+        // Make sure that the file is a valid PNG before copying it
+        let im = image::load_from_memory_with_format(&data, ImageFormat::Png)?;
+        let orientation = exif_orientation(&data, options);
+        if orientation == 1 && is_stored_as_is(&im, options) {
+            return Ok((data, EntryFormat::Png));
+        }
+        let encoded = encode_image(&orientation::apply(im, orientation), options)?;
+        return Ok(keep_metadata(encoded, &data, options));
+    }
+
+    let im = load_upright(&data, input_format(&data, path)?, options)?;
+    let encoded = encode_image(&im, options)?;
+
+    Ok(keep_metadata(encoded, &data, options))
+}
+
+/// Copy the metadata of an input file that the options keep into the
+/// PNG data of its entry. Data in other formats is returned as it is.
+fn keep_metadata(
+    encoded: (Vec<u8>, EntryFormat),
+    source: &[u8],
+    options: &EncodeOptions,
+) -> (Vec<u8>, EntryFormat) {
+    match encoded {
+        (png, EntryFormat::Png) => {
+            let chunks = chunks::source_chunks(source, options.metadata_policy);
+            (chunks::insert(png, &chunks), EntryFormat::Png)
+        }
+        encoded => encoded,
+    }
+}
+
+/// Open the image of an input file, turned upright by its EXIF
+/// orientation unless the options say to ignore it.
+fn open_image(path: &Path, options: &EncodeOptions) -> Result<image::DynamicImage, SfaError> {
+    let open = || {
+        let data = fs::read(path)?;
+        load_upright(&data, input_format(&data, path)?, options)
+    };
+
+    open().map_err(|e| input_error(path, e))
+}
+
+/// Decode the data of an input image, turned upright by its EXIF
+/// orientation unless the options say to ignore it.
+fn load_upright(
+    data: &[u8],
+    format: ImageFormat,
+    options: &EncodeOptions,
+) -> Result<image::DynamicImage, SfaError> {
+    let im = image::load_from_memory_with_format(data, format)?;
+
+    Ok(orientation::apply(im, exif_orientation(data, options)))
+}
+
+/// The format of the data of an input file. Some formats like TGA can
+/// not be detected from their contents, so the extension is used then.
+fn input_format(data: &[u8], path: &Path) -> Result<ImageFormat, SfaError> {
+    match image::guess_format(data) {
+        Ok(format) => Ok(format),
+        Err(_) => Ok(ImageFormat::from_path(path)?),
+    }
+}
+
+/// The EXIF orientation of the data of an input image, or 1 (upright)
+/// if the options say to ignore it.
+fn exif_orientation(data: &[u8], options: &EncodeOptions) -> u32 {
+    match options.ignore_exif_orientation {
+        true => 1,
+        false => orientation::read(data),
+    }
+}
+
+/// Encode the image of the input file like `encode_file`, or as a delta
+/// entry if enabled in the options and the image allows it.
+fn encode_file_with_delta(
+    path: &Path,
+    options: &EncodeOptions,
+    delta: &mut Delta,
+) -> Result<(Vec<u8>, EntryFormat), SfaError> {
+    if !options.delta_frames {
+        return encode_file(path, options);
+    }
+
+    let im = open_image(path, options)?;
+    if let Some(data) = delta.encode(&im, options)? {
+        return Ok((data, EntryFormat::Delta));
+    }
+    if options.keep_original_format || options.reuse_png_bytes {
+        return encode_file(path, options);
+    }
+
+    encode_image(&im, options)
+}
+
+/// Encode the image like `encode_image`, or as a delta entry if
+/// enabled in the options and the image allows it.
+fn encode_image_with_delta(
+    im: &image::DynamicImage,
+    options: &EncodeOptions,
+    delta: &mut Delta,
+) -> Result<(Vec<u8>, EntryFormat), SfaError> {
+    match delta.encode(im, options)? {
+        Some(data) => Ok((data, EntryFormat::Delta)),
+        None => encode_image(im, options),
+    }
+}
+
+/// The image resized and converted to the color type as chosen by the
+/// options, or the image itself if it is stored as it is.
+fn prepare_image<'a>(
+    im: &'a image::DynamicImage,
+    options: &EncodeOptions,
+) -> Cow<'a, image::DynamicImage> {
+    let im = match resized_dimensions(im, options) {
+        Some((width, height)) => {
+            Cow::Owned(im.resize_exact(width, height, options.resize_filter.into()))
+        }
+        None => Cow::Borrowed(im),
+    };
+
+    match (color_target(&im, options), options.force_color) {
+        // Detected grayscale images keep the values of their pixels
+        (Some(target), None) => Cow::Owned(target.convert_gray(&im)),
+        (Some(target), Some(_)) => Cow::Owned(target.convert(&im)),
+        (None, _) => im,
+    }
+}
+
+/// Whether the options store the image without changing it, so that
+/// the data of an input file can be copied.
+fn is_stored_as_is(im: &image::DynamicImage, options: &EncodeOptions) -> bool {
+    resized_dimensions(im, options).is_none()
+        && color_target(im, options).is_none()
+        && !(options.trim_transparent && metadata::opaque_bounds(im).is_some())
+}
+
+/// The dimensions that the options resize the image to. `None` is
+/// returned if the image keeps its dimensions.
+fn resized_dimensions(im: &image::DynamicImage, options: &EncodeOptions) -> Option<(u32, u32)> {
+    let dimensions = im.dimensions();
+    match options.resize {
+        Some(resize) => Some(resize.dimensions(dimensions)).filter(|&d| d != dimensions),
+        None => None,
+    }
+}
+
+/// The color type that the options convert the image to before it is
+/// stored. `None` is returned if the image keeps its color type.
+fn color_target(im: &image::DynamicImage, options: &EncodeOptions) -> Option<ColorTarget> {
+    match options.force_color {
+        Some(target) if im.color() == target.color_type() => None,
+        Some(target) => Some(target),
+        None if options.detect_grayscale => ColorTarget::grayscale(im),
+        None => None,
+    }
+}
+
+/// Write the image in the storage format of the options to a `Vec<u8>`,
+/// trimmed if enabled in the options.
+fn encode_image(
+    im: &image::DynamicImage,
+    options: &EncodeOptions,
+) -> Result<(Vec<u8>, EntryFormat), SfaError> {
+    let im = prepare_image(im, options);
+    let im = im.as_ref();
+    if options.trim_transparent {
+        if let Some((x, y, width, height)) = metadata::opaque_bounds(im) {
+            let (original_width, original_height) = im.dimensions();
+            let trim = Trim {
+                x,
+                y,
+                width: original_width,
+                height: original_height,
+            };
+            let (data, format) = encode_prepared(&im.crop_imm(x, y, width, height), options)?;
+            return Ok((
+                metadata::encode_trimmed(trim, format, &data),
+                EntryFormat::Trimmed,
+            ));
+        }
+    }
+
+    encode_prepared(im, options)
+}
+
+/// Write the image that is already resized and converted to the
+/// storage format of the options.
+fn encode_prepared(
+    im: &image::DynamicImage,
+    options: &EncodeOptions,
+) -> Result<(Vec<u8>, EntryFormat), SfaError> {
+    match options.format {
+        StorageFormat::Png => Ok((encode_png(im, options)?, EntryFormat::Png)),
+        StorageFormat::Jpeg { quality } => {
+            // JPEG has no alpha channel
+            let im = match im {
+                image::DynamicImage::ImageLuma8(_) | image::DynamicImage::ImageRgb8(_) => {
+                    im.clone()
+                }
+                _ => image::DynamicImage::ImageRgb8(im.to_rgb8()),
+            };
+            let mut buffer: Vec<u8> = vec![];
+            im.write_to(&mut buffer, ImageOutputFormat::Jpeg(quality.clamp(1, 100)))?;
+            Ok((buffer, EntryFormat::Jpeg))
+        }
+        #[cfg(feature = "webp")]
+        StorageFormat::WebP { quality } => {
+            let im = im.to_rgb8();
+            let (width, height) = im.dimensions();
+            let data =
+                webp::Encoder::from_rgb(&im, width, height).encode(f32::from(quality.min(100)));
+            Ok((data.to_vec(), EntryFormat::WebP))
+        }
+        #[cfg(feature = "webp")]
+        StorageFormat::WebPLossless => {
+            let (width, height) = im.dimensions();
+            let data = if im.color().has_alpha() {
+                webp::Encoder::from_rgba(&im.to_rgba8(), width, height).encode_lossless()
+            } else {
+                webp::Encoder::from_rgb(&im.to_rgb8(), width, height).encode_lossless()
+            };
+            Ok((data.to_vec(), EntryFormat::WebP))
+        }
+        #[cfg(feature = "qoi")]
+        StorageFormat::Qoi => {
+            let (width, height) = im.dimensions();
+            let data = if im.color().has_alpha() {
+                qoi::encode_to_vec(im.to_rgba8().into_raw(), width, height)
+            } else {
+                qoi::encode_to_vec(im.to_rgb8().into_raw(), width, height)
+            };
+            let data = data.map_err(|e| {
+                image::ImageError::Encoding(image::error::EncodingError::new(
+                    image::error::ImageFormatHint::Name(String::from("QOI")),
+                    e,
+                ))
+            })?;
+            Ok((data, EntryFormat::Qoi))
+        }
+    }
+}
+
+/// Write the image in PNG format to a `Vec<u8>`.
+fn encode_png(im: &image::DynamicImage, options: &EncodeOptions) -> Result<Vec<u8>, SfaError> {
+    if let Some(quantize) = &options.quantize {
+        if let Some(png) = palette::encode_indexed(im, quantize, options) {
+            return Ok(png);
+        }
+    }
+
+    if options.interlaced {
+        return png::encode_image(im, options, true);
+    }
+
+    let mut buffer: Vec<u8> = vec![];
+    write_png(im, options, &mut buffer)?;
+
+    Ok(buffer)
+}
+
+/// Write the image in non interlaced PNG format to the writer.
+fn write_png<W: Write>(
+    im: &image::DynamicImage,
+    options: &EncodeOptions,
+    mut writer: W,
+) -> Result<(), SfaError> {
+    let sixteen_bit = matches!(
+        im,
+        image::DynamicImage::ImageLuma16(_)
+            | image::DynamicImage::ImageLumaA16(_)
+            | image::DynamicImage::ImageRgb16(_)
+            | image::DynamicImage::ImageRgba16(_)
+    );
+    if options.compression == CompressionLevel::Default
+        && options.filter == PngFilter::Sub
+        && !sixteen_bit
+    {
+        // Kept on the same code path as before options existed so that
+        // the output stays byte for byte the same.
+        im.write_to(&mut writer, ImageOutputFormat::Png)?;
+    } else {
+        // PNG has no BGR color types
+        let im = match im {
+            image::DynamicImage::ImageBgr8(_) => image::DynamicImage::ImageRgb8(im.to_rgb8()),
+            image::DynamicImage::ImageBgra8(_) => image::DynamicImage::ImageRgba8(im.to_rgba8()),
+            _ => im.clone(),
+        };
+        let filter = match options.filter.image_filter() {
+            Some(filter) => filter,
+            // The `image` crate can not choose the filter of each row
+            None => {
+                writer.write_all(&png::encode_image(&im, options, false)?)?;
+                return Ok(());
+            }
+        };
+        // PNG stores 16 bit samples in big endian order, which `image`
+        // does not convert them to
+        let samples: Cow<[u8]> = match &im {
+            image::DynamicImage::ImageLuma16(buffer) => Cow::Owned(png::to_big_endian(buffer)),
+            image::DynamicImage::ImageLumaA16(buffer) => Cow::Owned(png::to_big_endian(buffer)),
+            image::DynamicImage::ImageRgb16(buffer) => Cow::Owned(png::to_big_endian(buffer)),
+            image::DynamicImage::ImageRgba16(buffer) => Cow::Owned(png::to_big_endian(buffer)),
+            _ => Cow::Borrowed(im.as_bytes()),
+        };
+        let (width, height) = im.dimensions();
+        PngEncoder::new_with_quality(writer, options.compression.into(), filter).encode(
+            &samples,
+            width,
+            height,
+            im.color(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Prefix names that contain a `:` with their length so that the
+/// name can not be confused with the size. Sizes never start with a
+/// `=`, so files written before names had a prefix read the same.
+fn frame_name(name: &str) -> String {
+    match name.contains(':') {
+        true => format!("{}:={}", name.len(), name),
+        false => name.to_owned(),
+    }
+}
+
+/// Decodes an SFA file from disk and returns a result of `sfa::SfaError`
+/// or the expected `HashMap<String, image::DynamicImage>`. The values
+/// can then be converted for use with other graphics generating libraries.
+///
+/// Please refer to `sfa::decode_from_reader` for more insight because
+/// this is a wrapper over that function.
+///
+/// # Arguments
+///
+/// * `file` - A path like object that refers to the file to read for decoding. (`AsRef<Path>`)
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::collections::HashMap;
+/// use sfa::decode;
+/// use image::DynamicImage;
+///
+/// let my_sprite: HashMap<String, DynamicImage> = decode("sp.sfa").unwrap();
+///
+/// let my_sprite_frame_1 = &my_sprite["sp1.png"];
+/// ```
+#[cfg(feature = "fs")]
+pub fn decode<P: AsRef<Path>>(file: P) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    decode_from_reader(&mut file)
+}
+
+/// Decodes a sfa file from disk and reports the progress after each
+/// entry is decoded.
+///
+/// Please refer to `sfa::decode_from_reader_with_progress` for more
+/// insight because this is a wrapper over that function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::decode_with_progress;
+///
+/// let my_sprite = decode_with_progress("sp.sfa", |done, total| {
+///     eprint!("\rdecoded {}/{}", done, total);
+/// })
+/// .unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn decode_with_progress<P: AsRef<Path>, C: FnMut(usize, usize)>(
+    file: P,
+    on_progress: C,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    decode_from_reader_with_progress(&mut file, on_progress)
+}
+
+/// Decodes sfa file from a reader object that implements
+/// the trait `std::io::Read`. It returns a in memory HashMap
+/// with keys being `String` objects which are the names of the
+/// entries, usually the file names of the original files, and
+/// the values are `image::DynamicImage` which are always
+/// in PNG format. You might want to use this function for reading
+/// from TcpStreams or some in memory buffer.
+///
+/// The file is read incrementally, and the data of each entry is
+/// decoded before the next one is read, so only the data of one entry
+/// is kept in memory next to the decoded images. The headers of the
+/// entries are read one byte at a time, so it is recommended to wrap
+/// unbuffered readers like `std::net::TcpStream` in a `std::io::BufReader`.
+///
+/// The entries are checked against the limits of `DecodeOptions::default()`,
+/// which only stop files that are corrupt or made to exhaust memory. Use
+/// `sfa::decode_from_reader_with_options` with `DecodeOptions::unlimited()`
+/// for trusted files that are larger.
+///
+/// Most of the times you only want to read from disk and is thus
+/// recommended to use `sfa::decode` instead for convenience.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+/// * An entry is not a valid image or has an unknown format tag.
+///   (`SfaError::DecodeEntry` which contains the name of the entry, or
+///   `SfaError::UnknownFormatTag`)
+/// * The file is beyond the limits of `DecodeOptions::default()`.
+///   (`SfaError::TooManyEntries`, `SfaError::EntryTooLarge` or
+///   `SfaError::TotalSizeTooLarge`)
+/// * Two entries have the same name. (`SfaError::DuplicateName`)
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::collections::HashMap;
+/// # use std::fs;
+/// use std::io::Read;
+/// use image::DynamicImage;
+/// use sfa::decode_from_reader;
+///
+/// let mut my_sprite_sfa_file = fs::File::open("sp1.sfa").unwrap();
+///
+/// let my_sprite: HashMap<String, DynamicImage> = decode_from_reader(&mut my_sprite_sfa_file).unwrap();
+/// ```
+///
+/// The kind of failure can be found out by matching on the error
+///
+/// ```
+/// use sfa::{decode_from_reader, SfaError};
+///
+/// match decode_from_reader(&mut b"PNG;".as_ref()) {
+///     Err(SfaError::MissingMagic) => (),
+///     _ => panic!("expected the magic text to be missing"),
+/// }
+///
+/// // Files shorter than the magic text, and 4 byte files that are not it
+/// for file in [&b""[..], b"S", b"SF", b"SFA", b"SFA:", b"sfa;"] {
+///     match decode_from_reader(&mut file.as_ref()) {
+///         Err(SfaError::MissingMagic) => (),
+///         _ => panic!("expected the magic text to be missing"),
+///     }
+/// }
+///
+/// match decode_from_reader(&mut b"SFA;a.png:12".as_ref()) {
+///     Err(SfaError::TruncatedHeader(8)) => (),
+///     _ => panic!("expected the file to be truncated"),
+/// }
+///
+/// // The size is checked against the data that is actually there
+/// // before allocating memory for it
+/// match decode_from_reader(&mut b"SFA;a.png:1000000000:abc".as_ref()) {
+///     Err(SfaError::InvalidSize(size)) => assert_eq!(size, "1000000000"),
+///     _ => panic!("expected the size to be invalid"),
+/// }
+///
+/// // Sizes over the limits of `DecodeOptions::default()` are rejected
+/// // before that
+/// match decode_from_reader(&mut b"SFA;a.png:4000000000:abc".as_ref()) {
+///     Err(SfaError::EntryTooLarge { size, .. }) => assert_eq!(size, 4000000000),
+///     _ => panic!("expected the entry to be too large"),
+/// }
+///
+/// // Sizes that do not fit into a u64 or are not plain digits are
+/// // rejected
+/// for size in ["18446744073709551616", "+3", "-3", "3 ", ""] {
+///     let file = format!("SFA;a.png:{}:abc", size);
+///     for result in [
+///         decode_from_reader(&mut file.as_bytes()),
+///         sfa::decode_from_slice(file.as_bytes()),
+///     ] {
+///         match result {
+///             Err(SfaError::InvalidSize(invalid)) => assert_eq!(invalid, size),
+///             _ => panic!("expected the size {:?} to be invalid", size),
+///         }
+///     }
+/// }
+///
+/// // Junk appended to an archive is not read as another entry
+/// let mut archive = b"SFA;".to_vec();
+/// archive.extend_from_slice(b"junk");
+/// match decode_from_reader(&mut archive.as_slice()) {
+///     Err(SfaError::TruncatedHeader(4)) => (),
+///     _ => panic!("expected the junk to be rejected"),
+/// }
+///
+/// // Entries that are not valid images are named in the error
+/// match decode_from_reader(&mut b"SFA;a.png:3:abc".as_ref()) {
+///     Err(SfaError::DecodeEntry { name, .. }) => assert_eq!(name, "a.png"),
+///     _ => panic!("expected the entry to fail"),
+/// }
+///
+/// // A broken entry between two valid ones is named along with the
+/// // offset of its data
+/// let mut png: Vec<u8> = vec![];
+/// image::DynamicImage::new_rgba8(2, 2)
+///     .write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png)
+///     .unwrap();
+/// let mut archive = b"SFA;".to_vec();
+/// archive.extend_from_slice(format!("a.png:{}:", png.len()).as_bytes());
+/// archive.extend_from_slice(&png);
+/// archive.extend_from_slice(b"b.png:3:");
+/// let expected = archive.len() as u64;
+/// archive.extend_from_slice(b"abc");
+/// archive.extend_from_slice(format!("c.png:{}:", png.len()).as_bytes());
+/// archive.extend_from_slice(&png);
+/// for result in [
+///     decode_from_reader(&mut archive.as_slice()),
+///     sfa::decode_from_slice(&archive),
+/// ] {
+///     match result {
+///         Err(e @ SfaError::DecodeEntry { .. }) => {
+///             let message = e.to_string();
+///             assert!(message.contains(&format!("\"b.png\" at offset {}", expected)));
+///             match e {
+///                 SfaError::DecodeEntry { name, offset, .. } => {
+///                     assert_eq!(name, "b.png");
+///                     assert_eq!(offset, expected);
+///                 }
+///                 _ => unreachable!(),
+///             }
+///         }
+///         _ => panic!("expected the middle entry to fail"),
+///     }
+/// }
+/// ```
+///
+/// Nothing after an entry is read before it is decoded
+///
+/// ```
+/// use std::io::{self, Read};
+/// use image::DynamicImage;
+/// use sfa::{decode_from_reader, encode_images_to_vec, SfaError};
+///
+/// /// A stream that yields at most 3 bytes at a time
+/// struct Trickle<'a> {
+///     data: &'a [u8],
+///     read: usize,
+/// }
+///
+/// impl Read for Trickle<'_> {
+///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+///         let size = buf.len().min(3);
+///         let read = (&self.data[self.read..]).read(&mut buf[..size])?;
+///         self.read += read;
+///         Ok(read)
+///     }
+/// }
+///
+/// let frames = vec![(String::from("b.png"), DynamicImage::new_rgba8(64, 64))];
+/// let valid = encode_images_to_vec(frames).unwrap();
+///
+/// let mut stream = Trickle { data: &valid, read: 0 };
+/// let decoded = decode_from_reader(&mut stream).unwrap();
+/// assert!(decoded.contains_key("b.png"));
+/// assert_eq!(stream.read, valid.len());
+///
+/// // A broken first entry stops the decoding before the next one is read
+/// let mut archive = b"SFA;a.png:3:abc".to_vec();
+/// archive.extend_from_slice(&valid[4..]);
+/// let mut stream = Trickle { data: &archive, read: 0 };
+/// let result = decode_from_reader(&mut stream);
+/// assert!(matches!(result, Err(SfaError::DecodeEntry { name, .. }) if name == "a.png"));
+/// assert_eq!(stream.read, b"SFA;a.png:3:abc".len());
+/// ```
+pub fn decode_from_reader<F: Read>(
+    reader: &mut F,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    collect_unique(SfaReader::new(reader)?.frames())
+}
+
+/// Decodes a sfa file from disk with the given options.
+///
+/// Please refer to `sfa::decode_from_reader_with_options` for more
+/// insight because this is a wrapper over that function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::{decode_with_options, DecodeOptions};
+///
+/// let options = DecodeOptions {
+///     max_dimension: Some(128),
+///     ..Default::default()
+/// };
+/// let thumbnails = decode_with_options("sp.sfa", &options).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn decode_with_options<P: AsRef<Path>>(
+    file: P,
+    options: &DecodeOptions,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    decode_from_reader_with_options(&mut file, options)
+}
+
+/// Decodes sfa file from a reader object like `sfa::decode_from_reader`
+/// with the given options. The limits on the number and size of the
+/// entries guard services that decode untrusted files against running
+/// out of memory, and `DecodeOptions::max_dimension` scales the images
+/// down.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+/// * `options` - A reference to `sfa::DecodeOptions`.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::decode_from_reader`, with
+/// the limits of the options instead of those of `DecodeOptions::default()`.
+///
+/// Entries with the same name are kept as `DecodeOptions::duplicates`
+/// says, which fails with `SfaError::DuplicateEntry` by default instead
+/// of `SfaError::DuplicateName`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs;
+/// use std::io::BufReader;
+/// use sfa::{decode_from_reader_with_options, DecodeOptions};
+///
+/// let options = DecodeOptions {
+///     max_entries: Some(1000),
+///     max_entry_bytes: Some(16 * 1024 * 1024),
+///     max_total_bytes: Some(256 * 1024 * 1024),
+///     ..Default::default()
+/// };
+///
+/// let mut upload = BufReader::new(fs::File::open("upload.sfa").unwrap());
+/// let my_sprite = decode_from_reader_with_options(&mut upload, &options).unwrap();
+/// ```
+pub fn decode_from_reader_with_options<F: Read>(
+    reader: &mut F,
+    options: &DecodeOptions,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    SfaReader::with_options(reader, *options)?.collect_frames()
+}
+
+/// Decodes a sfa file from disk with the given options and returns the
+/// entries that were skipped because they could not be decoded.
+///
+/// Please refer to `sfa::decode_from_reader_with_warnings` for more
+/// insight because this is a wrapper over that function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::{decode_with_warnings, DecodeOptions, ErrorPolicy};
+///
+/// let options = DecodeOptions {
+///     on_error: ErrorPolicy::Skip,
+///     ..Default::default()
+/// };
+/// let (frames, warnings) = decode_with_warnings("sp.sfa", &options).unwrap();
+/// for warning in warnings {
+///     eprintln!("skipped {}: {}", warning.name, warning.reason);
+/// }
+/// ```
+#[cfg(feature = "fs")]
+pub fn decode_with_warnings<P: AsRef<Path>>(
+    file: P,
+    options: &DecodeOptions,
+) -> Result<(HashMap<String, image::DynamicImage>, Vec<DecodeWarning>), SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    decode_from_reader_with_warnings(&mut file, options)
+}
+
+/// Decodes sfa file from a reader object like
+/// `sfa::decode_from_reader_with_options` and returns the entries that
+/// were skipped because they could not be decoded along with the
+/// decoded ones. Entries are only skipped with `ErrorPolicy::Skip` in
+/// `DecodeOptions::on_error`, which keeps one broken frame from
+/// failing a whole archive. Errors in the headers of the entries are
+/// still returned, since the entries after them can not be found.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+/// * `options` - A reference to `sfa::DecodeOptions`.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::decode_from_reader_with_options`,
+/// apart from the ones of entries that are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_reader_with_warnings, DecodeOptions, ErrorPolicy, SfaError};
+///
+/// let mut archive = b"SFA;".to_vec();
+/// for (name, data) in [("walk_1.png", &b"abc"[..]), ("walk_2.png", &b"xyz"[..])] {
+///     archive.extend_from_slice(format!("{}:{}:", name, data.len()).as_bytes());
+///     archive.extend_from_slice(data);
+/// }
+/// let mut png = vec![];
+/// DynamicImage::new_rgba8(4, 4)
+///     .write_to(&mut png, image::ImageOutputFormat::Png)
+///     .unwrap();
+/// archive.extend_from_slice(format!("walk_3.png:{}:", png.len()).as_bytes());
+/// archive.extend_from_slice(&png);
+///
+/// let options = DecodeOptions {
+///     on_error: ErrorPolicy::Skip,
+///     ..Default::default()
+/// };
+/// let (frames, warnings) =
+///     decode_from_reader_with_warnings(&mut archive.as_slice(), &options).unwrap();
+/// assert_eq!(frames.len(), 1);
+/// assert!(frames.contains_key("walk_3.png"));
+/// let skipped: Vec<&str> = warnings.iter().map(|warning| warning.name.as_str()).collect();
+/// assert_eq!(skipped, ["walk_1.png", "walk_2.png"]);
+/// assert!(matches!(warnings[0].reason, SfaError::DecodeEntry { .. }));
+///
+/// // The first broken entry fails the whole file by default
+/// let options = DecodeOptions::default();
+/// let result = decode_from_reader_with_warnings(&mut archive.as_slice(), &options);
+/// assert!(matches!(result, Err(SfaError::DecodeEntry { name, .. }) if name == "walk_1.png"));
+/// ```
+pub fn decode_from_reader_with_warnings<F: Read>(
+    reader: &mut F,
+    options: &DecodeOptions,
+) -> Result<(HashMap<String, image::DynamicImage>, Vec<DecodeWarning>), SfaError> {
+    let mut reader = SfaReader::with_options(reader, *options)?;
+    let frames = reader.collect_frames()?;
+
+    Ok((frames, reader.take_warnings()))
+}
+
+/// Decodes sfa file from a reader object like `sfa::decode_from_reader`
+/// and reports the progress after each entry is decoded. The headers of
+/// the entries are read first to count them, seeking over their data,
+/// and the reader is then moved back to decode the entries one at a time.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the traits `io::Read` and
+///   `io::Seek`, positioned at the start of the sfa file.
+/// * `on_progress` - Called with the number of entries decoded so far
+///   and the total number of entries, after each entry.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::decode_from_reader`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use image::DynamicImage;
+/// use sfa::{decode_from_reader_with_progress, encode_images_to_vec};
+///
+/// let names = ["frame_1.png", "frame_2.png"];
+/// let archive =
+///     encode_images_to_vec(names.iter().map(|name| (name, DynamicImage::new_rgba8(4, 4))))
+///         .unwrap();
+///
+/// let mut calls = vec![];
+/// decode_from_reader_with_progress(&mut Cursor::new(archive), |done, total| {
+///     calls.push((done, total))
+/// })
+/// .unwrap();
+/// assert_eq!(calls, [(1, 2), (2, 2)]);
+/// ```
+pub fn decode_from_reader_with_progress<F: Read + Seek, C: FnMut(usize, usize)>(
+    reader: &mut F,
+    mut on_progress: C,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    let start = reader.stream_position()?;
+    let total = count_entries(reader)?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    let mut done = 0;
+    collect_unique(SfaReader::new(reader)?.frames().inspect(|entry| {
+        if entry.is_ok() {
+            done += 1;
+            on_progress(done, total);
+        }
+    }))
+}
+
+/// Count the entries of a file by reading their headers and seeking
+/// over their data.
+fn count_entries<R: Read + Seek>(reader: &mut R) -> Result<usize, SfaError> {
+    let layout = read_magic(reader)?;
+
+    let mut headers = Headers::new(layout);
+    while let Some(header) = headers.next(reader)? {
+        seek_payload(reader, header.size)?;
+    }
+
+    Ok(headers.count)
+}
+
+/// Decodes sfa file from a reader object that implements the trait
+/// `tokio::io::AsyncRead`, such as a TCP stream or the body of a HTTP
+/// response. Only available with the `tokio` feature.
+///
+/// The whole file is read into memory without blocking first. The images
+/// are then decoded on a thread of the blocking pool of the runtime with
+/// `tokio::task::spawn_blocking`, so decoding does not hold up the other
+/// tasks of the worker thread. It must be awaited within a tokio runtime.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `tokio::io::AsyncRead`.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::decode_from_reader`, and
+/// `SfaError::Io` if the runtime shuts down before the images are decoded.
+///
+/// # Panics
+///
+/// If it is not awaited within a tokio runtime.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tokio::io::AsyncRead;
+/// use sfa::{decode_from_async_reader, SfaError};
+///
+/// async fn load_sprite<R: AsyncRead + Unpin>(body: &mut R) -> Result<(), SfaError> {
+///     let my_sprite = decode_from_async_reader(body).await?;
+///     let my_sprite_frame_1 = &my_sprite["sp1.png"];
+///     Ok(())
+/// }
+/// ```
+///
+/// The images are also decoded on the blocking pool of a runtime with a
+/// single thread.
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_async_reader, encode_images_to_vec};
+///
+/// let frames = vec![(String::from("sp1.png"), DynamicImage::new_rgba8(4, 4))];
+/// let archive = encode_images_to_vec(frames).unwrap();
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let my_sprite = runtime
+///     .block_on(decode_from_async_reader(&mut archive.as_slice()))
+///     .unwrap();
+/// assert!(my_sprite.contains_key("sp1.png"));
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn decode_from_async_reader<R: tokio::io::AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    use tokio::io::AsyncReadExt;
+
+    let mut buffer: Vec<u8> = vec![];
+    reader.read_to_end(&mut buffer).await?;
+
+    match tokio::task::spawn_blocking(move || decode_from_slice(&buffer)).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(io::Error::other(e).into()),
+    }
+}
+
+/// Decodes an SFA file from disk while preserving the order in which
+/// the entries were packed. This is what you want for animated sprites
+/// where frame 1, frame 2, frame 3 must be played back in sequence.
+///
+/// Please refer to `sfa::decode_from_reader_ordered` for more insight
+/// because this is a wrapper over that function.
+///
+/// # Arguments
+///
+/// * `file` - A path like object that refers to the file to read for decoding. (`AsRef<Path>`)
+///
+/// # Errors
+///
+/// Same as `sfa::decode_from_reader_ordered` with the addition of
+/// errors raised while opening the file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::decode_ordered;
+///
+/// let frames = decode_ordered("sp.sfa").unwrap();
+///
+/// for (name, frame) in &frames {
+///     println!("{} is {:?}", name, frame.color());
+/// }
+/// ```
+#[cfg(feature = "fs")]
+pub fn decode_ordered<P: AsRef<Path>>(
+    file: P,
+) -> Result<Vec<(String, image::DynamicImage)>, SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    decode_from_reader_ordered(&mut file)
+}
+
+/// Decodes sfa file from a reader object that implements the trait
+/// `std::io::Read` and returns the entries in exactly the order they
+/// appear in the file. `sfa::decode_from_reader` returns the same
+/// entries collected in a `HashMap`. Like it, the file is read
+/// incrementally.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+/// * An entry is not a valid image or has an unknown format tag.
+///   (`SfaError::DecodeEntry` which contains the name of the entry, or
+///   `SfaError::UnknownFormatTag`)
+/// * The file is beyond the limits of `DecodeOptions::default()`.
+///   (`SfaError::TooManyEntries`, `SfaError::EntryTooLarge` or
+///   `SfaError::TotalSizeTooLarge`)
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::fs;
+/// use sfa::decode_from_reader_ordered;
+///
+/// let mut my_sprite_sfa_file = fs::File::open("sp1.sfa").unwrap();
+///
+/// let frames = decode_from_reader_ordered(&mut my_sprite_sfa_file).unwrap();
+/// let names: Vec<&str> = frames.iter().map(|(name, _)| name.as_str()).collect();
+/// ```
+///
+/// The entries come back in the order they were encoded in, which
+/// need not be the order of their names.
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_reader_ordered, encode_images_to_vec};
+///
+/// let names = ["frame_10.png", "frame_2.png", "frame_1.png"];
+/// let archive =
+///     encode_images_to_vec(names.iter().map(|name| (name, DynamicImage::new_rgba8(4, 4))))
+///         .unwrap();
+///
+/// let frames = decode_from_reader_ordered(&mut archive.as_slice()).unwrap();
+/// let decoded: Vec<&str> = frames.iter().map(|(name, _)| name.as_str()).collect();
+/// assert_eq!(decoded, names);
+/// ```
+pub fn decode_from_reader_ordered<F: Read>(
+    reader: &mut F,
+) -> Result<Vec<(String, image::DynamicImage)>, SfaError> {
+    SfaReader::new(reader)?.frames().collect()
+}
+
+/// Decodes sfa file contents that are already in memory, for example
+/// embedded with `include_bytes!`. The entries are decoded straight
+/// from the slice without copying their data first.
+///
+/// # Arguments
+///
+/// * `bytes` - The contents of a sfa file.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * The contents do not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+/// * An entry is not a valid image or has an unknown format tag.
+///   (`SfaError::DecodeEntry` which contains the name of the entry, or
+///   `SfaError::UnknownFormatTag`)
+/// * Two entries have the same name. (`SfaError::DuplicateName`)
+/// * The file is beyond the limits of `DecodeOptions::default()`.
+///   (`SfaError::TooManyEntries`, `SfaError::EntryTooLarge` or
+///   `SfaError::TotalSizeTooLarge`)
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_slice, encode_images_to_writer};
+///
+/// let frames = vec![(String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4))];
+/// let mut archive = vec![];
+/// encode_images_to_writer(frames, &mut archive).unwrap();
+///
+/// let decoded = decode_from_slice(&archive).unwrap();
+/// assert!(decoded.contains_key("frame_1.png"));
+/// ```
+///
+/// Archives embedded in the binary need no `std::io::Cursor`. This
+/// example is not compiled because it needs `sprites.sfa` next to the
+/// source file.
+///
+/// ```ignore
+/// let sprites = sfa::decode_from_slice(include_bytes!("sprites.sfa")).unwrap();
+/// ```
+pub fn decode_from_slice(bytes: &[u8]) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    collect_unique(
+        decode_slice_ordered(bytes, &DecodeOptions::default())?
+            .into_iter()
+            .map(Ok),
+    )
+}
+
+/// Decodes sfa file from disk by mapping it into memory instead of
+/// reading it into a buffer. The images are decoded straight from the
+/// mapped bytes like `sfa::decode_from_slice` does, so the data of the
+/// entries is never copied. Only available with the `mmap` feature.
+///
+/// Use `sfa::SfaMmap` to also get the stored image data of the entries
+/// borrowed from the mapped bytes.
+///
+/// # Safety
+///
+/// The file must not be changed while it is decoded. Please refer to
+/// `sfa::SfaMmap::open` for what happens if it is.
+///
+/// # Errors
+///
+/// * Opening or mapping the file was unsuccessful. (`SfaError::Io`)
+/// * The other errors are the same as those of `sfa::decode_from_slice`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::decode_mmap;
+///
+/// // Safety: nothing writes to the file while it is decoded
+/// let my_sprite = unsafe { decode_mmap("sp.sfa") }.unwrap();
+/// let frame_1 = &my_sprite["sp1.png"];
+/// ```
+#[cfg(feature = "mmap")]
+pub unsafe fn decode_mmap<P: AsRef<Path>>(
+    file: P,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    SfaMmap::open(file)?.decode()
+}
+
+/// Decodes sfa file from a reader object that implements the trait
+/// `std::io::Read` into any container that implements
+/// `sfa::FromSfaEntries`. `sfa::decode_from_reader` is the same as
+/// decoding into a `HashMap` with this function.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::decode_from_reader`.
+/// `Vec` containers never return `SfaError::DuplicateName`.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::{BTreeMap, HashMap};
+/// use image::DynamicImage;
+/// use sfa::{decode_into, encode_images_to_vec};
+///
+/// let names = ["frame_2.png", "frame_1.png", "frame_3.png"];
+/// let archive =
+///     encode_images_to_vec(names.iter().map(|name| (name, DynamicImage::new_rgba8(4, 4))))
+///         .unwrap();
+///
+/// let by_name: HashMap<String, DynamicImage> = decode_into(&mut archive.as_slice()).unwrap();
+/// assert!(by_name.contains_key("frame_3.png"));
+///
+/// let sorted: BTreeMap<String, DynamicImage> = decode_into(&mut archive.as_slice()).unwrap();
+/// assert!(sorted.keys().eq(["frame_1.png", "frame_2.png", "frame_3.png"]));
+///
+/// let in_order: Vec<(String, DynamicImage)> = decode_into(&mut archive.as_slice()).unwrap();
+/// assert!(in_order.iter().map(|(name, _)| name).eq(names));
+/// ```
+pub fn decode_into<C: FromSfaEntries, R: Read>(reader: &mut R) -> Result<C, SfaError> {
+    let entries = SfaReader::new(reader)?
+        .frames()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    C::from_sfa_entries(entries)
+}
+
+/// Reads the stored image data of the entries of a sfa file from disk
+/// without decoding the images.
+///
+/// Please refer to `sfa::decode_raw_from_reader` for more insight
+/// because this is a wrapper over that function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::decode_raw;
+///
+/// let my_sprite = decode_raw("sp.sfa").unwrap();
+/// let png_of_frame_1: &[u8] = &my_sprite["sp1.png"];
+/// ```
+#[cfg(feature = "fs")]
+pub fn decode_raw<P: AsRef<Path>>(file: P) -> Result<HashMap<String, Vec<u8>>, SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    decode_raw_from_reader(&mut file)
+}
+
+/// Reads the stored image data of the entries of a sfa file from a
+/// reader object that implements the trait `std::io::Read`, without
+/// decoding the images. This is much faster than `sfa::decode_from_reader`
+/// when the images are only passed on, for example served to browsers.
+///
+/// The data is in the format the entry is stored in, which is always
+/// PNG for files written with the default options. Aliases have the
+/// data of the entries they refer to and trimmed entries the data of
+/// the trimmed image. Delta entries only store the changes to the
+/// entry before them, so their images are decoded and encoded as PNG.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+/// * A PNG entry does not start with the PNG signature. Other than that
+///   the data is not checked. (`SfaError::DecodeEntry`)
+/// * Two entries have the same name. (`SfaError::DuplicateName`)
+/// * The file is beyond the limits of `DecodeOptions::default()`.
+///   (`SfaError::TooManyEntries`, `SfaError::EntryTooLarge` or
+///   `SfaError::TotalSizeTooLarge`)
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_reader, decode_raw_from_reader, encode_images_to_vec};
+///
+/// let frames = vec![(String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4))];
+/// let archive = encode_images_to_vec(frames).unwrap();
+///
+/// let raw = decode_raw_from_reader(&mut archive.as_slice()).unwrap();
+/// let decoded = decode_from_reader(&mut archive.as_slice()).unwrap();
+///
+/// let from_raw = image::load_from_memory(&raw["frame_1.png"]).unwrap();
+/// assert_eq!(from_raw.as_bytes(), decoded["frame_1.png"].as_bytes());
+/// ```
+pub fn decode_raw_from_reader<F: Read>(
+    reader: &mut F,
+) -> Result<HashMap<String, Vec<u8>>, SfaError> {
+    let mut reader = Positioned::new(reader);
+    let layout = read_magic(&mut reader)?;
+
+    let options = DecodeOptions::default();
+    let mut total: u64 = 0;
+    let mut results: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut buffer: Vec<u8> = vec![];
+    // Format and offset of the image data of the entries that aliases can
+    // refer to, whose data is kept in `results`
+    let mut targets: AliasTargets<(EntryFormat, u64)> = AliasTargets::new();
+    // The previous entry, whose image is only decoded if a delta entry follows
+    let mut base: Option<(String, EntryFormat, u64)> = None;
+    let mut previous: Option<image::DynamicImage> = None;
+    let mut headers = Headers::new(layout);
+    while let Some(header) = headers.next(&mut reader)? {
+        total = total.saturating_add(header.size as u64);
+        options.check(headers.count, total, &header.name, header.size)?;
+        let offset = reader.position();
+        read_payload(&mut reader, &header, &mut buffer)?;
+        let raw = match header.format {
+            EntryFormat::Alias => {
+                let (target, &(format, offset)) = targets.resolve(&header, &buffer)?;
+                let raw = results[&target].clone();
+                base = Some((target, format, offset));
+                raw
+            }
+            EntryFormat::Delta => {
+                if let Some((name, format, offset)) = base.take() {
+                    previous = Some(load_entry(&name, offset, format, &results[&name])?);
+                }
+                let im = match &previous {
+                    Some(previous) => delta::apply(&header.name, offset, previous, &buffer)?,
+                    None => return Err(SfaError::InvalidDelta(header.name.clone())),
+                };
+                let png = encode_png(&im, &EncodeOptions::default())?;
+                previous = Some(im);
+                png
+            }
+            format => {
+                let (format, data) = raw_image_data(&header.name, offset, format, &buffer)?;
+                targets.add(&header, (format, offset));
+                base = Some((header.name.clone(), format, offset));
+                data.to_vec()
+            }
+        };
+
+        match results.entry(header.name) {
+            Entry::Occupied(entry) => return Err(SfaError::DuplicateName(entry.key().clone())),
+            Entry::Vacant(entry) => {
+                entry.insert(raw);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Collect decoded entries into a `HashMap`. Returns
+/// `SfaError::DuplicateName` for the first name that is repeated
+/// instead of silently dropping one of the entries.
+fn collect_unique<I, V>(entries: I) -> Result<HashMap<String, V>, SfaError>
+where
+    I: IntoIterator<Item = Result<(String, V), SfaError>>,
+{
+    let mut results: HashMap<String, V> = HashMap::new();
+    for entry in entries {
+        let (name, im) = entry?;
+        match results.entry(name) {
+            Entry::Occupied(entry) => return Err(SfaError::DuplicateName(entry.key().clone())),
+            Entry::Vacant(entry) => {
+                entry.insert(im);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Decodes sfa file from a reader object like
+/// `sfa::decode_from_reader_ordered`, along with the metadata stored with
+/// each entry.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+/// * An entry is not a valid image or has an unknown format tag.
+///   (`SfaError::DecodeEntry` which contains the name of the entry, or
+///   `SfaError::UnknownFormatTag`)
+/// * The file is beyond the limits of `DecodeOptions::default()`.
+///   (`SfaError::TooManyEntries`, `SfaError::EntryTooLarge` or
+///   `SfaError::TotalSizeTooLarge`)
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, Rgba, RgbaImage};
+/// use sfa::{decode_with_entry_metadata, EncodeOptions, SfaBuilder, Trim};
+///
+/// let frame = RgbaImage::from_fn(256, 256, |x, y| match (x, y) {
+///     (40..=49, 100..=109) => Rgba([255, 0, 0, 255]),
+///     _ => Rgba([0, 0, 0, 0]),
+/// });
+/// let options = EncodeOptions {
+///     trim_transparent: true,
+///     ..Default::default()
+/// };
+/// let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+/// builder.add_image("walk_01.png", &DynamicImage::ImageRgba8(frame)).unwrap();
+/// let archive = builder.finish().unwrap();
+///
+/// let decoded = decode_with_entry_metadata(&mut archive.as_slice()).unwrap();
+/// let (name, im, metadata) = &decoded[0];
+/// assert_eq!(name, "walk_01.png");
+/// assert_eq!(im.to_rgba8().dimensions(), (10, 10));
+/// assert_eq!(metadata.trim, Some(Trim { x: 40, y: 100, width: 256, height: 256 }));
+/// ```
+pub fn decode_with_entry_metadata<F: Read>(
+    reader: &mut F,
+) -> Result<Vec<(String, image::DynamicImage, EntryMetadata)>, SfaError> {
+    let mut reader = SfaReader::new(reader)?;
+
+    let mut results: Vec<(String, image::DynamicImage, EntryMetadata)> = vec![];
+    while let Some((name, im)) = reader.next_frame()? {
+        results.push((name, im, reader.entry_metadata()));
+    }
+
+    Ok(results)
+}
+
+/// Decodes sfa file from a reader object like `sfa::decode_from_reader`,
+/// along with the metadata of the file that was written with
+/// `EncodeOptions::metadata`. The metadata is empty for files without it.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize`, `SfaError::InvalidName` or
+///   `SfaError::InvalidMetadata`)
+/// * An entry is not a valid image or has an unknown format tag.
+///   (`SfaError::DecodeEntry` which contains the name of the entry, or
+///   `SfaError::UnknownFormatTag`)
+/// * Two entries have the same name. (`SfaError::DuplicateName`)
+/// * The file is beyond the limits of `DecodeOptions::default()`.
+///   (`SfaError::TooManyEntries`, `SfaError::EntryTooLarge` or
+///   `SfaError::TotalSizeTooLarge`)
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use image::DynamicImage;
+/// use sfa::{decode_with_metadata, EncodeOptions, SfaBuilder};
+///
+/// let mut metadata = HashMap::new();
+/// metadata.insert(String::from("fps"), String::from("12"));
+/// metadata.insert(String::from("author"), String::from("Ada"));
+/// let options = EncodeOptions {
+///     metadata,
+///     ..Default::default()
+/// };
+/// let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+/// builder.add_image("run_01.png", &DynamicImage::new_rgba8(4, 4)).unwrap();
+/// let archive = builder.finish().unwrap();
+///
+/// let (images, metadata) = decode_with_metadata(&mut archive.as_slice()).unwrap();
+/// assert!(images.contains_key("run_01.png"));
+/// assert_eq!(metadata["fps"], "12");
+/// assert_eq!(metadata["author"], "Ada");
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn decode_with_metadata<F: Read>(
+    reader: &mut F,
+) -> Result<
+    (
+        HashMap<String, image::DynamicImage>,
+        HashMap<String, String>,
+    ),
+    SfaError,
+> {
+    let mut reader = SfaReader::new(reader)?;
+    let metadata = reader.take_metadata();
+    let images = collect_unique(reader.frames())?;
+
+    Ok((images, metadata))
+}
+
+/// Decode all the entries of a slice in order without copying their data.
+fn decode_slice_ordered(
+    bytes: &[u8],
+    options: &DecodeOptions,
+) -> Result<Vec<(String, image::DynamicImage)>, SfaError> {
+    let entries = decode_slice_entries(bytes, options)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|(name, im, _)| (name, im))
+        .collect())
+}
+
+/// Split a slice into the headers, the offsets and the data of its
+/// entries, which are checked against their checksums and the limits of
+/// the options.
+#[allow(clippy::type_complexity)]
+fn split_entries<'a>(
+    mut bytes: &'a [u8],
+    options: &DecodeOptions,
+) -> Result<Vec<(EntryHeader, u64, &'a [u8])>, SfaError> {
+    let length = bytes.len();
+    let layout = read_magic(&mut bytes)?;
+
+    let mut entries: Vec<(EntryHeader, u64, &[u8])> = vec![];
+    let mut total: u64 = 0;
+    let mut headers = Headers::new(layout).strict(options.strict);
+    while let Some(header) = headers.next(&mut bytes)? {
+        total = total.saturating_add(header.size as u64);
+        options.check(entries.len() + 1, total, &header.name, header.size)?;
+        if header.size > bytes.len() {
+            return Err(SfaError::InvalidSize(header.size.to_string()));
+        }
+
+        let offset = (length - bytes.len()) as u64;
+        let (data, rest) = bytes.split_at(header.size);
+        verify_checksum(&header, data)?;
+        entries.push((header, offset, data));
+        bytes = rest;
+    }
+
+    Ok(entries)
+}
+
+/// Get the stored image data of all the entries of a slice in order.
+/// Aliases get the data of the entries they refer to and trimmed entries
+/// the data of the trimmed image. Delta entries do not store a whole
+/// image, so they are decoded and encoded as PNG again. The data of the
+/// other entries is borrowed from the slice.
+#[allow(clippy::type_complexity)]
+fn decode_raw_slice(bytes: &[u8]) -> Result<Vec<(String, Cow<'_, [u8]>)>, SfaError> {
+    let entries = split_entries(bytes, &DecodeOptions::default())?;
+
+    let mut results: Vec<(String, Cow<[u8]>)> = vec![];
+    // Format and data of the entries that aliases can refer to
+    let mut targets: AliasTargets<(EntryFormat, u64, &[u8])> = AliasTargets::new();
+    // The image of the previous entry, only decoded if a delta entry follows
+    let mut previous: Option<image::DynamicImage> = None;
+    for (i, (header, offset, data)) in entries.iter().enumerate() {
+        let (format, offset, data) = match header.format {
+            EntryFormat::Alias => *targets.resolve(header, data)?.1,
+            EntryFormat::Delta => (EntryFormat::Delta, *offset, *data),
+            format => {
+                targets.add(header, (format, *offset, data));
+                (format, *offset, *data)
+            }
+        };
+        let delta_follows = matches!(
+            entries.get(i + 1),
+            Some((next, _, _)) if next.format == EntryFormat::Delta
+        );
+        let raw = if format == EntryFormat::Delta {
+            let im = match &previous {
+                Some(previous) => delta::apply(&header.name, offset, previous, data)?,
+                None => return Err(SfaError::InvalidDelta(header.name.clone())),
+            };
+            let png = encode_png(&im, &EncodeOptions::default())?;
+            previous = Some(im);
+            Cow::Owned(png)
+        } else {
+            previous = match delta_follows {
+                true => Some(load_entry(&header.name, offset, format, data)?),
+                false => None,
+            };
+            Cow::Borrowed(raw_image_data(&header.name, offset, format, data)?.1)
+        };
+        results.push((header.name.clone(), raw));
+    }
+
+    Ok(results)
+}
+
+/// The format and image data of an entry that stores a whole image, which
+/// are those of the trimmed image for trimmed entries. PNG data is checked
+/// to start with the PNG signature, other data is not checked.
+fn raw_image_data<'a>(
+    name: &str,
+    offset: u64,
+    format: EntryFormat,
+    data: &'a [u8],
+) -> Result<(EntryFormat, &'a [u8]), SfaError> {
+    let (format, data) = match format {
+        EntryFormat::Trimmed => {
+            let (_, format, data) = metadata::split_trimmed(data)?;
+            (format, data)
+        }
+        format => (format, data),
+    };
+
+    if format == EntryFormat::Png && !data.starts_with(PNG_SIGNATURE) {
+        let hint = ImageFormatHint::Exact(ImageFormat::Png);
+        let error = image::ImageError::Decoding(DecodingError::from_format_hint(hint));
+        return Err(decode_error(name, offset, error.into()));
+    }
+
+    Ok((format, data))
+}
+
+/// Decode all the entries of a slice in order along with their metadata.
+fn decode_slice_entries(
+    bytes: &[u8],
+    options: &DecodeOptions,
+) -> Result<Vec<(String, image::DynamicImage, EntryMetadata)>, SfaError> {
+    // All the entries are split up first so that an entry that fails
+    // to decode can name the delta entries that depend on it
+    let entries = split_entries(bytes, options)?;
+
+    let mut results: Vec<(String, image::DynamicImage, EntryMetadata)> = vec![];
+    // Positions of the entries in `results` that aliases can refer to
+    let mut targets: AliasTargets<usize> = AliasTargets::new();
+    for (i, (header, offset, data)) in entries.iter().enumerate() {
+        // Aliases and delta entries have the metadata of the entries
+        // that they are decoded from
+        let entry = match header.format {
+            EntryFormat::Alias => targets
+                .resolve(header, data)
+                .map(|(_, &i)| (results[i].1.clone(), results[i].2)),
+            EntryFormat::Delta => match results.last() {
+                Some((_, previous, metadata)) => {
+                    delta::apply(&header.name, *offset, previous, data).map(|im| (im, *metadata))
+                }
+                None => Err(SfaError::InvalidDelta(header.name.clone())),
+            },
+            format => {
+                targets.add(header, results.len());
+                EntryMetadata::read(format, data).and_then(|metadata| {
+                    Ok((load_entry(&header.name, *offset, format, data)?, metadata))
+                })
+            }
+        };
+
+        let following = entries[i + 1..].iter().map(|(header, _, _)| header);
+        let (im, metadata) = entry.map_err(|e| with_dependents(&header.name, following, e))?;
+        results.push((header.name.clone(), im, metadata));
+    }
+
+    Ok(results)
+}
+
+/// Decodes sfa file from a reader object that implements the trait
+/// `std::io::Read` without reading the whole file into memory. The
+/// compressed data of only one entry is kept in memory at a time, which
+/// is then decoded before moving on to the next entry. This is the same
+/// as `sfa::decode_from_reader`, which used to read the whole file first.
+///
+/// The headers of the entries are read one byte at a time, so it is
+/// recommended to wrap unbuffered readers like `std::fs::File` in a
+/// `std::io::BufReader`.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+/// * An entry is not a valid image or has an unknown format tag.
+///   (`SfaError::DecodeEntry` which contains the name of the entry, or
+///   `SfaError::UnknownFormatTag`)
+/// * Two entries have the same name. (`SfaError::DuplicateName`)
+/// * The file is beyond the limits of `DecodeOptions::default()`.
+///   (`SfaError::TooManyEntries`, `SfaError::EntryTooLarge` or
+///   `SfaError::TotalSizeTooLarge`)
+///
+/// # Examples
+///
+/// ```
+/// # use std::collections::HashMap;
+/// use std::fs;
+/// use std::io::BufReader;
+/// use image::DynamicImage;
+/// use sfa::{decode_streaming, encode_images};
+///
+/// # let mut frames = HashMap::new();
+/// # frames.insert(String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4));
+/// let path = std::env::temp_dir().join("sfa_decode_streaming.sfa");
+/// encode_images(&frames, &path).unwrap();
+///
+/// let mut reader = BufReader::new(fs::File::open(&path).unwrap());
+/// let decoded = decode_streaming(&mut reader).unwrap();
+/// assert!(decoded.contains_key("frame_1.png"));
+/// ```
+pub fn decode_streaming<R: Read>(
+    reader: &mut R,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    collect_unique(SfaReader::new(reader)?.frames())
+}
+
+/// Decodes sfa file from a reader object that implements the trait
+/// `std::io::BufRead` like `sfa::decode_from_reader`. The headers of the
+/// entries are read with `BufRead::read_until` instead of one byte at a
+/// time, which is faster for buffered readers like `std::io::BufReader`.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::BufRead`.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::decode_from_reader`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, BufRead, BufReader, Read};
+/// use image::DynamicImage;
+/// use sfa::{decode_from_bufread, decode_from_reader, encode_images_to_vec};
+///
+/// /// Counts the calls to `read`, which every byte read one at a time costs
+/// struct Counting<R> {
+///     inner: R,
+///     reads: usize,
+/// }
+///
+/// impl<R: BufRead> Read for Counting<R> {
+///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+///         self.reads += 1;
+///         self.inner.read(buf)
+///     }
+/// }
+///
+/// impl<R: BufRead> BufRead for Counting<R> {
+///     fn fill_buf(&mut self) -> io::Result<&[u8]> {
+///         self.inner.fill_buf()
+///     }
+///
+///     fn consume(&mut self, amount: usize) {
+///         self.inner.consume(amount)
+///     }
+/// }
+///
+/// let archive = encode_images_to_vec(
+///     (1..=20).map(|i| (format!("sfa_walk_cycle_{}.png", i), DynamicImage::new_rgba8(4, 4))),
+/// )
+/// .unwrap();
+///
+/// let mut byte_at_a_time = Counting { inner: BufReader::new(archive.as_slice()), reads: 0 };
+/// let mut buffered = Counting { inner: BufReader::new(archive.as_slice()), reads: 0 };
+/// let expected = decode_from_reader(&mut byte_at_a_time).unwrap();
+/// let decoded = decode_from_bufread(&mut buffered).unwrap();
+///
+/// assert_eq!(decoded.len(), expected.len());
+/// for (name, frame) in &expected {
+///     assert_eq!(decoded[name].to_rgba8(), frame.to_rgba8());
+/// }
+/// assert!(buffered.reads * 4 < byte_at_a_time.reads);
+/// ```
+pub fn decode_from_bufread<R: BufRead>(
+    reader: &mut R,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    collect_unique(SfaReader::buffered(reader)?.frames())
+}
+
+/// Decodes a sfa file from disk using all the cores of the machine.
+/// Only available with the `rayon` feature (also enabled by the
+/// `parallel` feature).
+///
+/// Please refer to `sfa::decode_from_reader_parallel` for more insight
+/// because this is a wrapper over that function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::decode_parallel;
+///
+/// let my_sprite = decode_parallel("sp.sfa").unwrap();
+/// ```
+#[cfg(all(feature = "fs", feature = "rayon"))]
+pub fn decode_parallel<P: AsRef<Path>>(
+    file: P,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    decode_from_reader_parallel(&mut file)
+}
+
+/// Decodes sfa file from a reader object like `sfa::decode_from_reader`
+/// using all the cores of the machine. The whole file is read into
+/// memory first, then the entries that store a whole image are decoded
+/// in parallel. Aliases and delta entries are resolved after that in the
+/// order of the file. Only available with the `rayon` feature (also
+/// enabled by the `parallel` feature).
+///
+/// The result is the same as that of `sfa::decode_from_reader`. If more
+/// than one entry fails to decode, the error of the first one in the file
+/// is returned.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::decode_from_reader`.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, Rgba, RgbaImage};
+/// use sfa::{decode_from_reader, decode_from_reader_parallel, encode_images_to_vec, SfaError};
+///
+/// let frames = (0..16u8).map(|i| {
+///     let frame = RgbaImage::from_fn(8, 8, |x, y| Rgba([x as u8 * i, y as u8, i, 255]));
+///     (format!("spin_{}.png", i), DynamicImage::ImageRgba8(frame))
+/// });
+/// let archive = encode_images_to_vec(frames).unwrap();
+///
+/// let serial = decode_from_reader(&mut archive.as_slice()).unwrap();
+/// let parallel = decode_from_reader_parallel(&mut archive.as_slice()).unwrap();
+/// assert_eq!(parallel.len(), serial.len());
+/// for (name, frame) in &serial {
+///     assert_eq!(parallel[name].to_rgba8(), frame.to_rgba8());
+/// }
+///
+/// let broken = b"SFA;broken_1.png:3:abcbroken_2.png:3:xyz";
+/// let result = decode_from_reader_parallel(&mut broken.as_ref());
+/// assert!(matches!(result, Err(SfaError::DecodeEntry { name, .. }) if name == "broken_1.png"));
+/// ```
+#[cfg(feature = "rayon")]
+pub fn decode_from_reader_parallel<F: Read>(
+    reader: &mut F,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+    use rayon::prelude::*;
+
+    let mut buffer: Vec<u8> = vec![];
+    reader.read_to_end(&mut buffer)?;
+    let entries = split_entries(&buffer, &DecodeOptions::default())?;
+
+    // Collecting an indexed parallel iterator keeps the order of the file
+    let decoded: Vec<Option<Result<image::DynamicImage, SfaError>>> = entries
+        .par_iter()
+        .map(|(header, offset, data)| match header.format {
+            EntryFormat::Alias | EntryFormat::Delta => None,
+            format => Some(load_entry(&header.name, *offset, format, data)),
+        })
+        .collect();
+
+    let mut results: Vec<(String, image::DynamicImage)> = vec![];
+    // Positions of the entries in `results` that aliases can refer to
+    let mut targets: AliasTargets<usize> = AliasTargets::new();
+    for (i, ((header, offset, data), im)) in entries.iter().zip(decoded).enumerate() {
+        let im = match (header.format, im) {
+            (_, Some(im)) => {
+                targets.add(header, results.len());
+                im
+            }
+            (EntryFormat::Alias, None) => targets
+                .resolve(header, data)
+                .map(|(_, &i)| results[i].1.clone()),
+            (_, None) => match results.last() {
+                Some((_, previous)) => delta::apply(&header.name, *offset, previous, data),
+                None => Err(SfaError::InvalidDelta(header.name.clone())),
+            },
+        };
+
+        let following = entries[i + 1..].iter().map(|(header, _, _)| header);
+        let im = im.map_err(|e| with_dependents(&header.name, following, e))?;
+        results.push((header.name.clone(), im));
+    }
+
+    collect_unique(results.into_iter().map(Ok))
+}
+
+/// Decodes only the entries of a sfa file that `action` returns
+/// `EntryAction::Decode` for, and returns them in the order of the file.
+/// `action` is called with the name of each entry before its data is
+/// read. The data of skipped entries is read and discarded without being
+/// decoded, unless a delta entry that is decoded is based on them.
+///
+/// The headers of the entries are read one byte at a time, so it is
+/// recommended to wrap unbuffered readers like `std::fs::File` in a
+/// `std::io::BufReader`.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+/// * `action` - Called with the name of each entry, returns whether to
+///   decode or skip it.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::SfaReader::next_frame`,
+/// and are only returned for skipped entries if the file does not comply
+/// with the sfa format.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, ImageOutputFormat};
+/// use sfa::{decode_with, EntryAction};
+///
+/// let mut png = vec![];
+/// DynamicImage::new_rgba8(2, 2)
+///     .write_to(&mut png, ImageOutputFormat::Png)
+///     .unwrap();
+///
+/// // The skipped entries are not valid images
+/// let mut archive = b"SFA;hidden_1.png:3:abc".to_vec();
+/// archive.extend_from_slice(format!("visible.png:{}:", png.len()).as_bytes());
+/// archive.extend_from_slice(&png);
+/// archive.extend_from_slice(b"hidden_2.png:3:xyz");
+///
+/// let mut seen = vec![];
+/// let decoded = decode_with(&mut archive.as_slice(), |name| {
+///     seen.push(name.to_owned());
+///     match name.starts_with("visible") {
+///         true => EntryAction::Decode,
+///         false => EntryAction::Skip,
+///     }
+/// })
+/// .unwrap();
+///
+/// assert_eq!(seen, ["hidden_1.png", "visible.png", "hidden_2.png"]);
+/// assert_eq!(decoded.len(), 1);
+/// assert_eq!(decoded[0].0, "visible.png");
+/// ```
+///
+/// Delta entries are decoded from the skipped entries before them
+///
+/// ```
+/// use image::{DynamicImage, Rgba, RgbaImage};
+/// use sfa::{decode_with, EncodeOptions, EntryAction, SfaBuilder};
+///
+/// let frames: Vec<DynamicImage> = (0..4)
+///     .map(|i| RgbaImage::from_fn(8, 8, |x, _| Rgba([(x == i) as u8 * 255, 0, 0, 255])))
+///     .map(DynamicImage::ImageRgba8)
+///     .collect();
+/// let options = EncodeOptions {
+///     delta_frames: true,
+///     ..Default::default()
+/// };
+/// let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+/// for (i, frame) in frames.iter().enumerate() {
+///     builder.add_image(&format!("blink_{}.png", i), frame).unwrap();
+/// }
+/// let archive = builder.finish().unwrap();
+///
+/// let decoded = decode_with(&mut archive.as_slice(), |name| match name {
+///     "blink_3.png" => EntryAction::Decode,
+///     _ => EntryAction::Skip,
+/// })
+/// .unwrap();
+/// assert_eq!(decoded[0].1.to_rgba8(), frames[3].to_rgba8());
+/// ```
+pub fn decode_with<R, F>(
+    reader: &mut R,
+    mut action: F,
+) -> Result<Vec<(String, image::DynamicImage)>, SfaError>
+where
+    R: Read,
+    F: FnMut(&str) -> EntryAction,
+{
+    let mut reader = SfaReader::new(reader)?;
+
+    let mut results: Vec<(String, image::DynamicImage)> = vec![];
+    while let Some((name, im)) = reader.next_entry(&mut action)? {
+        if let Some(im) = im {
+            results.push((name, im));
+        }
+    }
+
+    Ok(results)
+}
+
+/// Decodes only the entries of a sfa file whose names pass the
+/// predicate, and returns them in a `HashMap` like `sfa::decode_from_reader`.
+/// The other entries are skipped without being decoded, which is much
+/// faster than filtering the result of `sfa::decode_from_reader`.
+///
+/// Please refer to `sfa::decode_with` for more insight because this is
+/// a wrapper over that function.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::decode_with` with the
+/// addition of the following.
+///
+/// * Two entries that pass the predicate have the same name.
+///   (`SfaError::DuplicateName`)
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_filtered, encode_images_to_vec};
+///
+/// let names = ["player_idle.png", "enemy_idle.png", "enemy_walk.png"];
+/// let archive =
+///     encode_images_to_vec(names.iter().map(|name| (name, DynamicImage::new_rgba8(4, 4))))
+///         .unwrap();
+///
+/// let decoded =
+///     decode_filtered(&mut archive.as_slice(), |name| name.starts_with("player_")).unwrap();
+/// assert_eq!(decoded.len(), 1);
+/// assert!(decoded.contains_key("player_idle.png"));
+/// ```
+pub fn decode_filtered<R, P>(
+    reader: &mut R,
+    mut predicate: P,
+) -> Result<HashMap<String, image::DynamicImage>, SfaError>
+where
+    R: Read,
+    P: FnMut(&str) -> bool,
+{
+    let entries = decode_with(reader, |name| match predicate(name) {
+        true => EntryAction::Decode,
+        false => EntryAction::Skip,
+    })?;
+
+    collect_unique(entries.into_iter().map(Ok))
+}
+
+/// Reads a sfa file from disk without decoding any of its images.
+///
+/// Please refer to `sfa::decode_lazy_from_reader` for more insight
+/// because this is a wrapper over that function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::decode_lazy;
+///
+/// let my_sprite = decode_lazy("sp.sfa").unwrap();
+/// let frame = my_sprite["frame_1.png"].image().unwrap();
+/// ```
+#[cfg(feature = "fs")]
+pub fn decode_lazy<P: AsRef<Path>>(file: P) -> Result<HashMap<String, LazyImage>, SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    decode_lazy_from_reader(&mut file)
+}
+
+/// Reads a sfa file from a reader object that implements the trait
+/// `std::io::Read` without decoding any of its images. Each entry is
+/// decoded the first time its image is needed, see `sfa::LazyImage`, so
+/// opening a large archive is fast and only the images that are used
+/// cost the time to decode them. The stored data of all the entries is
+/// kept in memory.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+/// * The format tag of an entry is not known. (`SfaError::UnknownFormatTag`)
+/// * An entry is an alias or delta entry that does not fit the entries
+///   before it. (`SfaError::InvalidAlias` or `SfaError::InvalidDelta`)
+/// * Two entries have the same name. (`SfaError::DuplicateName`)
+/// * The file is beyond the limits of `DecodeOptions::default()`.
+///   (`SfaError::TooManyEntries`, `SfaError::EntryTooLarge` or
+///   `SfaError::TotalSizeTooLarge`)
+///
+/// Entries that are not valid images are only found out about when they
+/// are decoded.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, ImageOutputFormat};
+/// use sfa::decode_lazy_from_reader;
+///
+/// let mut png = vec![];
+/// DynamicImage::new_rgba8(6, 4)
+///     .write_to(&mut png, ImageOutputFormat::Png)
+///     .unwrap();
+///
+/// let mut archive = format!("SFA;a.png:{}:", png.len()).into_bytes();
+/// archive.extend_from_slice(&png);
+/// archive.extend_from_slice(b"broken.png:3:abc");
+///
+/// let entries = decode_lazy_from_reader(&mut archive.as_slice()).unwrap();
+/// let frame = &entries["a.png"];
+/// assert_eq!(frame.dimensions().unwrap(), (6, 4));
+/// assert!(!frame.is_decoded());
+///
+/// assert_eq!(frame.image().unwrap().to_rgba8().dimensions(), (6, 4));
+/// assert!(frame.is_decoded());
+///
+/// // The broken entry only fails once it is decoded
+/// assert!(entries["broken.png"].image().is_err());
+/// ```
+pub fn decode_lazy_from_reader<R: Read>(
+    reader: &mut R,
+) -> Result<HashMap<String, LazyImage>, SfaError> {
+    // The offsets of the entries are kept for the errors
+    let mut reader = Positioned::new(reader);
+    let layout = read_magic(&mut reader)?;
+
+    let options = DecodeOptions::default();
+    let mut total: u64 = 0;
+    let mut entries: Vec<(String, LazyImage)> = vec![];
+    // Entries that aliases can refer to
+    let mut targets: AliasTargets<LazyImage> = AliasTargets::new();
+    let mut headers = Headers::new(layout);
+    while let Some(header) = headers.next(&mut reader)? {
+        total = total.saturating_add(header.size as u64);
+        options.check(headers.count, total, &header.name, header.size)?;
+        let offset = reader.position();
+        let mut data: Vec<u8> = vec![];
+        read_payload(&mut reader, &header, &mut data)?;
+        let lazy = match header.format {
+            EntryFormat::Alias => targets.resolve(&header, &data)?.1.clone(),
+            EntryFormat::Delta => match entries.last() {
+                Some((_, previous)) => {
+                    LazyImage::delta(header.name.clone(), previous.clone(), offset, data)
+                }
+                None => return Err(SfaError::InvalidDelta(header.name)),
+            },
+            format => {
+                let lazy = LazyImage::entry(header.name.clone(), format, offset, data);
+                targets.add(&header, lazy.clone());
+                lazy
+            }
+        };
+        entries.push((header.name, lazy));
+    }
+
+    collect_unique(entries.into_iter().map(Ok))
+}
+
+/// Returns an iterator over the entries of a sfa file that decodes
+/// them one at a time as `next` is called, for processing each image
+/// and dropping it before the next one is decoded. Unlike the functions
+/// that decode all the entries at once, the entries before a malformed
+/// one are still returned. The iterator ends after the first error.
+///
+/// The headers of the entries are read one byte at a time, so it is
+/// recommended to wrap unbuffered readers like `std::fs::File` in a
+/// `std::io::BufReader`.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// * Reading from the reader was unsuccessful. (`SfaError::Io`)
+/// * The file does not start with the magic text. (`SfaError::MissingMagic`)
+///
+/// The errors of the entries are returned by the iterator, see
+/// `sfa::SfaReader::next_frame`.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, ImageOutputFormat};
+/// use sfa::{entries_from_reader, SfaError};
+///
+/// let mut png = vec![];
+/// DynamicImage::new_rgba8(2, 2)
+///     .write_to(&mut png, ImageOutputFormat::Png)
+///     .unwrap();
+///
+/// let mut archive = format!("SFA;a.png:{}:", png.len()).into_bytes();
+/// archive.extend_from_slice(&png);
+/// archive.extend_from_slice(b"b.png:3:abc");
+/// archive.extend_from_slice(format!("c.png:{}:", png.len()).as_bytes());
+/// archive.extend_from_slice(&png);
+///
+/// let mut entries = entries_from_reader(archive.as_slice()).unwrap();
+/// let (name, frame) = entries.next().unwrap().unwrap();
+/// assert_eq!(name, "a.png");
+/// assert_eq!(frame.to_rgba8().dimensions(), (2, 2));
+///
+/// let result = entries.next().unwrap();
+/// assert!(matches!(result, Err(SfaError::DecodeEntry { name, .. }) if name == "b.png"));
+/// assert!(entries.next().is_none());
+/// ```
+pub fn entries_from_reader<R: Read>(reader: R) -> Result<SfaEntries<R>, SfaError> {
+    Ok(SfaEntries::new(SfaReader::new(reader)?))
+}
+
+/// Lists the names and sizes of the entries of a sfa file without
+/// decoding any images. The size is the number of bytes of the stored
+/// PNG data. The data itself is skipped, so this is much faster than
+/// decoding the file, which is useful for showing the contents of
+/// an archive.
+///
+/// The headers of the entries are read one byte at a time, so it is
+/// recommended to wrap unbuffered readers like `std::fs::File` in a
+/// `std::io::BufReader`.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{encode_images_to_writer, list_entries};
+///
+/// let frames = vec![
+///     (String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("frame_2.png"), DynamicImage::new_rgba8(64, 64)),
+/// ];
+/// let mut archive = vec![];
+/// encode_images_to_writer(frames, &mut archive).unwrap();
+///
+/// let entries = list_entries(&mut archive.as_slice()).unwrap();
+/// assert_eq!(entries[0].0, "frame_1.png");
+/// assert_eq!(entries[1].0, "frame_2.png");
+/// assert!(entries.iter().all(|(_, size)| *size > 0));
+/// ```
+pub fn list_entries<R: Read>(reader: &mut R) -> Result<Vec<(String, usize)>, SfaError> {
+    let layout = read_magic(reader)?;
+
+    let mut entries: Vec<(String, usize)> = vec![];
+    let mut headers = Headers::new(layout);
+    while let Some(header) = headers.next(reader)? {
+        skip_payload(reader, header.size)?;
+        entries.push((header.name, header.size));
+    }
+
+    Ok(entries)
+}
+
+/// Lists the names of the entries of a sfa file on disk in the order
+/// they appear in the file, without decoding any images.
+///
+/// Please refer to `sfa::list_names_from_reader` for more insight
+/// because this is a wrapper over that function.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sfa::list_names;
+///
+/// for name in list_names("sp.sfa").unwrap() {
+///     println!("{}", name);
+/// }
+/// ```
+#[cfg(feature = "fs")]
+pub fn list_names<P: AsRef<Path>>(file: P) -> Result<Vec<String>, SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    list_names_from_reader(&mut file)
+}
+
+/// Lists the names of the entries of a sfa file in the order they
+/// appear in the file. This is the same as `sfa::list_entries` without
+/// the sizes, so the data of the entries is skipped as well.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the trait `io::Read`.
+///
+/// # Errors
+///
+/// The errors are the same as those of `sfa::list_entries`, so files
+/// that are cut short or have malformed sizes are still reported.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{encode_images_to_vec, list_names_from_reader, SfaError};
+///
+/// let frames = vec![
+///     (String::from("idle.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("walk.png"), DynamicImage::new_rgba8(4, 4)),
+/// ];
+/// let archive = encode_images_to_vec(frames).unwrap();
+///
+/// let names = list_names_from_reader(&mut archive.as_slice()).unwrap();
+/// assert_eq!(names, ["idle.png", "walk.png"]);
+///
+/// // The data of the last entry is cut short
+/// let truncated = &archive[..archive.len() - 1];
+/// let result = list_names_from_reader(&mut truncated.as_ref());
+/// assert!(matches!(result, Err(SfaError::InvalidSize(_))));
+/// ```
+pub fn list_names_from_reader<R: Read>(reader: &mut R) -> Result<Vec<String>, SfaError> {
+    let entries = list_entries(reader)?;
+
+    Ok(entries.into_iter().map(|(name, _)| name).collect())
+}
+
+/// Decodes a single entry of a sfa file on disk without decoding the
+/// other entries. `Ok(None)` is returned if there is no entry with the
+/// given name, so that a missing entry can be told apart from a broken
+/// file.
+///
+/// Please refer to `sfa::extract_one` for more insight because this
+/// is a wrapper over that function, which works with any reader that
+/// implements `std::io::Seek` as well.
+///
+/// # Examples
+///
+/// Only the requested entry is decoded, so the broken entries around
+/// it do not matter
+///
+/// ```
+/// use image::{DynamicImage, ImageOutputFormat};
+/// use sfa::decode_entry;
+///
+/// let mut png = vec![];
+/// DynamicImage::new_rgba8(4, 4)
+///     .write_to(&mut png, ImageOutputFormat::Png)
+///     .unwrap();
+/// let mut archive = b"SFA;a.png:3:abc".to_vec();
+/// archive.extend_from_slice(format!("b.png:{}:", png.len()).as_bytes());
+/// archive.extend_from_slice(&png);
+/// archive.extend_from_slice(b"c.png:3:abc");
+///
+/// let path = std::env::temp_dir().join("sfa_decode_entry.sfa");
+/// std::fs::write(&path, &archive).unwrap();
+///
+/// let frame = decode_entry(&path, "b.png").unwrap().unwrap();
+/// assert_eq!(frame.to_rgba8().dimensions(), (4, 4));
+/// assert!(decode_entry(&path, "d.png").unwrap().is_none());
+/// assert!(decode_entry(&path, "a.png").is_err());
+/// ```
+#[cfg(feature = "fs")]
+pub fn decode_entry<P: AsRef<Path>>(
+    file: P,
+    name: &str,
+) -> Result<Option<image::DynamicImage>, SfaError> {
+    let file = fs::File::open(file)?;
+    let mut file = io::BufReader::new(file);
+
+    extract_one(&mut file, name)
+}
+
+/// Extracts a single entry from a sfa file without decoding the
+/// other entries. The headers are scanned until the entry with the
+/// given name is found and the data of the entries before it is
+/// skipped by seeking. If there are multiple entries with the same
+/// name, the first one is returned. Use `sfa::SfaIndex` to extract
+/// many entries without scanning the headers again every time.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the traits `io::Read` and `io::Seek`.
+/// * `name` - The name of the entry to extract.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+/// * The entry is not a valid image or has an unknown format tag.
+///   (`SfaError::DecodeEntry` or `SfaError::UnknownFormatTag`)
+///
+/// If there is no entry with the given name `Ok(None)` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use image::DynamicImage;
+/// use sfa::{encode_images_to_writer, extract_one};
+///
+/// let frames = vec![
+///     (String::from("idle_01.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("idle_02.png"), DynamicImage::new_rgba8(8, 8)),
+/// ];
+/// let mut archive = vec![];
+/// encode_images_to_writer(frames, &mut archive).unwrap();
+///
+/// let mut reader = Cursor::new(archive);
+/// let frame = extract_one(&mut reader, "idle_02.png").unwrap().unwrap();
+/// assert_eq!(frame.to_rgba8().dimensions(), (8, 8));
+///
+/// reader.set_position(0);
+/// assert!(extract_one(&mut reader, "idle_03.png").unwrap().is_none());
+/// ```
+pub fn extract_one<R: Read + Seek>(
+    reader: &mut R,
+    name: &str,
+) -> Result<Option<image::DynamicImage>, SfaError> {
+    match entries_until(reader, name)? {
+        Some(entries) => extract_last(reader, &entries).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Finds the width and height of a single entry of a sfa file without
+/// decoding it. The headers are scanned like `sfa::extract_one` does,
+/// but only the start of the data of the entry is read for PNG and
+/// trimmed entries, which is where their dimensions are stored. Entries
+/// in other formats are decoded to find them out. Delta entries have
+/// the dimensions of the entry they are based on and aliases those of
+/// the entry they refer to.
+///
+/// The checksum of the entry is not verified, since most of its data
+/// is never read.
+///
+/// # Arguments
+///
+/// * `reader` - An object that implements the traits `io::Read` and `io::Seek`.
+/// * `name` - The name of the entry.
+///
+/// # Errors
+///
+/// The errors are of type `sfa::SfaError` and can be returned in
+/// either one of these situations.
+///
+/// * Reading from the reader was unsuccessful (Maybe due to timeout).
+///   (`SfaError::Io`)
+/// * The file does not comply with the sfa format.
+///   (`SfaError::MissingMagic`, `SfaError::UnexpectedEof`,
+///   `SfaError::TruncatedHeader`, `SfaError::TrailingData`,
+///   `SfaError::InvalidSize` or `SfaError::InvalidName`)
+/// * The entry has an unknown format tag or is an alias or delta entry
+///   that does not fit the entries before it. (`SfaError::UnknownFormatTag`,
+///   `SfaError::InvalidAlias` or `SfaError::InvalidDelta`)
+/// * The entry had to be decoded and is not a valid image.
+///   (`SfaError::DecodeEntry`)
+///
+/// If there is no entry with the given name `Ok(None)` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use image::DynamicImage;
+/// use sfa::{encode_images_to_writer, entry_dimensions};
+///
+/// let frames = vec![
+///     (String::from("idle_01.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("idle_02.png"), DynamicImage::new_rgba8(16, 8)),
+/// ];
+/// let mut archive = vec![];
+/// encode_images_to_writer(frames, &mut archive).unwrap();
+///
+/// let mut reader = Cursor::new(archive);
+/// assert_eq!(entry_dimensions(&mut reader, "idle_02.png").unwrap(), Some((16, 8)));
+///
+/// reader.set_position(0);
+/// assert_eq!(entry_dimensions(&mut reader, "idle_03.png").unwrap(), None);
+/// ```
+pub fn entry_dimensions<R: Read + Seek>(
+    reader: &mut R,
+    name: &str,
+) -> Result<Option<(u32, u32)>, SfaError> {
+    match entries_until(reader, name)? {
+        Some(entries) => dimensions_last(reader, &entries).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Scan the headers until the first entry with the given name and return
+/// the headers and positions of the data of that entry and all the entries
+/// before it, since aliases and delta entries need earlier entries. `None`
+/// is returned if there is no entry with the name.
+fn entries_until<R: Read + Seek>(
+    reader: &mut R,
+    name: &str,
+) -> Result<Option<Vec<(EntryHeader, u64)>>, SfaError> {
+    let layout = read_magic(reader)?;
+
+    let mut entries: Vec<(EntryHeader, u64)> = vec![];
+    let mut headers = Headers::new(layout);
+    while let Some(header) = headers.next(reader)? {
+        let position = reader.stream_position()?;
+        let found = header.name == name;
+        let size = header.size;
+        entries.push((header, position));
+        if found {
+            return Ok(Some(entries));
+        }
+
+        seek_payload(reader, size)?;
+    }
+
+    Ok(None)
+}
+
+/// The entry that the alias with the given header and data refers to,
+/// out of the entries before it.
+fn alias_target<'a>(
+    entries: &'a [(EntryHeader, u64)],
+    header: &EntryHeader,
+    data: &[u8],
+) -> Result<&'a (EntryHeader, u64), SfaError> {
+    let mut targets = AliasTargets::new();
+    for entry in entries {
+        targets.add(&entry.0, entry);
+    }
+
+    targets.resolve(header, data).map(|(_, &entry)| entry)
+}
+
+/// Decode the last of the entries found by `sfa::extract_one`. A delta
+/// entry is decoded by applying all the delta entries since the last
+/// entry stored in full to that entry.
+fn extract_last<R: Read + Seek>(
+    reader: &mut R,
+    entries: &[(EntryHeader, u64)],
+) -> Result<image::DynamicImage, SfaError> {
+    let base = match entries
+        .iter()
+        .rposition(|(header, _)| header.format != EntryFormat::Delta)
+    {
+        Some(base) => base,
+        None => return Err(SfaError::InvalidDelta(entries[0].0.name.clone())),
+    };
+
+    let mut buffer: Vec<u8> = vec![];
+    let (header, position) = &entries[base];
+    let following = entries[base + 1..].iter().map(|(header, _)| header);
+    let mut im = read_payload_at(reader, header, *position, &mut buffer)
+        .and_then(|()| match header.format {
+            EntryFormat::Alias => {
+                let (earlier, position) = alias_target(&entries[..base], header, &buffer)?;
+                read_payload_at(reader, earlier, *position, &mut buffer)?;
+                load_entry(&earlier.name, *position, earlier.format, &buffer)
+            }
+            format => load_entry(&header.name, *position, format, &buffer),
+        })
+        .map_err(|e| with_dependents(&header.name, following, e))?;
+
+    for (i, (header, position)) in entries.iter().enumerate().skip(base + 1) {
+        let following = entries[i + 1..].iter().map(|(header, _)| header);
+        im = read_payload_at(reader, header, *position, &mut buffer)
+            .and_then(|()| delta::apply(&header.name, *position, &im, &buffer))
+            .map_err(|e| with_dependents(&header.name, following, e))?;
+    }
+
+    Ok(im)
+}
+
+/// The width and height of the last of the entries found by
+/// `sfa::entry_dimensions`. Only the start of the data of PNG and
+/// trimmed entries is read, other formats are decoded.
+fn dimensions_last<R: Read + Seek>(
+    reader: &mut R,
+    entries: &[(EntryHeader, u64)],
+) -> Result<(u32, u32), SfaError> {
+    // Delta entries have the dimensions of the entry they are based on
+    let base = match entries
+        .iter()
+        .rposition(|(header, _)| header.format != EntryFormat::Delta)
+    {
+        Some(base) => base,
+        None => return Err(SfaError::InvalidDelta(entries[0].0.name.clone())),
+    };
+
+    let mut buffer: Vec<u8> = vec![];
+    let (mut header, mut position) = (&entries[base].0, entries[base].1);
+    if header.format == EntryFormat::Alias {
+        read_payload_at(reader, header, position, &mut buffer)?;
+        let (earlier, earlier_position) = alias_target(&entries[..base], header, &buffer)?;
+        header = earlier;
+        position = *earlier_position;
+    }
+
+    // The signature and IHDR chunk of PNG data and the dimensions, offset
+    // and format tag before the data of trimmed images
+    let length = match header.format {
+        EntryFormat::Png => 24,
+        EntryFormat::Trimmed => 17,
+        _ => 0,
+    };
+    if length > 0 {
+        reader.seek(SeekFrom::Start(position))?;
+        buffer.clear();
+        reader
+            .by_ref()
+            .take(length.min(header.size) as u64)
+            .read_to_end(&mut buffer)?;
+        match header.format {
+            EntryFormat::Png => {
+                if let Some(dimensions) = lazy::png_dimensions(&buffer) {
+                    return Ok(dimensions);
+                }
+            }
+            _ => {
+                let (trim, _, _) = metadata::split_trimmed(&buffer)?;
+                return Ok((trim.width, trim.height));
+            }
+        }
+    }
+
+    Ok(extract_last(reader, &entries[..=base])?.dimensions())
+}
+
+/// Read and validate the magic text identifier and return the layout
+/// of the file. The metadata block of the file is skipped.
+fn read_magic<R: Read>(reader: &mut R) -> Result<Layout, SfaError> {
+    let layout = Layout::read(reader)?;
+    metadata::read_archive_metadata(reader, layout)?;
+
+    Ok(layout)
+}
+
+/// Read a single byte from the reader, `None` is returned at EOF.
+fn read_byte<R: Read>(reader: &mut R) -> Result<Option<u8>, SfaError> {
+    let mut byte = [0u8; 1];
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Header of a single entry.
+struct EntryHeader {
+    name: String,
+    /// Size of the data, not including the format tag and checksum.
+    size: usize,
+    format: EntryFormat,
+    /// CRC32 of the data in files with checksums.
+    checksum: Option<u32>,
+}
+
+/// Reads the headers of the entries one after the other. In files with
+/// a count of entries, the entries are counted and checked against the
+/// count at the end of the file, which has to be there. The table of
+/// contents is skipped.
+struct Headers<R: Read> {
+    layout: Layout,
+    count: usize,
+    read_field: ReadField<R>,
+    /// Bytes after the last entry of files without the count that are
+    /// not a header fail with `SfaError::TrailingData`.
+    strict: bool,
+}
+
+impl<R: Read> Headers<R> {
+    fn new(layout: Layout) -> Headers<R> {
+        Headers {
+            layout,
+            count: 0,
+            read_field: read_field::<R>,
+            strict: false,
+        }
+    }
+
+    /// Read the headers like `DecodeOptions::strict` says.
+    fn strict(mut self, strict: bool) -> Headers<R> {
+        self.strict = strict;
+        self
+    }
+
+    /// Read the headers with `BufRead::read_until` instead of one byte
+    /// at a time.
+    fn buffered(layout: Layout) -> Headers<R>
+    where
+        R: BufRead,
+    {
+        Headers {
+            layout,
+            count: 0,
+            read_field: read_field_buffered::<R>,
+            strict: false,
+        }
+    }
+
+    /// Read the header of the next entry. `None` is returned once all
+    /// the entries have been read.
+    fn next(&mut self, reader: &mut R) -> Result<Option<EntryHeader>, SfaError> {
+        let header = loop {
+            let header = match self.strict && !self.layout.count() {
+                true => self.next_strict(reader)?,
+                false => read_header_with(reader, self.layout, self.read_field)?,
+            };
+            match header {
+                Some(header) if header.format == EntryFormat::Toc => {
+                    skip_payload(reader, header.size)?
+                }
+                Some(header) => break header,
+                // The file was cut short if the count is missing
+                None if self.layout.count() => return Err(SfaError::UnexpectedEof),
+                None => return Ok(None),
+            }
+        };
+        if header.format != EntryFormat::End {
+            self.count += 1;
+            return Ok(Some(header));
+        }
+
+        let mut data: Vec<u8> = vec![];
+        read_payload(reader, &header, &mut data)?;
+        // Followed by the position of the table of contents if there is one
+        let size = if self.layout.table_of_contents() {
+            12
+        } else {
+            4
+        };
+        if data.len() != size {
+            return Err(SfaError::InvalidSize(header.size.to_string()));
+        }
+        let expected = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        if expected != self.count {
+            return Err(SfaError::EntryCountMismatch {
+                expected,
+                found: self.count,
+            });
+        }
+
+        // Nothing may follow the count, like the data of another file
+        // that was concatenated with this one
+        check_exhausted(reader, vec![])?;
+
+        Ok(None)
+    }
+
+    /// Read the header of the next entry of a file without the count of
+    /// entries, where bytes that are not a header can only be left over
+    /// after the last entry.
+    fn next_strict(&self, reader: &mut R) -> Result<Option<EntryHeader>, SfaError> {
+        let mut recording = Recording {
+            inner: &mut *reader,
+            bytes: vec![],
+        };
+        match read_header(&mut recording, self.layout) {
+            Err(SfaError::Io(e)) => Err(SfaError::Io(e)),
+            // Always fails, since a header was started
+            Err(_) => {
+                let read = recording.bytes;
+                check_exhausted(reader, read)?;
+                Ok(None)
+            }
+            result => result,
+        }
+    }
+}
+
+/// A reader that keeps a copy of the bytes read from it.
+struct Recording<'a, R> {
+    inner: &'a mut R,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read> Read for Recording<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Return `SfaError::TrailingData` if anything is left in the reader,
+/// counting the bytes that were already read from it after the end.
+fn check_exhausted<R: Read>(reader: &mut R, mut preview: Vec<u8>) -> Result<(), SfaError> {
+    let mut count = preview.len();
+    if count < TRAILING_PREVIEW {
+        count += reader
+            .by_ref()
+            .take((TRAILING_PREVIEW - count) as u64)
+            .read_to_end(&mut preview)?;
+    }
+    count += io::copy(reader, &mut io::sink())? as usize;
+    preview.truncate(TRAILING_PREVIEW);
+
+    match count {
+        0 => Ok(()),
+        _ => Err(SfaError::TrailingData { count, preview }),
+    }
+}
+
+/// Write the number of entries at the end of files whose layout has
+/// it, as an entry without a name whose data is the count as a little
+/// endian `u32`, followed by the position of the table of contents as a
+/// little endian `u64` if there is one. Nothing is written for other
+/// files.
+fn write_end<W: Write>(
+    writer: &mut W,
+    layout: Layout,
+    count: usize,
+    toc: Option<u64>,
+) -> Result<(), SfaError> {
+    if layout.count() {
+        let mut data = (count as u32).to_le_bytes().to_vec();
+        if let Some(position) = toc {
+            data.extend_from_slice(&position.to_le_bytes());
+        }
+        write_raw_entry(writer, layout, "", &data, EntryFormat::End)?;
+    }
+
+    Ok(())
+}
+
+/// Return `SfaError::EmptyInput` if there are no entries to write and
+/// the options do not allow empty archives.
+fn check_not_empty(count: usize, options: &EncodeOptions) -> Result<(), SfaError> {
+    match count {
+        0 if !options.allow_empty => Err(SfaError::EmptyInput),
+        _ => Ok(()),
+    }
+}
+
+/// Reads a field of a header up to the `:` that ends it into the
+/// vector, without the `:`. Returns whether the `:` was found before EOF.
+type ReadField<R> = fn(&mut R, &mut Vec<u8>) -> Result<bool, SfaError>;
+
+/// Read a field of a header one byte at a time.
+fn read_field<R: Read>(reader: &mut R, field: &mut Vec<u8>) -> Result<bool, SfaError> {
+    loop {
+        match read_byte(reader)? {
+            None => return Ok(false),
+            Some(b':') => return Ok(true),
+            Some(b) => field.push(b),
+        }
+    }
+}
+
+/// Read a field of a header with `BufRead::read_until`.
+fn read_field_buffered<R: BufRead>(reader: &mut R, field: &mut Vec<u8>) -> Result<bool, SfaError> {
+    let read = reader.read_until(b':', field)?;
+    if read == 0 || field.last() != Some(&b':') {
+        return Ok(false);
+    }
+    field.pop();
+
+    Ok(true)
+}
+
+/// Read the `name:size:` header of the next entry along with the
+/// format tag in files of version 1 and later and the checksum in
+/// files with checksums. `None` is returned if the reader is at EOF
+/// before the header starts.
+fn read_header<R: Read>(reader: &mut R, layout: Layout) -> Result<Option<EntryHeader>, SfaError> {
+    read_header_with(reader, layout, read_field::<R>)
+}
+
+/// Read the header of the next entry like `read_header`, reading the
+/// `name:size:` fields with the given function.
+fn read_header_with<R: Read>(
+    reader: &mut R,
+    layout: Layout,
+    read_field: ReadField<R>,
+) -> Result<Option<EntryHeader>, SfaError> {
+    let mut name: Vec<u8> = vec![];
+    let found = read_field(reader, &mut name)?;
+    // Number of bytes of the header read so far, for files that end in it
+    let mut read = name.len();
+    if !found && read == 0 {
+        return Ok(None);
+    } else if !found {
+        return Err(SfaError::TruncatedHeader(read));
+    }
+    read += 1;
+
+    let mut size: Vec<u8> = vec![];
+    let found = match read_byte(reader)? {
+        // `length:=name:size:` is a name of `length` bytes that can contain `:`
+        Some(b'=') => {
+            name = read_prefixed_name(reader, &name, read + 1)?;
+            read += name.len() + 2;
+            read_field(reader, &mut size)?
+        }
+        Some(b':') => true,
+        Some(b) => {
+            size.push(b);
+            read_field(reader, &mut size)?
+        }
+        None => false,
+    };
+    read += size.len();
+    if !found {
+        return Err(SfaError::TruncatedHeader(read));
+    }
+    read += 1;
+
+    let name = parse_name(name)?;
+    let size = parse_size(&size)?;
+    let format = match layout.tags() {
+        false => EntryFormat::Png,
+        true => match read_byte(reader)? {
+            Some(tag) => match EntryFormat::from_tag(tag)? {
+                format if !layout.knows(format) => return Err(SfaError::UnknownFormatTag(tag)),
+                format => format,
+            },
+            None => return Err(SfaError::TruncatedHeader(read)),
+        },
+    };
+    let checksum = match layout.checksums() {
+        false => None,
+        true => {
+            let mut checksum: Vec<u8> = vec![];
+            reader.take(4).read_to_end(&mut checksum)?;
+            let checksum: [u8; 4] = checksum
+                .as_slice()
+                .try_into()
+                .map_err(|_| SfaError::TruncatedHeader(read + 1 + checksum.len()))?;
+            Some(u32::from_be_bytes(checksum))
+        }
+    };
+
+    Ok(Some(EntryHeader {
+        name,
+        size,
+        format,
+        checksum,
+    }))
+}
+
+/// Read the name that follows a `length:=` prefix along with the `:`
+/// after it. `read` is the number of bytes of the header before the
+/// name, for files that end in it.
+fn read_prefixed_name<R: Read>(
+    reader: &mut R,
+    length: &[u8],
+    read: usize,
+) -> Result<Vec<u8>, SfaError> {
+    let length = parse_size(length)?;
+    let mut name: Vec<u8> = vec![];
+    reader.take(length as u64).read_to_end(&mut name)?;
+    if name.len() < length {
+        return Err(SfaError::TruncatedHeader(read + name.len()));
+    }
+
+    match read_byte(reader)? {
+        Some(b':') => Ok(name),
+        // The size has to follow the name
+        Some(b) => Err(SfaError::InvalidSize(
+            String::from_utf8_lossy(&[b]).into_owned(),
+        )),
+        None => Err(SfaError::TruncatedHeader(read + name.len())),
+    }
+}
+
+/// The error for an alias whose target is not an earlier entry.
+fn invalid_alias(name: String, target: String) -> SfaError {
+    SfaError::InvalidAlias { name, target }
+}
+
+/// Decode the data of an entry, naming the entry and the offset of its
+/// data in the errors.
+fn load_entry(
+    name: &str,
+    offset: u64,
+    format: EntryFormat,
+    data: &[u8],
+) -> Result<image::DynamicImage, SfaError> {
+    format.load(data).map_err(|e| decode_error(name, offset, e))
+}
+
+/// The error for the data of an entry that could not be decoded.
+fn decode_error(name: &str, offset: u64, error: SfaError) -> SfaError {
+    SfaError::DecodeEntry {
+        name: name.to_owned(),
+        offset,
+        source: Box::new(error),
+    }
+}
+
+/// Wrap the error of an entry that could not be decoded with the names
+/// of the delta entries directly following it, which depend on it and
+/// can not be decoded either. Other errors are returned as is.
+fn with_dependents<'a, I>(name: &str, following: I, error: SfaError) -> SfaError
+where
+    I: IntoIterator<Item = &'a EntryHeader>,
+{
+    let dependents: Vec<String> = following
+        .into_iter()
+        .take_while(|header| header.format == EntryFormat::Delta)
+        .map(|header| header.name.clone())
+        .collect();
+    if dependents.is_empty() {
+        return error;
+    }
+
+    SfaError::BrokenDeltaBase {
+        name: name.to_owned(),
+        dependents,
+        source: Box::new(error),
+    }
+}
+
+/// Read the data of the entry into `buffer` and verify its checksum.
+/// The buffer grows with the data that is actually read instead of
+/// being allocated up front, so a corrupt size can not cause a huge
+/// allocation.
+fn read_payload<R: Read>(
+    reader: &mut R,
+    header: &EntryHeader,
+    buffer: &mut Vec<u8>,
+) -> Result<(), SfaError> {
+    buffer.clear();
+    reader.take(header.size as u64).read_to_end(buffer)?;
+    if buffer.len() != header.size {
+        return Err(SfaError::InvalidSize(header.size.to_string()));
+    }
+
+    verify_checksum(header, buffer)
+}
+
+/// Read the data of an entry that starts at the given position.
+fn read_payload_at<R: Read + Seek>(
+    reader: &mut R,
+    header: &EntryHeader,
+    position: u64,
+    buffer: &mut Vec<u8>,
+) -> Result<(), SfaError> {
+    reader.seek(SeekFrom::Start(position))?;
+    read_payload(reader, header, buffer)
+}
+
+/// Compare the data of an entry with its checksum, if it has one.
+fn verify_checksum(header: &EntryHeader, data: &[u8]) -> Result<(), SfaError> {
+    match header.checksum {
+        Some(checksum) if crc32fast::hash(data) != checksum => Err(SfaError::ChecksumMismatch {
+            name: header.name.clone(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Skip exactly `size` bytes of entry data without keeping them in memory.
+fn skip_payload<R: Read>(reader: &mut R, size: usize) -> Result<(), SfaError> {
+    let skipped = io::copy(&mut reader.take(size as u64), &mut io::sink())?;
+    if skipped != size as u64 {
+        return Err(SfaError::InvalidSize(size.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Skip exactly `size` bytes of entry data by seeking over them.
+fn seek_payload<R: Seek>(reader: &mut R, size: usize) -> Result<(), SfaError> {
+    let offset = i64::try_from(size).map_err(|_| SfaError::InvalidSize(size.to_string()))?;
+    let position = reader.seek(SeekFrom::Current(offset))?;
+    // Seeking past the end of a truncated file is not an error by itself
+    if position > reader.seek(SeekFrom::End(0))? {
+        return Err(SfaError::InvalidSize(size.to_string()));
+    }
+    reader.seek(SeekFrom::Start(position))?;
+
+    Ok(())
+}
+
+/// Convert the raw bytes of a name to a `String`. Names are collected
+/// as raw bytes because a single character can span multiple bytes in UTF-8.
+fn parse_name(name: Vec<u8>) -> Result<String, SfaError> {
+    String::from_utf8(name)
+        .map_err(|e| SfaError::InvalidName(String::from_utf8_lossy(e.as_bytes()).into_owned()))
+}
+
+/// Parse the size of an entry from its raw bytes. Only decimal digits
+/// are allowed, and sizes that do not fit into a `u64`, or into a
+/// `usize` on 32 bit targets, are invalid instead of wrapping around.
+fn parse_size(size: &[u8]) -> Result<usize, SfaError> {
+    std::str::from_utf8(size)
+        .ok()
+        .filter(|size| size.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|size| size.parse::<u64>().ok())
+        .and_then(|size| usize::try_from(size).ok())
+        .ok_or_else(|| SfaError::InvalidSize(String::from_utf8_lossy(size).into_owned()))
+}
+
+/// Error returned by all the functions of this crate.
+/// It can be matched upon to find out the kind of failure
+/// instead of relying on the error message.
+///
+/// Errors from the underlying reader/writer and from the
+/// `image` crate are wrapped in `Io` and `Image` respectively.
+/// All the other variants are SFA specific.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SfaError {
+    /// The magic text identifier `SFA;` was not found at the start of the file.
+    MissingMagic,
+    /// The file was written in a version of the file format that is not
+    /// supported, probably by a newer version of this crate. Contains the
+    /// version.
+    UnsupportedVersion(u8),
+    /// The flags after the magic text of a file of version 9 have bits
+    /// set that are not known, or a table of contents without the count
+    /// of entries. Contains the flags.
+    UnknownFlags(u8),
+    /// Reached EOF before the end of the file, which is after the count
+    /// of entries in files that have one, or in the middle of the
+    /// metadata block or the data of a trimmed entry.
+    UnexpectedEof,
+    /// Reached EOF in the middle of the header of an entry. Contains the
+    /// number of bytes of the header that were read. These are usually
+    /// left over from a file that was cut short or appended to another.
+    TruncatedHeader(usize),
+    /// There is data after the count of entries at the end of a file that
+    /// has one, or bytes after the last entry of a file without the count
+    /// that are not a header with `DecodeOptions::strict`.
+    TrailingData {
+        /// The number of bytes after the count.
+        count: usize,
+        /// Up to the first 16 of them.
+        preview: Vec<u8>,
+    },
+    /// The size of an entry is not a valid number or larger than the data
+    /// left in the file. Contains the size as found in the file.
+    InvalidSize(String),
+    /// The name of an entry is not valid UTF-8. Contains the name with the
+    /// invalid parts replaced by `�`.
+    InvalidName(String),
+    /// The same name was given to more than one entry. Contains the name.
+    DuplicateName(String),
+    /// Two entries of a file have the same name and
+    /// `DecodeOptions::duplicates` is `DuplicatePolicy::Error`.
+    DuplicateEntry {
+        /// Name of the entries.
+        name: String,
+        /// Position of the data of the first entry in bytes from the
+        /// start of the file, or from where the reader started.
+        first: u64,
+        /// Position of the data of the second entry.
+        second: u64,
+    },
+    /// The format tag of an entry is not known. Contains the tag. The file
+    /// was probably written by a newer version of this crate.
+    UnknownFormatTag(u8),
+    /// An entry is stored in a format that needs a feature of this crate
+    /// which is not enabled. Contains the name of the feature.
+    FeatureDisabled(&'static str),
+    /// An entry is an alias of an entry that does not come before it.
+    InvalidAlias {
+        /// Name of the alias.
+        name: String,
+        /// Name of the entry that the alias refers to.
+        target: String,
+    },
+    /// A delta entry has no entry before it or its changed region does
+    /// not fit into the image of that entry. Contains the name.
+    InvalidDelta(String),
+    /// The metadata block of the file is cut short or is not UTF-8.
+    InvalidMetadata,
+    /// The table of contents at the end of a file is not where the end
+    /// of the file says it is, or is cut short.
+    InvalidTableOfContents,
+    /// An entry could not be decoded, so neither can the delta entries
+    /// that directly follow it since they only store the changes to it.
+    BrokenDeltaBase {
+        /// Name of the entry that could not be decoded.
+        name: String,
+        /// Names of the delta entries that depend on it.
+        dependents: Vec<String>,
+        /// The error that occurred.
+        source: Box<SfaError>,
+    },
+    /// The data of an entry does not match the checksum stored with it,
+    /// so the file is corrupt.
+    ChecksumMismatch {
+        /// Name of the entry.
+        name: String,
+    },
+    /// There are no entries to write and `EncodeOptions::allow_empty`
+    /// is not set.
+    EmptyInput,
+    /// `sfa::split` was asked for parts of 0 entries.
+    EmptyChunk,
+    /// The number of entries of the file does not match the count at
+    /// its end.
+    EntryCountMismatch {
+        /// The count at the end of the file.
+        expected: usize,
+        /// The number of entries that were read.
+        found: usize,
+    },
+    /// Reading or decoding an input entry failed.
+    InputEntry {
+        /// Name of the entry that failed, or the path of the input file
+        /// for functions that read files.
+        name: String,
+        /// The error that occurred.
+        source: Box<SfaError>,
+    },
+    /// The file has more entries than `DecodeOptions::max_entries`.
+    TooManyEntries {
+        /// Name of the first entry over the limit.
+        name: String,
+        /// The limit.
+        limit: usize,
+    },
+    /// The data of an entry is larger than `DecodeOptions::max_entry_bytes`.
+    EntryTooLarge {
+        /// Name of the entry.
+        name: String,
+        /// Size of the data of the entry in bytes.
+        size: usize,
+        /// The limit.
+        limit: usize,
+    },
+    /// The data of the entries together is larger than
+    /// `DecodeOptions::max_total_bytes`.
+    TotalSizeTooLarge {
+        /// Name of the first entry that goes over the limit.
+        name: String,
+        /// Size of the data of the entries up to and including this
+        /// one in bytes.
+        total: u64,
+        /// The limit.
+        limit: u64,
+    },
+    /// Decoding the image of an entry failed.
+    DecodeEntry {
+        /// Name of the entry that failed.
+        name: String,
+        /// Position of the data of the entry in bytes from the start of
+        /// the file, or from where the reader started.
+        offset: u64,
+        /// The error that occurred.
+        source: Box<SfaError>,
+    },
+    /// Reading or writing failed due to some OS error.
+    Io(io::Error),
+    /// The `image` crate failed to read, decode or encode an image.
+    Image(image::ImageError),
+}
+
+impl std::fmt::Display for SfaError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            SfaError::MissingMagic => write!(
+                formatter,
+                "Magic Text Identifier not found after parsing 4 letters of the file"
+            ),
+            SfaError::UnsupportedVersion(version) => {
+                write!(formatter, "Unsupported file format version {}", version)
+            }
+            SfaError::UnknownFlags(flags) => {
+                write!(formatter, "Unknown file format flags {:#010b}", flags)
+            }
+            SfaError::UnexpectedEof => write!(
+                formatter,
+                "Reached EOF before all file content was retrieved"
+            ),
+            SfaError::TruncatedHeader(read) => write!(
+                formatter,
+                "Reached EOF after {} bytes of the header of an entry",
+                read
+            ),
+            SfaError::TrailingData { count, preview } => {
+                write!(formatter, "{} bytes of data follow the last entry:", count)?;
+                for byte in preview {
+                    write!(formatter, " {:02x}", byte)?;
+                }
+                if *count > preview.len() {
+                    write!(formatter, " ...")?;
+                }
+                Ok(())
+            }
+            SfaError::InvalidSize(size) => write!(formatter, "Invalid entry size {:?}", size),
+            SfaError::InvalidName(name) => {
+                write!(formatter, "Name of an entry is not valid UTF-8: {:?}", name)
+            }
+            SfaError::DuplicateName(name) => {
+                write!(formatter, "More than one entry is named {:?}", name)
+            }
+            SfaError::DuplicateEntry {
+                name,
+                first,
+                second,
+            } => write!(
+                formatter,
+                "The entries at offsets {} and {} are both named {:?}",
+                first, second, name
+            ),
+            SfaError::UnknownFormatTag(tag) => {
+                write!(formatter, "Unknown format tag {} of an entry", tag)
+            }
+            SfaError::FeatureDisabled(feature) => write!(
+                formatter,
+                "Decoding an entry needs the {:?} feature of sfa which is not enabled",
+                feature
+            ),
+            SfaError::InvalidAlias { name, target } => write!(
+                formatter,
+                "Entry {:?} is an alias of {:?} which is not an earlier entry",
+                name, target
+            ),
+            SfaError::InvalidDelta(name) => {
+                write!(
+                    formatter,
+                    "Delta entry {:?} does not fit the entry before it",
+                    name
+                )
+            }
+            SfaError::InvalidMetadata => write!(formatter, "Invalid metadata block"),
+            SfaError::InvalidTableOfContents => write!(formatter, "Invalid table of contents"),
+            SfaError::BrokenDeltaBase {
+                name,
+                dependents,
+                source,
+            } => write!(
+                formatter,
+                "Failed to decode entry {:?} which the delta entries {:?} depend on: {}",
+                name, dependents, source
+            ),
+            SfaError::ChecksumMismatch { name } => {
+                write!(
+                    formatter,
+                    "Data of entry {:?} does not match its checksum",
+                    name
+                )
+            }
+            SfaError::EmptyInput => write!(
+                formatter,
+                "No entries to write, set EncodeOptions::allow_empty to write empty archives"
+            ),
+            SfaError::EmptyChunk => {
+                write!(formatter, "The parts of a split need at least 1 entry each")
+            }
+            SfaError::EntryCountMismatch { expected, found } => write!(
+                formatter,
+                "The file should have {} entries but has {}",
+                expected, found
+            ),
+            SfaError::InputEntry { name, source } => {
+                write!(formatter, "Failed to read entry {:?}: {}", name, source)
+            }
+            SfaError::TooManyEntries { name, limit } => write!(
+                formatter,
+                "Entry {:?} is over the limit of {} entries (max_entries)",
+                name, limit
+            ),
+            SfaError::EntryTooLarge { name, size, limit } => write!(
+                formatter,
+                "Entry {:?} of {} bytes is larger than the limit of {} bytes (max_entry_bytes)",
+                name, size, limit
+            ),
+            SfaError::TotalSizeTooLarge { name, total, limit } => write!(
+                formatter,
+                "Entry {:?} brings the size of the entries to {} bytes, over the limit of {} bytes (max_total_bytes)",
+                name, total, limit
+            ),
+            SfaError::DecodeEntry {
+                name,
+                offset,
+                source,
+            } => write!(
+                formatter,
+                "Failed to decode entry {:?} at offset {}: {}",
+                name, offset, source
+            ),
+            SfaError::Io(e) => write!(formatter, "{}", e),
+            SfaError::Image(e) => write!(formatter, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SfaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SfaError::InputEntry { source, .. } => Some(source.as_ref()),
+            SfaError::DecodeEntry { source, .. } => Some(source.as_ref()),
+            SfaError::BrokenDeltaBase { source, .. } => Some(source.as_ref()),
+            SfaError::Io(e) => Some(e),
+            SfaError::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SfaError {
+    fn from(e: io::Error) -> SfaError {
+        SfaError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for SfaError {
+    fn from(e: image::ImageError) -> SfaError {
+        SfaError::Image(e)
+    }
+}
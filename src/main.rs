@@ -1,41 +1,70 @@
-mod lib;
-use clap::{app_from_crate, arg, App};
+use clap::{app_from_crate, arg, App, AppSettings};
+use image::GenericImageView;
+use std::cmp::Ordering;
 use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::path::Path;
+use std::process;
 
 fn main() {
     let app = app_from_crate!()
         .about("Creates SFA files or extract them")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
         .subcommand(App::new("pack").about("Create a SFA archive")
             .arg(arg!(
-                output_file: -o --outfile [FILENAME] "Output SFA file to save to"
-            )).arg(arg!(
-                [input_images] ... "Input Images to use. Can be any format but output will always be PNG."
+                output_file: -o --outfile [FILENAME] "Output SFA file to save to, - for stdout"
+            ).default_value("out.sfa")).arg(arg!(
+                sort: --sort [ORDER] "Order of the input images, natural sorts frame2.png before frame10.png"
+            ).possible_values(["natural", "name", "none"]).default_value("natural")).arg(arg!(
+                <input_images> ... "Input Images to use, glob patterns like frames/*.png are expanded. Can be any format but output will always be PNG."
             ))
         )
         .subcommand(App::new("unpack").about("Unpack SFA files")
             .arg(arg!(
                 output_directory: -o --outdir [DIRECTORY] "Output Directory"
+            ).default_value(".")).arg(arg!(
+                <input_file> "Input SFA file to process, - for stdin"
+            ))
+        )
+        .subcommand(App::new("list").about("List the entries of SFA files")
+            .arg(arg!(
+                dimensions: -d --dimensions "Decode the entries to show their dimensions"
             )).arg(arg!(
-                [input_file] "Input SFA file to process"
+                <input_file> "Input SFA file to list, - for stdin"
             ))
         );
 
     let matches = app.get_matches();
 
     match matches.subcommand() {
+        // clap exits with a usage message before this point if a required
+        // argument is missing and the others have defaults, so unwrapping
+        // the values is fine
         Some(("pack", sub_matches)) => {
-            let input_images: Vec<&str> = sub_matches.values_of("input_images").unwrap().collect();
+            let mut input_images = expand_globs(sub_matches.values_of("input_images").unwrap());
+            match sub_matches.value_of("sort").unwrap() {
+                "natural" => input_images.sort_by(|a, b| natural_cmp(a, b)),
+                "name" => input_images.sort(),
+                _ => (),
+            }
             let output_file = sub_matches.value_of("output_file").unwrap();
 
-            lib::encode(&input_images, output_file)
-                .expect("Unexpected error while encoding the images");
+            if output_file == "-" {
+                let stdout = io::stdout();
+                let mut writer = BufWriter::new(stdout.lock());
+                sfa::encode_to_writer(&input_images, &mut writer)
+                    .expect("Unexpected error while encoding the images");
+                writer.flush().unwrap();
+            } else {
+                sfa::encode(&input_images, output_file)
+                    .expect("Unexpected error while encoding the images");
+            }
         }
         Some(("unpack", sub_matches)) => {
             let output_dir = Path::new(sub_matches.value_of("output_directory").unwrap());
             let input_file = sub_matches.value_of("input_file").unwrap();
 
-            let extracted_data = lib::decode(input_file).unwrap();
+            let extracted_data = sfa::decode_from_reader(&mut open_input(input_file)).unwrap();
             if !output_dir.exists() {
                 fs::create_dir(output_dir).unwrap();
             }
@@ -44,6 +73,125 @@ fn main() {
                 contents.save(output_dir.join(name)).unwrap();
             }
         }
-        _ => (),
+        Some(("list", sub_matches)) => {
+            let input_file = sub_matches.value_of("input_file").unwrap();
+
+            // stdin can only be read once, so it is kept in memory when
+            // the dimensions need a second pass over the entries
+            let stdin_data = if input_file == "-" && sub_matches.is_present("dimensions") {
+                let mut data = vec![];
+                io::stdin().read_to_end(&mut data).unwrap();
+                Some(data)
+            } else {
+                None
+            };
+            let open = || -> Box<dyn Read + '_> {
+                match &stdin_data {
+                    Some(data) => Box::new(data.as_slice()),
+                    None => open_input(input_file),
+                }
+            };
+
+            let entries = sfa::list_entries(&mut open()).unwrap();
+
+            if sub_matches.is_present("dimensions") {
+                // Decode one entry at a time only when dimensions are asked for
+                let mut sfa_reader = sfa::SfaReader::new(open()).unwrap();
+                for ((name, size), frame) in entries.iter().zip(sfa_reader.frames()) {
+                    let (width, height) = frame.unwrap().1.dimensions();
+                    println!("{}\t{}\t{}x{}", name, size, width, height);
+                }
+            } else {
+                for (name, size) in &entries {
+                    println!("{}\t{}", name, size);
+                }
+            }
+        }
+        _ => unreachable!("a subcommand is required"),
+    }
+}
+
+/// Open the input file for reading, or stdin if it is `-`.
+fn open_input(input_file: &str) -> Box<dyn Read> {
+    if input_file == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(BufReader::new(fs::File::open(input_file).unwrap()))
+    }
+}
+
+/// Expand the glob patterns among the inputs. Inputs without glob
+/// characters or naming an existing file are kept as they are, so
+/// names that were already expanded by the shell still work. Exits
+/// with an error if a pattern matches no files.
+fn expand_globs<'a, I: IntoIterator<Item = &'a str>>(inputs: I) -> Vec<String> {
+    let mut expanded = vec![];
+    for input in inputs {
+        let is_pattern = input.contains(['*', '?', '[']);
+        if !is_pattern || Path::new(input).exists() {
+            expanded.push(input.to_owned());
+            continue;
+        }
+
+        let paths = glob::glob(input).unwrap_or_else(|e| {
+            eprintln!("error: Invalid pattern {:?}: {}", input, e);
+            process::exit(2);
+        });
+        let before = expanded.len();
+        for path in paths {
+            expanded.push(path.unwrap().to_string_lossy().into_owned());
+        }
+        if expanded.len() == before {
+            eprintln!("error: No files match {:?}", input);
+            process::exit(2);
+        }
+    }
+
+    expanded
+}
+
+/// Compare names so that runs of digits are ordered by their numeric
+/// value, e.g. `frame2.png` before `frame10.png`.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                // Without leading zeros the longer number is the larger one
+                let ordering = x
+                    .trim_start_matches('0')
+                    .len()
+                    .cmp(&y.trim_start_matches('0').len())
+                    .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0')))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
     }
 }
+
+/// Take the run of ASCII digits at the start of the characters.
+fn take_number<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        number.push(c);
+    }
+
+    number
+}
@@ -0,0 +1,208 @@
+//! Metadata stored with sfa files and their entries.
+//!
+//! Files with metadata start with a metadata block after the
+//! magic, which is its size in bytes as ASCII digits and a `:`, followed
+//! by the key and value of each pair as a little endian `u32` length and
+//! that many bytes of UTF-8.
+//!
+//! The data of a trimmed entry is the width and height of the image
+//! before it was trimmed and the x and y position of the trimmed image
+//! in it as little endian `u32`, followed by the format tag and data
+//! of the trimmed image.
+
+use crate::layout::Layout;
+use crate::{parse_size, read_byte, EntryFormat, SfaError};
+use image::{DynamicImage, GenericImageView};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// Metadata of a single entry, as returned by
+/// `sfa::decode_with_entry_metadata`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct EntryMetadata {
+    /// Where the image was cut out of the original image by
+    /// `EncodeOptions::trim_transparent`. `None` if it was not trimmed.
+    pub trim: Option<Trim>,
+}
+
+impl EntryMetadata {
+    /// Read the metadata of an entry that is neither an alias nor a
+    /// delta entry, which take the metadata of the entries they refer to.
+    pub(crate) fn read(format: EntryFormat, data: &[u8]) -> Result<EntryMetadata, SfaError> {
+        let trim = match format {
+            EntryFormat::Trimmed => Some(split_trimmed(data)?.0),
+            _ => None,
+        };
+
+        Ok(EntryMetadata { trim })
+    }
+}
+
+/// The position of a trimmed image in the original image. Drawing the
+/// trimmed image at `x` and `y` on a transparent image of `width` and
+/// `height` gives back the original image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Trim {
+    /// Distance of the trimmed image from the left edge of the original.
+    pub x: u32,
+    /// Distance of the trimmed image from the top edge of the original.
+    pub y: u32,
+    /// Width of the original image.
+    pub width: u32,
+    /// Height of the original image.
+    pub height: u32,
+}
+
+/// The bounding box of the pixels of the image that are not fully
+/// transparent as `(x, y, width, height)`. `None` is returned if the
+/// image has no alpha channel or nothing can be trimmed from it.
+pub(crate) fn opaque_bounds(im: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+    if !im.color().has_alpha() {
+        return None;
+    }
+
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in im.pixels() {
+        if pixel[3] != 0 {
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+    }
+
+    let (left, top, width, height) = match bounds {
+        Some((left, top, right, bottom)) => (left, top, right - left + 1, bottom - top + 1),
+        // Images can not be empty, so a single transparent pixel is kept
+        None => (0, 0, 1, 1),
+    };
+    if (width, height) == im.dimensions() {
+        return None;
+    }
+
+    Some((left, top, width, height))
+}
+
+/// Encode the data of a trimmed entry from the data of the trimmed image.
+pub(crate) fn encode_trimmed(trim: Trim, format: EntryFormat, data: &[u8]) -> Vec<u8> {
+    let mut trimmed = Vec::with_capacity(17 + data.len());
+    for value in [trim.width, trim.height, trim.x, trim.y] {
+        trimmed.extend_from_slice(&value.to_le_bytes());
+    }
+    trimmed.push(format.tag());
+    trimmed.extend_from_slice(data);
+
+    trimmed
+}
+
+/// Split the data of a trimmed entry into its position and the format
+/// and data of the trimmed image.
+pub(crate) fn split_trimmed(data: &[u8]) -> Result<(Trim, EntryFormat, &[u8]), SfaError> {
+    if data.len() < 17 {
+        return Err(SfaError::UnexpectedEof);
+    }
+
+    let value = |i: usize| u32::from_le_bytes(data[i * 4..i * 4 + 4].try_into().unwrap());
+    let trim = Trim {
+        width: value(0),
+        height: value(1),
+        x: value(2),
+        y: value(3),
+    };
+    let format = match EntryFormat::from_tag(data[16])? {
+        // Only image data can be trimmed
+        EntryFormat::Alias
+        | EntryFormat::Delta
+        | EntryFormat::Trimmed
+        | EntryFormat::End
+        | EntryFormat::Toc => return Err(SfaError::UnknownFormatTag(data[16])),
+        format => format,
+    };
+
+    Ok((trim, format, &data[17..]))
+}
+
+/// Write the metadata block of files whose layout has one. Nothing is
+/// written for other files. The pairs are sorted by their keys so
+/// that the same metadata is always written the same way.
+pub(crate) fn write_archive_metadata<W: Write>(
+    writer: &mut W,
+    layout: Layout,
+    metadata: &HashMap<String, String>,
+) -> Result<(), SfaError> {
+    if !layout.metadata() {
+        return Ok(());
+    }
+
+    let mut pairs: Vec<(&String, &String)> = metadata.iter().collect();
+    pairs.sort();
+
+    let mut block: Vec<u8> = vec![];
+    for (key, value) in pairs {
+        for text in [key, value] {
+            block.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            block.extend_from_slice(text.as_bytes());
+        }
+    }
+    writer.write_all(format!("{}:", block.len()).as_bytes())?;
+    writer.write_all(&block)?;
+
+    Ok(())
+}
+
+/// Read the metadata block of files whose layout has one. Other files
+/// have no metadata.
+pub(crate) fn read_archive_metadata<R: Read>(
+    reader: &mut R,
+    layout: Layout,
+) -> Result<HashMap<String, String>, SfaError> {
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    if !layout.metadata() {
+        return Ok(metadata);
+    }
+
+    let mut size: Vec<u8> = vec![];
+    loop {
+        match read_byte(reader)? {
+            Some(b':') => break,
+            Some(b) => size.push(b),
+            None => return Err(SfaError::UnexpectedEof),
+        }
+    }
+    let size = parse_size(&size)?;
+
+    // Like entry data, the block grows with the data actually read
+    let mut block: Vec<u8> = vec![];
+    reader.take(size as u64).read_to_end(&mut block)?;
+    if block.len() != size {
+        return Err(SfaError::InvalidSize(size.to_string()));
+    }
+
+    let mut rest = block.as_slice();
+    while !rest.is_empty() {
+        let key = take_text(&mut rest)?;
+        let value = take_text(&mut rest)?;
+        metadata.insert(key, value);
+    }
+
+    Ok(metadata)
+}
+
+/// Take a length prefixed UTF-8 text from the start of the metadata block.
+fn take_text(rest: &mut &[u8]) -> Result<String, SfaError> {
+    if rest.len() < 4 {
+        return Err(SfaError::InvalidMetadata);
+    }
+    let (length, data) = rest.split_at(4);
+    let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+    if data.len() < length {
+        return Err(SfaError::InvalidMetadata);
+    }
+
+    let (text, data) = data.split_at(length);
+    *rest = data;
+    String::from_utf8(text.to_vec()).map_err(|_| SfaError::InvalidMetadata)
+}
@@ -0,0 +1,103 @@
+//! Decoding of memory mapped files, with the `mmap` feature.
+
+use crate::{collect_unique, decode_from_slice, decode_raw_slice, SfaError};
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A sfa file that is mapped into memory instead of being read into a
+/// buffer. The headers are parsed from the mapped bytes and the images
+/// are decoded straight from them, so the data of the entries is never
+/// copied. The operating system only loads the parts of the file that
+/// are actually read, which keeps very large archives cheap to open.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_slice, decode_raw_from_reader, encode_images_to_vec, SfaMmap};
+///
+/// let frames = vec![
+///     (String::from("idle.png"), DynamicImage::new_rgba8(4, 4)),
+///     (String::from("walk.png"), DynamicImage::new_rgba8(8, 8)),
+/// ];
+/// let archive = encode_images_to_vec(frames).unwrap();
+/// let path = std::env::temp_dir().join("sfa-mmap-example.sfa");
+/// std::fs::write(&path, &archive).unwrap();
+///
+/// // Safety: the file is not changed while it is mapped
+/// let mapped = unsafe { SfaMmap::open(&path) }.unwrap();
+/// assert_eq!(mapped.as_bytes(), archive.as_slice());
+///
+/// let decoded = mapped.decode().unwrap();
+/// let expected = decode_from_slice(&archive).unwrap();
+/// assert_eq!(decoded.len(), expected.len());
+/// for (name, im) in &expected {
+///     assert_eq!(decoded[name].as_bytes(), im.as_bytes());
+/// }
+///
+/// let raw = mapped.decode_raw().unwrap();
+/// let expected = decode_raw_from_reader(&mut archive.as_slice()).unwrap();
+/// for (name, data) in &expected {
+///     assert_eq!(&*raw[name.as_str()], data.as_slice());
+/// }
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct SfaMmap {
+    map: Mmap,
+}
+
+impl SfaMmap {
+    /// Map the sfa file at the given path into memory. Nothing is read
+    /// until the entries are decoded.
+    ///
+    /// # Safety
+    ///
+    /// The mapped bytes are the contents of the file, so they change
+    /// when the file is changed by this or any other process while it
+    /// is mapped, which is undefined behavior. If the file is truncated,
+    /// reading the bytes past its new end crashes the process with
+    /// `SIGBUS` on Unix instead of returning an error. The file must not
+    /// be changed until the `SfaMmap` is dropped. Use `sfa::decode` for
+    /// files that might be written to at the same time, such as files
+    /// that are being downloaded.
+    ///
+    /// # Errors
+    ///
+    /// * Opening or mapping the file was unsuccessful. (`SfaError::Io`)
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<SfaMmap, SfaError> {
+        let file = fs::File::open(path)?;
+        let map = Mmap::map(&file)?;
+
+        Ok(SfaMmap { map })
+    }
+
+    /// The mapped bytes of the file.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Decode the entries of the file like `sfa::decode_from_slice`.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same as those of `sfa::decode_from_slice`.
+    pub fn decode(&self) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+        decode_from_slice(&self.map)
+    }
+
+    /// Get the stored image data of the entries of the file like
+    /// `sfa::decode_raw_from_reader`, borrowed from the mapped bytes.
+    /// Only delta entries, whose images are decoded and encoded as PNG,
+    /// have data of their own.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same as those of `sfa::decode_raw_from_reader`,
+    /// apart from `SfaError::Io`.
+    pub fn decode_raw(&self) -> Result<HashMap<String, Cow<'_, [u8]>>, SfaError> {
+        collect_unique(decode_raw_slice(&self.map)?.into_iter().map(Ok))
+    }
+}
@@ -0,0 +1,1541 @@
+//! Options for customizing how sfa files are written and read.
+
+use crate::layout::Layout;
+use crate::SfaError;
+use image::codecs::png::{CompressionType, FilterType};
+use image::imageops;
+use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, Luma, LumaA};
+use std::collections::HashMap;
+
+/// Options for encoding sfa files. `EncodeOptions::default()`
+/// produces exactly the same output as the functions without
+/// options such as `sfa::encode`.
+///
+/// # Examples
+///
+/// ```
+/// use sfa::{CompressionLevel, EncodeOptions};
+///
+/// let options = EncodeOptions {
+///     compression: CompressionLevel::Best,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EncodeOptions {
+    /// Format of the data stored for each entry. Defaults to
+    /// `StorageFormat::Png`.
+    pub format: StorageFormat,
+    /// Compression level used while writing the PNG data of the entries.
+    /// Defaults to `CompressionLevel::Default`.
+    pub compression: CompressionLevel,
+    /// Filter applied to the rows of the PNG data before compression.
+    /// Defaults to `PngFilter::Sub`.
+    pub filter: PngFilter,
+    /// Write the PNG data of the entries interlaced with Adam7 so that
+    /// they can be rendered progressively while being downloaded.
+    /// Interlaced images are usually noticeably larger because the
+    /// neighbouring pixels of each pass are further apart and thus
+    /// compress worse. Decoding handles interlaced entries transparently.
+    /// Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, Rgba, RgbaImage};
+    /// use sfa::{decode_from_reader, encode_to_writer_with_options, EncodeOptions};
+    ///
+    /// let sprite = RgbaImage::from_fn(13, 7, |x, y| Rgba([x as u8 * 16, y as u8 * 32, 0, 255]));
+    /// let frame = std::env::temp_dir().join("sfa_interlaced.png");
+    /// DynamicImage::ImageRgba8(sprite.clone()).save(&frame).unwrap();
+    ///
+    /// let options = EncodeOptions {
+    ///     interlaced: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&[&frame], &mut archive, &options).unwrap();
+    ///
+    /// // The interlace method is the last byte of the PNG header
+    /// let png = archive.windows(4).position(|w| w == b"\x89PNG").unwrap();
+    /// assert_eq!(archive[png + 28], 1);
+    ///
+    /// let decoded = decode_from_reader(&mut archive.as_slice()).unwrap();
+    /// assert_eq!(decoded["sfa_interlaced.png"].to_rgba8(), sprite);
+    /// ```
+    pub interlaced: bool,
+    /// Copy input files that are already PNG into the archive byte for
+    /// byte instead of decoding and re-encoding them. This saves time and
+    /// keeps files that were optimized with tools like `oxipng` as small
+    /// as they are. The files are still fully decoded once to make sure
+    /// that they are valid. The other options have no effect on these
+    /// files. Other formats are converted to PNG as usual. Only applies
+    /// to functions that read input files. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::DynamicImage;
+    /// use sfa::{encode_to_writer_with_options, list_entries, EncodeOptions};
+    ///
+    /// let frame = std::env::temp_dir().join("sfa_reuse_png_bytes.png");
+    /// DynamicImage::new_rgba8(16, 16).save(&frame).unwrap();
+    ///
+    /// let options = EncodeOptions {
+    ///     reuse_png_bytes: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&[&frame], &mut archive, &options).unwrap();
+    ///
+    /// let entries = list_entries(&mut archive.as_slice()).unwrap();
+    /// let original = std::fs::metadata(&frame).unwrap().len() as usize;
+    /// assert_eq!(entries[0].1, original);
+    /// ```
+    pub reuse_png_bytes: bool,
+    /// Store the input files byte for byte in their original format
+    /// instead of converting them to PNG. Lossy formats like JPEG are
+    /// often many times smaller than the same image as PNG. The files
+    /// are still fully decoded once to make sure that they are valid.
+    ///
+    /// This breaks the guarantee that every entry is PNG data. Decoding
+    /// reads the format of every entry from the tag that follows its
+    /// header and handles both. Files of formats that sfa has no tag for are converted to PNG. Takes
+    /// precedence over `reuse_png_bytes` and only applies to functions
+    /// that read input files. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, ImageOutputFormat};
+    /// use sfa::{decode_from_reader, encode_to_writer_with_options, list_entries, EncodeOptions};
+    ///
+    /// let frame = std::env::temp_dir().join("sfa_keep_original_format.jpg");
+    /// let mut jpeg = vec![];
+    /// DynamicImage::new_rgb8(64, 64)
+    ///     .write_to(&mut jpeg, ImageOutputFormat::Jpeg(90))
+    ///     .unwrap();
+    /// std::fs::write(&frame, &jpeg).unwrap();
+    ///
+    /// let options = EncodeOptions {
+    ///     keep_original_format: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&[&frame], &mut archive, &options).unwrap();
+    ///
+    /// let entries = list_entries(&mut archive.as_slice()).unwrap();
+    /// assert_eq!(entries[0].1, jpeg.len());
+    ///
+    /// let decoded = decode_from_reader(&mut archive.as_slice()).unwrap();
+    /// assert_eq!(decoded["sfa_keep_original_format.jpg"].to_rgb8().dimensions(), (64, 64));
+    /// ```
+    pub keep_original_format: bool,
+    /// Largest number of bytes of encoded data of a single entry that is
+    /// kept in memory. The size of an entry is written before its data,
+    /// so the data has to be buffered until it is complete. With a limit,
+    /// data that grows beyond it is moved to a temporary file which is
+    /// then copied into the archive. This keeps the memory use bounded
+    /// when packing very large images, the decoded image itself still
+    /// has to fit into memory. Only applies to non interlaced entries
+    /// stored as PNG. Defaults to `None`, which buffers everything in
+    /// memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, Rgb, RgbImage};
+    /// use sfa::{decode_from_slice, encode_to_writer_with_options, list_entries, EncodeOptions};
+    ///
+    /// let layer = RgbImage::from_fn(512, 512, |x, y| Rgb([(x ^ y) as u8, (x * y) as u8, x as u8]));
+    /// let frame = std::env::temp_dir().join("sfa_buffer_limit.png");
+    /// DynamicImage::ImageRgb8(layer.clone()).save(&frame).unwrap();
+    ///
+    /// let options = EncodeOptions {
+    ///     buffer_limit: Some(4096),
+    ///     ..Default::default()
+    /// };
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&[&frame], &mut archive, &options).unwrap();
+    ///
+    /// let entries = list_entries(&mut archive.as_slice()).unwrap();
+    /// assert!(entries[0].1 > 4096);
+    ///
+    /// let decoded = decode_from_slice(&archive).unwrap();
+    /// assert_eq!(decoded["sfa_buffer_limit.png"].to_rgb8(), layer);
+    /// ```
+    pub buffer_limit: Option<usize>,
+    /// Store the paths of the input files as given instead of only their
+    /// file names. Packing `assets/sprites/run_1.png` then creates an
+    /// entry named `assets/sprites/run_1.png` instead of `run_1.png`.
+    /// Only applies to functions that read input files. Defaults to
+    /// `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::DynamicImage;
+    /// use sfa::{encode_to_writer_with_options, list_entries, EncodeOptions};
+    ///
+    /// let frame = std::env::temp_dir().join("sfa_keep_paths.png");
+    /// DynamicImage::new_rgba8(4, 4).save(&frame).unwrap();
+    ///
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&[&frame], &mut archive, &EncodeOptions::default()).unwrap();
+    /// assert_eq!(list_entries(&mut archive.as_slice()).unwrap()[0].0, "sfa_keep_paths.png");
+    ///
+    /// let options = EncodeOptions {
+    ///     keep_paths: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&[&frame], &mut archive, &options).unwrap();
+    /// assert_eq!(list_entries(&mut archive.as_slice()).unwrap()[0].0, frame.to_str().unwrap());
+    /// ```
+    pub keep_paths: bool,
+    /// Store entries whose encoded data is identical to an earlier entry,
+    /// such as repeated frames of an animation, as a small alias of that
+    /// entry instead of storing the data again. Duplicates are found by
+    /// comparing SHA-256 hashes of the data. Decoding resolves aliases
+    /// transparently, so every name still has its own image.
+    ///
+    /// The data of every entry is kept in memory while it is written, so
+    /// `buffer_limit` has no effect. Only applies to functions that read
+    /// input files and to `sfa::SfaBuilder`. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::DynamicImage;
+    /// use sfa::{decode_from_slice, encode_to_writer_with_options, list_entries, EncodeOptions};
+    ///
+    /// let dir = std::env::temp_dir();
+    /// let frames: Vec<_> = (1..=3)
+    ///     .map(|i| {
+    ///         let path = dir.join(format!("sfa_dedupe_{}.png", i));
+    ///         DynamicImage::new_rgba8(32, 32).save(&path).unwrap();
+    ///         path
+    ///     })
+    ///     .collect();
+    ///
+    /// let options = EncodeOptions {
+    ///     dedupe: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&frames, &mut archive, &options).unwrap();
+    ///
+    /// // The second and third entries only contain the name of the first one
+    /// let entries = list_entries(&mut archive.as_slice()).unwrap();
+    /// assert_eq!(entries[1].1, "sfa_dedupe_1.png".len());
+    /// assert_eq!(entries[2].1, "sfa_dedupe_1.png".len());
+    ///
+    /// let decoded = decode_from_slice(&archive).unwrap();
+    /// assert_eq!(decoded.len(), 3);
+    /// assert_eq!(decoded["sfa_dedupe_3.png"].to_rgba8(), decoded["sfa_dedupe_1.png"].to_rgba8());
+    /// ```
+    ///
+    /// Images that are already in memory, like the frames of a looping
+    /// animation, are deduplicated by adding them to a `sfa::SfaBuilder`.
+    ///
+    /// ```
+    /// use image::{DynamicImage, Rgba, RgbaImage};
+    /// use sfa::{decode_from_reader_ordered, EncodeOptions, SfaBuilder};
+    ///
+    /// let poses: Vec<DynamicImage> = (0..3)
+    ///     .map(|i| RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8 * i, y as u8, 0, 255])))
+    ///     .map(DynamicImage::ImageRgba8)
+    ///     .collect();
+    ///
+    /// let encode = |dedupe| {
+    ///     let options = EncodeOptions {
+    ///         dedupe,
+    ///         ..Default::default()
+    ///     };
+    ///     let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    ///     // Three loops of the same three poses
+    ///     for i in 0..9 {
+    ///         builder.add_image(&format!("loop_{}.png", i), &poses[i % 3]).unwrap();
+    ///     }
+    ///     builder.finish().unwrap()
+    /// };
+    /// let full = encode(false);
+    /// let deduped = encode(true);
+    /// assert!(deduped.len() * 2 < full.len());
+    ///
+    /// let decoded = decode_from_reader_ordered(&mut deduped.as_slice()).unwrap();
+    /// assert_eq!(decoded.len(), 9);
+    /// assert_eq!(decoded[7].1.to_rgba8(), poses[1].to_rgba8());
+    /// ```
+    pub dedupe: bool,
+    /// Store images that have the same dimensions as the image before
+    /// them as a delta entry, which only contains the bounding box of
+    /// the pixels that changed as a PNG image and its position. This
+    /// makes animations where only a small part of the frame moves much
+    /// smaller. Decoding reconstructs the full image by replacing that
+    /// region of the previous image, with the color type of the previous
+    /// image. Images that changed everywhere, have another color type
+    /// than the previous image or have more than 8 bits per channel are
+    /// stored in full.
+    ///
+    /// The changes are found by comparing to the previous input image,
+    /// so with a lossy `format` the unchanged pixels keep the artifacts
+    /// of the entry before them. If an entry can not be decoded, the
+    /// decoding functions return `SfaError::BrokenDeltaBase` naming the
+    /// delta entries that depend on it.
+    ///
+    /// Like `dedupe`, `buffer_limit` has no effect. Only applies to functions
+    /// that read input files and to `sfa::SfaBuilder`. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, Rgba, RgbaImage};
+    /// use sfa::{decode_from_reader_ordered, EncodeOptions, SfaBuilder};
+    ///
+    /// // A noisy background with a small square moving over it
+    /// let frames: Vec<DynamicImage> = (0..20)
+    ///     .map(|i| {
+    ///         DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 64, |x, y| {
+    ///             if (i..i + 4).contains(&x) && (8..12).contains(&y) {
+    ///                 Rgba([255, 0, 0, 255])
+    ///             } else {
+    ///                 let noise = (x * 7919 + y * 104_729) % 251;
+    ///                 Rgba([noise as u8, (noise * 3) as u8, (noise * 7) as u8, 255])
+    ///             }
+    ///         }))
+    ///     })
+    ///     .collect();
+    ///
+    /// let encode = |delta_frames| {
+    ///     let options = EncodeOptions {
+    ///         delta_frames,
+    ///         ..Default::default()
+    ///     };
+    ///     let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    ///     for (i, frame) in frames.iter().enumerate() {
+    ///         builder.add_image(&format!("move_{}.png", i), frame).unwrap();
+    ///     }
+    ///     builder.finish().unwrap()
+    /// };
+    /// let full = encode(false);
+    /// let delta = encode(true);
+    /// assert!(delta.len() * 2 < full.len());
+    ///
+    /// let decoded = decode_from_reader_ordered(&mut delta.as_slice()).unwrap();
+    /// assert_eq!(decoded[19].1.to_rgba8(), frames[19].to_rgba8());
+    /// ```
+    pub delta_frames: bool,
+    /// Convert every image to the given color type before it is encoded,
+    /// so that decoding returns the same `DynamicImage` variant for all
+    /// the entries no matter which color types the inputs had. Images
+    /// with 16 bits per channel lose precision when converted to one of
+    /// the 8 bit color types. Input files that already have the color
+    /// type are still copied as is with `keep_original_format` or
+    /// `reuse_png_bytes`. Formats that can not store the color type,
+    /// like JPEG which has no alpha channel, decode to the closest one
+    /// they have. Defaults to `None`, which keeps the color types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::DynamicImage;
+    /// use sfa::{decode_from_slice, ColorTarget, EncodeOptions, SfaBuilder};
+    ///
+    /// let options = EncodeOptions {
+    ///     force_color: Some(ColorTarget::Rgba8),
+    ///     ..Default::default()
+    /// };
+    /// let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    /// builder.add_image("rgb.png", &DynamicImage::new_rgb8(4, 4)).unwrap();
+    /// builder.add_image("rgba16.png", &DynamicImage::new_rgba16(4, 4)).unwrap();
+    /// builder.add_image("luma.png", &DynamicImage::new_luma8(4, 4)).unwrap();
+    /// let archive = builder.finish().unwrap();
+    ///
+    /// let decoded = decode_from_slice(&archive).unwrap();
+    /// assert_eq!(decoded.len(), 3);
+    /// assert!(decoded
+    ///     .values()
+    ///     .all(|im| matches!(im, DynamicImage::ImageRgba8(_))));
+    /// ```
+    pub force_color: Option<ColorTarget>,
+    /// Store color images whose pixels are all gray as grayscale, which
+    /// makes them a lot smaller. Every pixel of the image is checked, and
+    /// if red, green and blue are equal for all of them the image is
+    /// converted to `Luma8`, or to `LumaA8` if some pixels are not fully
+    /// opaque. The pixel values do not change, only the decoded
+    /// `DynamicImage` variant does. Only images with 8 bits per channel
+    /// are converted. Has no effect together with `force_color`.
+    /// Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, Rgb, RgbImage};
+    /// use sfa::{decode_from_slice, EncodeOptions, SfaBuilder};
+    ///
+    /// let gray = RgbImage::from_fn(64, 64, |x, _| Rgb([x as u8 * 4; 3]));
+    /// let color = RgbImage::from_fn(64, 64, |x, y| Rgb([x as u8 * 4, y as u8 * 4, 0]));
+    ///
+    /// let options = EncodeOptions {
+    ///     detect_grayscale: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    /// builder.add_image("gray.png", &DynamicImage::ImageRgb8(gray.clone())).unwrap();
+    /// builder.add_image("color.png", &DynamicImage::ImageRgb8(color.clone())).unwrap();
+    /// let archive = builder.finish().unwrap();
+    ///
+    /// let decoded = decode_from_slice(&archive).unwrap();
+    /// assert!(matches!(decoded["gray.png"], DynamicImage::ImageLuma8(_)));
+    /// assert_eq!(decoded["gray.png"].to_rgb8(), gray);
+    /// assert!(matches!(decoded["color.png"], DynamicImage::ImageRgb8(_)));
+    /// assert_eq!(decoded["color.png"].to_rgb8(), color);
+    /// ```
+    pub detect_grayscale: bool,
+    /// Store color images with few colors as indexed color PNG, which
+    /// only stores a palette and one index per pixel and is much smaller
+    /// for things like pixel art. Images with more colors than
+    /// `QuantizeOptions::max_colors` are stored as usual, unless
+    /// `QuantizeOptions::force` is set, in which case their colors are
+    /// reduced to the closest ones that fit into the palette.
+    ///
+    /// Images that fit into the palette decode to exactly the same pixels.
+    /// **Forced quantization is lossy**, the decoded images are only an
+    /// approximation of the input. Only color images with 8 bits per
+    /// channel are quantized, they decode as RGB or RGBA depending on
+    /// whether they had an alpha channel. Only applies to entries stored
+    /// as PNG. Defaults to `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, Rgba, RgbaImage};
+    /// use sfa::{decode_from_slice, EncodeOptions, QuantizeOptions, SfaBuilder};
+    ///
+    /// // A sprite with 16 colors
+    /// let sprite = RgbaImage::from_fn(64, 64, |x, y| {
+    ///     let color = ((x / 4 + y / 4) % 16) as u8;
+    ///     Rgba([color * 16, 255 - color * 16, color * 8, 255])
+    /// });
+    ///
+    /// let encode = |quantize| {
+    ///     let options = EncodeOptions {
+    ///         quantize,
+    ///         ..Default::default()
+    ///     };
+    ///     let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    ///     builder.add_image("sprite.png", &DynamicImage::ImageRgba8(sprite.clone())).unwrap();
+    ///     builder.finish().unwrap()
+    /// };
+    /// let full = encode(None);
+    /// let indexed = encode(Some(QuantizeOptions::default()));
+    /// assert!(indexed.len() < full.len());
+    ///
+    /// // The sprite fits into the palette, so nothing is lost
+    /// let decoded = decode_from_slice(&indexed).unwrap();
+    /// assert_eq!(decoded["sprite.png"].to_rgba8(), sprite);
+    /// ```
+    pub quantize: Option<QuantizeOptions>,
+    /// Resize every image before it is encoded, for example to store
+    /// smaller versions of the sprites for low-end devices. The names of
+    /// the entries stay the same. Input files that keep their dimensions
+    /// are still copied as is with `keep_original_format` or
+    /// `reuse_png_bytes`. Defaults to `None`, which keeps the dimensions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, GenericImageView};
+    /// use sfa::{decode_from_slice, EncodeOptions, ResizeSpec, SfaBuilder};
+    ///
+    /// let dimensions = |resize| {
+    ///     let options = EncodeOptions {
+    ///         resize: Some(resize),
+    ///         ..Default::default()
+    ///     };
+    ///     let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    ///     builder.add_image("wide.png", &DynamicImage::new_rgba8(200, 100)).unwrap();
+    ///     let decoded = decode_from_slice(&builder.finish().unwrap()).unwrap();
+    ///     decoded["wide.png"].dimensions()
+    /// };
+    ///
+    /// assert_eq!(dimensions(ResizeSpec::Exact(64, 64)), (64, 64));
+    /// assert_eq!(dimensions(ResizeSpec::Scale(0.5)), (100, 50));
+    /// // The aspect ratio is kept
+    /// assert_eq!(dimensions(ResizeSpec::FitWithin(50, 50)), (50, 25));
+    /// // Images that already fit are not enlarged
+    /// assert_eq!(dimensions(ResizeSpec::FitWithin(400, 400)), (200, 100));
+    /// ```
+    pub resize: Option<ResizeSpec>,
+    /// Filter used to sample the images with `resize`. Defaults to
+    /// `ResizeFilter::Lanczos3`.
+    pub resize_filter: ResizeFilter,
+    /// Store a CRC32 checksum of the data of every entry, which the
+    /// decoding functions verify so that a corrupt or partially written
+    /// file fails with `SfaError::ChecksumMismatch` instead of decoding
+    /// into broken images or failing with errors of the `image` crate.
+    ///
+    /// Archives written with this option use version 9 of the file format
+    /// (`SFA9;`) with the checksums turned on in the byte of flags after
+    /// it, which older versions of this crate can not read. Defaults to
+    /// `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::DynamicImage;
+    /// use sfa::{decode_from_slice, EncodeOptions, SfaBuilder, SfaError};
+    ///
+    /// let options = EncodeOptions {
+    ///     checksums: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    /// builder.add_image("frame.png", &DynamicImage::new_rgba8(16, 16)).unwrap();
+    /// let mut archive = builder.finish().unwrap();
+    /// assert!(archive.starts_with(b"SFA9;\x05"));
+    /// assert!(decode_from_slice(&archive).is_ok());
+    ///
+    /// // Flip a byte in the middle of the PNG data
+    /// let middle = archive.len() - 20;
+    /// archive[middle] ^= 0xff;
+    /// let result = decode_from_slice(&archive);
+    /// assert!(matches!(result, Err(SfaError::ChecksumMismatch { name }) if name == "frame.png"));
+    /// ```
+    pub checksums: bool,
+    /// Cut the fully transparent borders off the images before they are
+    /// stored, which makes frames exported with large empty margins smaller
+    /// and faster to decode. The size of the original image and the
+    /// position of the trimmed image in it are stored with the entry and
+    /// returned as `sfa::EntryMetadata::trim` by
+    /// `sfa::decode_with_entry_metadata`, while all the decoding functions
+    /// return the trimmed images.
+    ///
+    /// Only images with an alpha channel are trimmed. `delta_frames` has
+    /// no effect with this option. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, Rgba, RgbaImage};
+    /// use sfa::{decode_with_entry_metadata, EncodeOptions, SfaBuilder};
+    ///
+    /// // A small sprite in the middle of a large transparent canvas
+    /// let frame = RgbaImage::from_fn(256, 256, |x, y| match (x, y) {
+    ///     (120..=129, 60..=69) => Rgba([x as u8, y as u8, 200, 255]),
+    ///     _ => Rgba([0, 0, 0, 0]),
+    /// });
+    /// let frame = DynamicImage::ImageRgba8(frame);
+    ///
+    /// let mut sizes = vec![];
+    /// for trim_transparent in [false, true] {
+    ///     let options = EncodeOptions {
+    ///         trim_transparent,
+    ///         ..Default::default()
+    ///     };
+    ///     let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    ///     builder.add_image("sprite.png", &frame).unwrap();
+    ///     sizes.push(builder.finish().unwrap());
+    /// }
+    /// assert!(sizes[1].len() < sizes[0].len());
+    ///
+    /// let decoded = decode_with_entry_metadata(&mut sizes[1].as_slice()).unwrap();
+    /// let (_, sprite, metadata) = &decoded[0];
+    /// assert_eq!(sprite.to_rgba8().dimensions(), (10, 10));
+    /// let trim = metadata.trim.unwrap();
+    /// assert_eq!((trim.x, trim.y, trim.width, trim.height), (120, 60, 256, 256));
+    /// ```
+    pub trim_transparent: bool,
+    /// Key value pairs stored once for the whole archive, like the frame
+    /// rate of an animation or its author, which are returned by
+    /// `sfa::decode_with_metadata`. The other decoding functions skip them.
+    ///
+    /// Archives with metadata use version 9 of the file format (`SFA9;`)
+    /// with the metadata block turned on in the byte of flags after it,
+    /// which older versions of this crate can not read. Defaults to no
+    /// metadata.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use image::DynamicImage;
+    /// use sfa::{decode_from_slice, decode_with_metadata, EncodeOptions, SfaBuilder};
+    ///
+    /// let mut metadata = HashMap::new();
+    /// metadata.insert(String::from("palette"), String::from("dawnbringer"));
+    /// let options = EncodeOptions {
+    ///     metadata,
+    ///     ..Default::default()
+    /// };
+    /// let mut builder = SfaBuilder::with_options(vec![], options).unwrap();
+    /// builder.add_image("idle.png", &DynamicImage::new_rgba8(4, 4)).unwrap();
+    /// let archive = builder.finish().unwrap();
+    /// assert!(archive.starts_with(b"SFA9;\x06"));
+    ///
+    /// // Decoding without the metadata skips it
+    /// assert!(decode_from_slice(&archive).unwrap().contains_key("idle.png"));
+    /// let (_, metadata) = decode_with_metadata(&mut archive.as_slice()).unwrap();
+    /// assert_eq!(metadata["palette"], "dawnbringer");
+    /// ```
+    pub metadata: HashMap<String, String>,
+    /// Store input files exactly as their pixels are laid out instead of
+    /// turning them upright by their EXIF orientation. Cameras save photos
+    /// in the orientation of the sensor and only record how to turn them
+    /// in the EXIF data, which the decoders ignore and PNG has no place
+    /// for. Files that are turned are never copied as they are by
+    /// `keep_original_format` or `reuse_png_bytes`. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, ImageOutputFormat, Rgb, RgbImage};
+    /// use sfa::{decode_from_reader, encode_to_writer_with_options, EncodeOptions};
+    ///
+    /// // Red on the left and blue on the right
+    /// let photo = RgbImage::from_fn(32, 16, |x, _| match x {
+    ///     0..=15 => Rgb([255, 0, 0]),
+    ///     _ => Rgb([0, 0, 255]),
+    /// });
+    /// let mut jpeg = vec![];
+    /// DynamicImage::ImageRgb8(photo)
+    ///     .write_to(&mut jpeg, ImageOutputFormat::Jpeg(90))
+    ///     .unwrap();
+    ///
+    /// // The dimensions and a red pixel once the photo is upright
+    /// for (orientation, dimensions, (x, y)) in [
+    ///     (3, (32, 16), (24, 8)),
+    ///     (6, (16, 32), (8, 8)),
+    ///     (8, (16, 32), (8, 24)),
+    /// ] {
+    ///     // An EXIF segment with only the orientation after the start of the JPEG
+    ///     let mut file = jpeg[..2].to_vec();
+    ///     file.extend_from_slice(b"\xff\xe1\x00\x22Exif\x00\x00MM\x00\x2a\x00\x00\x00\x08");
+    ///     file.extend_from_slice(b"\x00\x01\x01\x12\x00\x03\x00\x00\x00\x01");
+    ///     file.extend_from_slice(&[0, orientation, 0, 0, 0, 0, 0, 0]);
+    ///     file.extend_from_slice(&jpeg[2..]);
+    ///     let name = format!("sfa_orientation_{}.jpg", orientation);
+    ///     let path = std::env::temp_dir().join(&name);
+    ///     std::fs::write(&path, &file).unwrap();
+    ///
+    ///     for ignore_exif_orientation in [false, true] {
+    ///         let options = EncodeOptions {
+    ///             ignore_exif_orientation,
+    ///             ..Default::default()
+    ///         };
+    ///         let mut archive = vec![];
+    ///         encode_to_writer_with_options(&[&path], &mut archive, &options).unwrap();
+    ///
+    ///         let decoded = decode_from_reader(&mut archive.as_slice()).unwrap();
+    ///         let im = decoded[&name].to_rgb8();
+    ///         if ignore_exif_orientation {
+    ///             assert_eq!(im.dimensions(), (32, 16));
+    ///         } else {
+    ///             assert_eq!(im.dimensions(), dimensions);
+    ///             let pixel = im.get_pixel(x, y);
+    ///             assert!(pixel[0] > 200 && pixel[2] < 60);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub ignore_exif_orientation: bool,
+    /// Which metadata of the input files is copied into the PNG data of
+    /// their entries. Defaults to `MetadataPolicy::Strip`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, ImageOutputFormat};
+    /// use sfa::{encode_to_writer_with_options, EncodeOptions, MetadataPolicy};
+    ///
+    /// let mut jpeg = vec![];
+    /// DynamicImage::new_rgb8(8, 8)
+    ///     .write_to(&mut jpeg, ImageOutputFormat::Jpeg(90))
+    ///     .unwrap();
+    ///
+    /// // A JPEG with an ICC profile in a single APP2 segment
+    /// let profile = [7u8; 64];
+    /// let mut file = jpeg[..2].to_vec();
+    /// file.extend_from_slice(&[0xff, 0xe2, 0, 2 + 14 + 64]);
+    /// file.extend_from_slice(b"ICC_PROFILE\0\x01\x01");
+    /// file.extend_from_slice(&profile);
+    /// file.extend_from_slice(&jpeg[2..]);
+    /// let path = std::env::temp_dir().join("sfa_metadata_policy.jpg");
+    /// std::fs::write(&path, &file).unwrap();
+    ///
+    /// for (metadata_policy, has_profile) in [
+    ///     (MetadataPolicy::Strip, false),
+    ///     (MetadataPolicy::PreserveIcc, true),
+    /// ] {
+    ///     let options = EncodeOptions {
+    ///         metadata_policy,
+    ///         ..Default::default()
+    ///     };
+    ///     let mut archive = vec![];
+    ///     encode_to_writer_with_options(&[&path], &mut archive, &options).unwrap();
+    ///     assert_eq!(archive.windows(4).any(|w| w == b"iCCP"), has_profile);
+    /// }
+    /// ```
+    pub metadata_policy: MetadataPolicy,
+    /// Allow writing archives without entries. Without this option,
+    /// encoding fails with `SfaError::EmptyInput` if there is nothing to
+    /// encode, which is usually a mistake like a glob that matched no
+    /// files. The functions without options never allow it.
+    ///
+    /// Like all archives, empty ones end with the number of entries, so
+    /// that decoding can tell an empty archive from one that was cut short
+    /// and fails with `SfaError::UnexpectedEof` or
+    /// `SfaError::EntryCountMismatch` for the latter. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sfa::{decode_from_slice, encode_to_writer_with_options, EncodeOptions, SfaError};
+    ///
+    /// let no_files: [&str; 0] = [];
+    /// let result = encode_to_writer_with_options(&no_files, &mut vec![], &EncodeOptions::default());
+    /// assert!(matches!(result, Err(SfaError::EmptyInput)));
+    ///
+    /// let options = EncodeOptions {
+    ///     allow_empty: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&no_files, &mut archive, &options).unwrap();
+    /// assert!(decode_from_slice(&archive).unwrap().is_empty());
+    ///
+    /// // Only the magic is left of an archive that was cut short
+    /// let result = decode_from_slice(b"SFA9;\x04");
+    /// assert!(matches!(result, Err(SfaError::UnexpectedEof)));
+    ///
+    /// // Nothing may follow the number of entries
+    /// archive.extend_from_slice(b"junk");
+    /// let result = decode_from_slice(&archive);
+    /// assert!(matches!(
+    ///     result,
+    ///     Err(SfaError::TrailingData { count: 4, preview }) if preview == b"junk"
+    /// ));
+    /// ```
+    pub allow_empty: bool,
+    /// Write a table of contents with the name, position and size of
+    /// every entry after the last entry, which `sfa::SfaIndex::build`
+    /// reads instead of scanning the headers of the whole file. The
+    /// other readers skip it. Archives with a table of contents use
+    /// version 9 of the file format (`SFA9;`) with the table of contents
+    /// turned on in the byte of flags after it, which older versions of
+    /// this crate can not read. Defaults to `false`.
+    ///
+    /// See `sfa::SfaIndex::build` for an example.
+    pub table_of_contents: bool,
+}
+
+impl EncodeOptions {
+    /// The layout of the file format needed for these options. All new
+    /// archives end with the number of entries, so they are written in
+    /// version 9 of the file format with only the other parts that the
+    /// options need.
+    pub(crate) fn layout(&self) -> Layout {
+        let mut flags = Layout::COUNT;
+        if self.checksums {
+            flags |= Layout::CHECKSUMS;
+        }
+        if !self.metadata.is_empty() {
+            flags |= Layout::METADATA;
+        }
+        if self.table_of_contents {
+            flags |= Layout::TABLE_OF_CONTENTS;
+        }
+
+        Layout::with_flags(flags)
+    }
+}
+
+/// Options for decoding sfa files, such as limits for files from
+/// untrusted sources like uploads to a service. `DecodeOptions::default()`
+/// has generous limits that only stop files which are corrupt or made
+/// to exhaust memory, and returns the images as they are. The functions
+/// without options such as `sfa::decode_from_reader` have the same
+/// limits. `DecodeOptions::unlimited()` has no limits at all.
+///
+/// The limits are checked while reading the headers of the entries,
+/// before the data of the entry that exceeds them is read.
+///
+/// # Examples
+///
+/// ```
+/// use image::DynamicImage;
+/// use sfa::{decode_from_reader_with_options, encode_images_to_vec, DecodeOptions, SfaError};
+///
+/// let names = ["frame_1.png", "frame_2.png", "frame_3.png"];
+/// let archive =
+///     encode_images_to_vec(names.iter().map(|name| (name, DynamicImage::new_rgba8(4, 4))))
+///         .unwrap();
+///
+/// let options = DecodeOptions {
+///     max_entries: Some(2),
+///     ..Default::default()
+/// };
+/// let result = decode_from_reader_with_options(&mut archive.as_slice(), &options);
+/// assert!(matches!(result, Err(SfaError::TooManyEntries { name, limit: 2 }) if name == "frame_3.png"));
+///
+/// let options = DecodeOptions {
+///     max_entry_bytes: Some(16),
+///     ..Default::default()
+/// };
+/// let result = decode_from_reader_with_options(&mut archive.as_slice(), &options);
+/// assert!(matches!(result, Err(SfaError::EntryTooLarge { name, .. }) if name == "frame_1.png"));
+///
+/// let options = DecodeOptions {
+///     max_total_bytes: Some(1),
+///     ..Default::default()
+/// };
+/// let result = decode_from_reader_with_options(&mut archive.as_slice(), &options);
+/// assert!(matches!(result, Err(SfaError::TotalSizeTooLarge { name, .. }) if name == "frame_1.png"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Largest number of entries a file may have. Files with more fail
+    /// with `SfaError::TooManyEntries`. Defaults to `Some(1_000_000)`,
+    /// `None` is no limit.
+    pub max_entries: Option<usize>,
+    /// Largest size of the stored data of an entry in bytes. Larger
+    /// entries fail with `SfaError::EntryTooLarge`. Defaults to 1 GiB,
+    /// `None` is no limit.
+    pub max_entry_bytes: Option<usize>,
+    /// Largest size of the stored data of all the entries together in
+    /// bytes. Files with more fail with `SfaError::TotalSizeTooLarge`
+    /// at the first entry that goes over it. Defaults to 16 GiB, `None`
+    /// is no limit.
+    pub max_total_bytes: Option<u64>,
+    /// Largest width and height of the returned images. Larger images are
+    /// scaled down with `DynamicImage::thumbnail` to fit, keeping their
+    /// aspect ratio, which is useful for previews. The images are still
+    /// decoded in full first because PNG data can not be decoded at a
+    /// lower resolution, only the returned images take less memory.
+    /// Defaults to `None`, which keeps the images as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::DynamicImage;
+    /// use sfa::{decode_from_reader_with_options, encode_images_to_vec, DecodeOptions};
+    ///
+    /// let frames = vec![
+    ///     (String::from("wide.png"), DynamicImage::new_rgba8(512, 256)),
+    ///     (String::from("small.png"), DynamicImage::new_rgba8(64, 32)),
+    /// ];
+    /// let archive = encode_images_to_vec(frames).unwrap();
+    ///
+    /// let options = DecodeOptions {
+    ///     max_dimension: Some(128),
+    ///     ..Default::default()
+    /// };
+    /// let previews = decode_from_reader_with_options(&mut archive.as_slice(), &options).unwrap();
+    /// assert_eq!(previews["wide.png"].to_rgba8().dimensions(), (128, 64));
+    /// assert_eq!(previews["small.png"].to_rgba8().dimensions(), (64, 32));
+    /// ```
+    pub max_dimension: Option<u32>,
+    /// What happens when an entry can not be decoded. Defaults to
+    /// `ErrorPolicy::Fail`. With `ErrorPolicy::Skip` the entry is left
+    /// out of the decoded entries instead, and the error is kept as a
+    /// `sfa::DecodeWarning` that `sfa::decode_from_reader_with_warnings`
+    /// returns.
+    pub on_error: ErrorPolicy,
+    /// Which of the entries with the same name is kept in the returned
+    /// `HashMap`. Defaults to `DuplicatePolicy::Error`. Functions that
+    /// return the entries in order, like `sfa::decode_ordered`, take no
+    /// options and always return all of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, ImageOutputFormat};
+    /// use sfa::{decode_from_reader_ordered, decode_from_reader_with_options, decode_into};
+    /// use sfa::{DecodeOptions, DuplicatePolicy, SfaError};
+    ///
+    /// // Two entries named a.png, as written by a packer with a bug
+    /// let mut archive = b"SFA;".to_vec();
+    /// let mut offsets = vec![];
+    /// for size in [2, 4] {
+    ///     let mut png = vec![];
+    ///     DynamicImage::new_rgba8(size, size)
+    ///         .write_to(&mut png, ImageOutputFormat::Png)
+    ///         .unwrap();
+    ///     archive.extend_from_slice(format!("a.png:{}:", png.len()).as_bytes());
+    ///     offsets.push(archive.len() as u64);
+    ///     archive.extend_from_slice(&png);
+    /// }
+    ///
+    /// let result = decode_from_reader_with_options(&mut archive.as_slice(), &Default::default());
+    /// match result {
+    ///     Err(SfaError::DuplicateEntry { name, first, second }) => {
+    ///         assert_eq!(name, "a.png");
+    ///         assert_eq!([first, second], offsets[..]);
+    ///     }
+    ///     _ => panic!("expected the duplicate to be found"),
+    /// }
+    ///
+    /// for (duplicates, size) in [(DuplicatePolicy::KeepFirst, 2), (DuplicatePolicy::KeepLast, 4)] {
+    ///     let options = DecodeOptions {
+    ///         duplicates,
+    ///         ..Default::default()
+    ///     };
+    ///     let decoded = decode_from_reader_with_options(&mut archive.as_slice(), &options).unwrap();
+    ///     assert_eq!(decoded.len(), 1);
+    ///     assert_eq!(decoded["a.png"].to_rgba8().dimensions(), (size, size));
+    /// }
+    ///
+    /// // Both are returned in order
+    /// let ordered = decode_from_reader_ordered(&mut archive.as_slice()).unwrap();
+    /// assert_eq!(ordered.len(), 2);
+    ///
+    /// // An alias after them refers to the last one, even if it is skipped
+    /// let mut archive = b"SFA2;".to_vec();
+    /// for size in [2, 4] {
+    ///     let mut png = vec![];
+    ///     DynamicImage::new_rgba8(size, size)
+    ///         .write_to(&mut png, ImageOutputFormat::Png)
+    ///         .unwrap();
+    ///     archive.extend_from_slice(format!("a.png:{}:\x00", png.len()).as_bytes());
+    ///     archive.extend_from_slice(&png);
+    /// }
+    /// archive.extend_from_slice(b"b.png:5:\x80a.png");
+    ///
+    /// let options = DecodeOptions {
+    ///     duplicates: DuplicatePolicy::KeepFirst,
+    ///     ..Default::default()
+    /// };
+    /// let decoded = decode_from_reader_with_options(&mut archive.as_slice(), &options).unwrap();
+    /// assert_eq!(decoded["b.png"].to_rgba8().dimensions(), (4, 4));
+    /// let in_order: Vec<(String, DynamicImage)> = decode_into(&mut archive.as_slice()).unwrap();
+    /// assert_eq!(in_order[2].1.to_rgba8().dimensions(), (4, 4));
+    /// ```
+    pub duplicates: DuplicatePolicy,
+    /// Check that nothing follows the last entry of files of the older
+    /// versions of the file format, which end right after the data of the
+    /// last entry instead of with the count of entries. Bytes after it
+    /// that are not a whole header fail with `SfaError::TrailingData`,
+    /// like anything after the count of the files that have one, instead
+    /// of the error of the header they are read as. Bytes that happen to
+    /// be whole entries are still read as more entries, which only the
+    /// count can tell. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, ImageOutputFormat};
+    /// use sfa::{decode_from_reader_with_options, DecodeOptions, SfaError};
+    ///
+    /// let mut png = vec![];
+    /// DynamicImage::new_rgba8(2, 2)
+    ///     .write_to(&mut png, ImageOutputFormat::Png)
+    ///     .unwrap();
+    /// let mut archive = format!("SFA;a.png:{}:", png.len()).into_bytes();
+    /// archive.extend_from_slice(&png);
+    /// let options = DecodeOptions {
+    ///     strict: true,
+    ///     ..Default::default()
+    /// };
+    /// assert!(decode_from_reader_with_options(&mut archive.as_slice(), &options).is_ok());
+    ///
+    /// archive.extend_from_slice(b"junk");
+    /// let result = decode_from_reader_with_options(&mut archive.as_slice(), &options);
+    /// assert!(matches!(
+    ///     result,
+    ///     Err(SfaError::TrailingData { count: 4, preview }) if preview == b"junk"
+    /// ));
+    ///
+    /// // Without the option the junk is read as the start of a header
+    /// let result = decode_from_reader_with_options(&mut archive.as_slice(), &Default::default());
+    /// assert!(matches!(result, Err(SfaError::TruncatedHeader(4))));
+    /// ```
+    pub strict: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> DecodeOptions {
+        DecodeOptions {
+            max_entries: Some(1_000_000),
+            max_entry_bytes: Some(1 << 30),
+            max_total_bytes: Some(16 << 30),
+            max_dimension: None,
+            on_error: ErrorPolicy::Fail,
+            duplicates: DuplicatePolicy::Error,
+            strict: false,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// Options without any limits, for trusted files that are larger
+    /// than the limits of `DecodeOptions::default()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sfa::DecodeOptions;
+    ///
+    /// let options = DecodeOptions::unlimited();
+    /// assert_eq!(options.max_entries, None);
+    /// assert_eq!(options.max_entry_bytes, None);
+    /// assert_eq!(options.max_total_bytes, None);
+    /// ```
+    pub fn unlimited() -> DecodeOptions {
+        DecodeOptions {
+            max_entries: None,
+            max_entry_bytes: None,
+            max_total_bytes: None,
+            max_dimension: None,
+            on_error: ErrorPolicy::Fail,
+            duplicates: DuplicatePolicy::Error,
+            strict: false,
+        }
+    }
+
+    /// Check the entry with the given number, counting from 1, and size
+    /// against the limits. `total` is the size of the data of all the
+    /// entries up to and including this one.
+    pub(crate) fn check(
+        &self,
+        number: usize,
+        total: u64,
+        name: &str,
+        size: usize,
+    ) -> Result<(), SfaError> {
+        if let Some(limit) = self.max_entries {
+            if number > limit {
+                return Err(SfaError::TooManyEntries {
+                    name: name.to_owned(),
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.max_entry_bytes {
+            if size > limit {
+                return Err(SfaError::EntryTooLarge {
+                    name: name.to_owned(),
+                    size,
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.max_total_bytes {
+            if total > limit {
+                return Err(SfaError::TotalSizeTooLarge {
+                    name: name.to_owned(),
+                    total,
+                    limit,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scale the image down to `max_dimension` if it is larger.
+    pub(crate) fn shrink(&self, im: DynamicImage) -> DynamicImage {
+        match self.max_dimension {
+            Some(max) if im.width() > max || im.height() > max => im.thumbnail(max, max),
+            _ => im,
+        }
+    }
+}
+
+/// What `DecodeOptions::on_error` does with entries that can not be
+/// decoded. Only the data of the entries can be skipped, errors in the
+/// headers are always returned because the position of the next entry
+/// can not be known after them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Return the error of the entry, which ends decoding.
+    #[default]
+    Fail,
+    /// Skip the entry and keep decoding the entries after it. This
+    /// covers data that is not a valid image, does not match its
+    /// checksum, and aliases and delta entries that do not fit the
+    /// entries before them, which includes the delta entries after a
+    /// skipped entry.
+    Skip,
+}
+
+/// What `DecodeOptions::duplicates` does with entries that have the
+/// same name as an entry before them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Fail with `SfaError::DuplicateEntry`, which has the offsets of
+    /// both entries.
+    #[default]
+    Error,
+    /// Keep the first entry with the name. The data of the entries
+    /// after it is skipped without being decoded.
+    KeepFirst,
+    /// Keep the last entry with the name, like appending to the archive
+    /// would replace it.
+    KeepLast,
+}
+
+/// Format in which the images are stored in a sfa file. All formats
+/// decode to the same `DynamicImage` types, so which format an
+/// archive was written with does not matter when decoding it.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, Rgb, RgbImage};
+/// use sfa::{decode_from_reader, encode_to_writer_with_options, EncodeOptions, StorageFormat};
+///
+/// // Noisy gradients resemble photos, which compress badly as PNG
+/// let mut seed: u32 = 7;
+/// let photo = RgbImage::from_fn(128, 128, |x, y| {
+///     seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+///     let noise = (seed >> 27) as u8;
+///     Rgb([x as u8 + noise, y as u8 + noise, (x + y) as u8 / 2 + noise])
+/// });
+/// let frame = std::env::temp_dir().join("sfa_storage_format.png");
+/// DynamicImage::ImageRgb8(photo).save(&frame).unwrap();
+///
+/// let mut sizes = vec![];
+/// for format in [StorageFormat::Png, StorageFormat::Jpeg { quality: 80 }] {
+///     let options = EncodeOptions {
+///         format,
+///         ..Default::default()
+///     };
+///     let mut archive = vec![];
+///     encode_to_writer_with_options(&[&frame], &mut archive, &options).unwrap();
+///
+///     let decoded = decode_from_reader(&mut archive.as_slice()).unwrap();
+///     assert_eq!(decoded["sfa_storage_format.png"].to_rgb8().dimensions(), (128, 128));
+///     sizes.push(archive.len());
+/// }
+///
+/// assert!(sizes[1] < sizes[0]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum StorageFormat {
+    /// Lossless PNG, configured by the other fields of `EncodeOptions`.
+    #[default]
+    Png,
+    /// Lossy JPEG with the given quality from 1 to 100, values outside
+    /// of that range are clamped. JPEG has no alpha channel, so it is
+    /// discarded. The PNG specific options have no effect.
+    Jpeg {
+        /// Higher quality gives larger but more accurate images.
+        quality: u8,
+    },
+    /// Lossy WebP with the given quality from 0 to 100, values above
+    /// 100 are clamped. The alpha channel is discarded because the
+    /// `image` crate can not decode WebP data with alpha. The PNG
+    /// specific options have no effect. Only available with the
+    /// `webp` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::DynamicImage;
+    /// use sfa::{decode_from_reader, encode_to_writer_with_options, EncodeOptions, StorageFormat};
+    ///
+    /// let frame = std::env::temp_dir().join("sfa_storage_format_webp.png");
+    /// DynamicImage::new_rgb8(32, 32).save(&frame).unwrap();
+    ///
+    /// let options = EncodeOptions {
+    ///     format: StorageFormat::WebP { quality: 75 },
+    ///     ..Default::default()
+    /// };
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&[&frame], &mut archive, &options).unwrap();
+    ///
+    /// let decoded = decode_from_reader(&mut archive.as_slice()).unwrap();
+    /// assert_eq!(decoded["sfa_storage_format_webp.png"].to_rgb8().dimensions(), (32, 32));
+    /// ```
+    #[cfg(feature = "webp")]
+    WebP {
+        /// Higher quality gives larger but more accurate images.
+        quality: u8,
+    },
+    /// Lossless WebP, which is usually noticeably smaller than PNG for
+    /// sprite art. Images with more than 8 bits per channel are stored
+    /// with 8 bits per channel. The PNG specific options have no effect.
+    /// Only available with the `webp` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, Rgba, RgbaImage};
+    /// use sfa::{decode_from_reader, encode_to_writer_with_options, EncodeOptions, StorageFormat};
+    ///
+    /// let sprite = RgbaImage::from_fn(24, 24, |x, y| Rgba([x as u8 * 10, y as u8 * 10, 128, (x + y) as u8 * 5]));
+    /// let frame = std::env::temp_dir().join("sfa_storage_format_webp_lossless.png");
+    /// DynamicImage::ImageRgba8(sprite.clone()).save(&frame).unwrap();
+    ///
+    /// let mut decoded = vec![];
+    /// for format in [StorageFormat::Png, StorageFormat::WebPLossless] {
+    ///     let options = EncodeOptions {
+    ///         format,
+    ///         ..Default::default()
+    ///     };
+    ///     let mut archive = vec![];
+    ///     encode_to_writer_with_options(&[&frame], &mut archive, &options).unwrap();
+    ///     let mut frames = decode_from_reader(&mut archive.as_slice()).unwrap();
+    ///     decoded.push(frames.remove("sfa_storage_format_webp_lossless.png").unwrap().to_rgba8());
+    /// }
+    ///
+    /// assert_eq!(decoded[0], sprite);
+    /// assert_eq!(decoded[1], decoded[0]);
+    /// ```
+    #[cfg(feature = "webp")]
+    WebPLossless,
+    /// Lossless QOI, which encodes and decodes many times faster than
+    /// PNG at a similar size for sprite art. Images with more than 8 bits
+    /// per channel are stored with 8 bits per channel. The PNG specific
+    /// options have no effect. Only available with the `qoi` feature,
+    /// which is also needed to decode such entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::{DynamicImage, Rgba, RgbaImage};
+    /// use sfa::{decode_from_reader_ordered, encode_to_writer_with_options, EncodeOptions, StorageFormat};
+    ///
+    /// let dir = std::env::temp_dir();
+    /// let sprites: Vec<RgbaImage> = (0..50)
+    ///     .map(|i| RgbaImage::from_fn(32, 32, |x, y| Rgba([x as u8 * 8, y as u8 * 8, i * 5, 255])))
+    ///     .collect();
+    /// let frames: Vec<_> = sprites
+    ///     .iter()
+    ///     .enumerate()
+    ///     .map(|(i, sprite)| {
+    ///         let path = dir.join(format!("sfa_storage_format_qoi_{:02}.png", i));
+    ///         DynamicImage::ImageRgba8(sprite.clone()).save(&path).unwrap();
+    ///         path
+    ///     })
+    ///     .collect();
+    ///
+    /// let options = EncodeOptions {
+    ///     format: StorageFormat::Qoi,
+    ///     ..Default::default()
+    /// };
+    /// let mut archive = vec![];
+    /// encode_to_writer_with_options(&frames, &mut archive, &options).unwrap();
+    ///
+    /// let decoded = decode_from_reader_ordered(&mut archive.as_slice()).unwrap();
+    /// assert_eq!(decoded.len(), 50);
+    /// for ((_, frame), sprite) in decoded.iter().zip(&sprites) {
+    ///     assert_eq!(&frame.to_rgba8(), sprite);
+    /// }
+    /// ```
+    #[cfg(feature = "qoi")]
+    Qoi,
+}
+
+/// Compression level of the PNG data stored in a sfa file.
+/// Higher compression produces smaller files but takes more time
+/// to encode. Decoding is not affected by the compression level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionLevel {
+    /// Fast encoding with larger output.
+    Fast,
+    /// The default compression of the `image` crate.
+    #[default]
+    Default,
+    /// Slow encoding with the smallest output.
+    Best,
+}
+
+impl From<CompressionLevel> for CompressionType {
+    fn from(compression: CompressionLevel) -> CompressionType {
+        match compression {
+            CompressionLevel::Fast => CompressionType::Fast,
+            CompressionLevel::Default => CompressionType::Default,
+            CompressionLevel::Best => CompressionType::Best,
+        }
+    }
+}
+
+/// Filter applied to each row of the PNG data before it is compressed.
+/// The right filter depends on the images, for example pixel art
+/// usually compresses better with `PngFilter::None` or `PngFilter::Up`.
+/// The stored data is always valid PNG so decoding is not affected
+/// by the filter.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, Rgba, RgbaImage};
+/// use sfa::{decode_from_reader, encode_to_writer_with_options, EncodeOptions, PngFilter};
+///
+/// let sprite = RgbaImage::from_fn(64, 64, |x, y| Rgba([(x / 8) as u8 * 32, (y / 8) as u8 * 32, 0, 255]));
+/// let frame = std::env::temp_dir().join("sfa_png_filter.png");
+/// DynamicImage::ImageRgba8(sprite).save(&frame).unwrap();
+///
+/// let mut archives = vec![];
+/// for filter in [PngFilter::None, PngFilter::Paeth, PngFilter::Adaptive] {
+///     let options = EncodeOptions {
+///         filter,
+///         ..Default::default()
+///     };
+///     let mut archive = vec![];
+///     encode_to_writer_with_options(&[&frame], &mut archive, &options).unwrap();
+///     archives.push(archive);
+/// }
+///
+/// assert_ne!(archives[0].len(), archives[1].len());
+///
+/// let none = decode_from_reader(&mut archives[0].as_slice()).unwrap();
+/// let paeth = decode_from_reader(&mut archives[1].as_slice()).unwrap();
+/// let adaptive = decode_from_reader(&mut archives[2].as_slice()).unwrap();
+/// let name = "sfa_png_filter.png";
+/// assert_eq!(none[name].to_rgba8(), paeth[name].to_rgba8());
+/// assert_eq!(none[name].to_rgba8(), adaptive[name].to_rgba8());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngFilter {
+    /// No filtering.
+    None,
+    /// Difference to the pixel on the left. This is the default of the
+    /// `image` crate.
+    #[default]
+    Sub,
+    /// Difference to the pixel above.
+    Up,
+    /// Difference to the average of the pixels on the left and above.
+    Average,
+    /// Difference to the Paeth predictor of the neighbouring pixels.
+    Paeth,
+    /// Choose the filter for every row separately.
+    Adaptive,
+}
+
+impl PngFilter {
+    /// The filter of the `image` crate, which has none that is chosen
+    /// for every row separately. `PngFilter::Adaptive` is written by
+    /// `crate::png` instead.
+    pub(crate) fn image_filter(self) -> Option<FilterType> {
+        match self {
+            PngFilter::None => Some(FilterType::NoFilter),
+            PngFilter::Sub => Some(FilterType::Sub),
+            PngFilter::Up => Some(FilterType::Up),
+            PngFilter::Average => Some(FilterType::Avg),
+            PngFilter::Paeth => Some(FilterType::Paeth),
+            PngFilter::Adaptive => None,
+        }
+    }
+}
+
+/// Options for storing images as indexed color PNG with
+/// `EncodeOptions::quantize`.
+///
+/// # Examples
+///
+/// ```
+/// use sfa::{EncodeOptions, QuantizeOptions};
+///
+/// // Reduce every image to 32 colors, even if it has more
+/// let options = EncodeOptions {
+///     quantize: Some(QuantizeOptions {
+///         max_colors: 32,
+///         dithering: true,
+///         force: true,
+///     }),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuantizeOptions {
+    /// Largest number of colors in the palette, from 2 to 256. Values
+    /// outside of that range are clamped. Defaults to 256.
+    pub max_colors: u16,
+    /// Spread the difference between the color of a pixel and the color
+    /// it is reduced to over the neighbouring pixels, which looks better
+    /// for gradients but compresses worse. Only used when the colors are
+    /// reduced. Defaults to `false`.
+    pub dithering: bool,
+    /// Reduce the colors of images that have more than `max_colors`
+    /// colors instead of storing them as usual. This loses information.
+    /// Defaults to `false`.
+    pub force: bool,
+}
+
+impl Default for QuantizeOptions {
+    fn default() -> QuantizeOptions {
+        QuantizeOptions {
+            max_colors: 256,
+            dithering: false,
+            force: false,
+        }
+    }
+}
+
+/// New dimensions of the images with `EncodeOptions::resize`.
+/// Dimensions are always at least 1 pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeSpec {
+    /// Resize to exactly this width and height, which changes the
+    /// aspect ratio if it is not the same as the image's.
+    Exact(u32, u32),
+    /// Multiply the width and height by this factor, rounded to the
+    /// nearest pixel. `0.5` halves the dimensions.
+    Scale(f32),
+    /// Shrink the image so that it fits into this width and height while
+    /// keeping its aspect ratio. Images that already fit are unchanged.
+    FitWithin(u32, u32),
+}
+
+impl ResizeSpec {
+    /// The dimensions that an image of the given dimensions is resized to.
+    pub(crate) fn dimensions(self, (width, height): (u32, u32)) -> (u32, u32) {
+        let scale = |size: u32, factor: f32| ((size as f32 * factor).round() as u32).max(1);
+        match self {
+            ResizeSpec::Exact(width, height) => (width.max(1), height.max(1)),
+            ResizeSpec::Scale(factor) => (scale(width, factor), scale(height, factor)),
+            ResizeSpec::FitWithin(max_width, max_height) => {
+                if width <= max_width && height <= max_height {
+                    return (width, height);
+                }
+                let factor = f32::min(
+                    max_width as f32 / width as f32,
+                    max_height as f32 / height as f32,
+                );
+                (
+                    scale(width, factor).min(max_width.max(1)),
+                    scale(height, factor).min(max_height.max(1)),
+                )
+            }
+        }
+    }
+}
+
+/// Filter used to sample the images when they are resized with
+/// `EncodeOptions::resize`, from the fastest to the best looking.
+/// Pixel art usually looks best with `ResizeFilter::Nearest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResizeFilter {
+    /// Nearest neighbour, which keeps hard edges.
+    Nearest,
+    /// Linear filter.
+    Triangle,
+    /// Cubic filter.
+    CatmullRom,
+    /// Gaussian filter.
+    Gaussian,
+    /// Lanczos filter with a window of 3.
+    #[default]
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for imageops::FilterType {
+    fn from(filter: ResizeFilter) -> imageops::FilterType {
+        match filter {
+            ResizeFilter::Nearest => imageops::FilterType::Nearest,
+            ResizeFilter::Triangle => imageops::FilterType::Triangle,
+            ResizeFilter::CatmullRom => imageops::FilterType::CatmullRom,
+            ResizeFilter::Gaussian => imageops::FilterType::Gaussian,
+            ResizeFilter::Lanczos3 => imageops::FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Which metadata of the input files is kept by
+/// `EncodeOptions::metadata_policy`. PNG files keep their chunks with
+/// the metadata, the ICC profile of JPEG files is stored as an `iCCP`
+/// chunk. Files that are copied as they are by `reuse_png_bytes` or
+/// `keep_original_format` always keep all of their metadata, and delta
+/// and trimmed entries never have any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum MetadataPolicy {
+    /// Keep no metadata for the smallest files.
+    #[default]
+    Strip,
+    /// Keep the ICC color profile (`iCCP`) for color managed art.
+    PreserveIcc,
+    /// Keep the ICC color profile, the text (`tEXt`, `zTXt` and `iTXt`)
+    /// and the physical pixel dimensions (`pHYs`).
+    PreserveAll,
+}
+
+/// Color type that all the images are converted to with
+/// `EncodeOptions::force_color`. Each one matches the
+/// `DynamicImage` variant of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ColorTarget {
+    /// 8 bit grayscale.
+    Luma8,
+    /// 8 bit grayscale with alpha.
+    LumaA8,
+    /// 8 bit RGB.
+    Rgb8,
+    /// 8 bit RGB with alpha.
+    Rgba8,
+}
+
+impl ColorTarget {
+    /// The `image` color type of this target.
+    pub(crate) fn color_type(self) -> ColorType {
+        match self {
+            ColorTarget::Luma8 => ColorType::L8,
+            ColorTarget::LumaA8 => ColorType::La8,
+            ColorTarget::Rgb8 => ColorType::Rgb8,
+            ColorTarget::Rgba8 => ColorType::Rgba8,
+        }
+    }
+
+    /// `Luma8` for 8 bit color images whose pixels are all gray and
+    /// opaque, `LumaA8` if some of them are transparent. `None` is
+    /// returned for any other image.
+    pub(crate) fn grayscale(im: &DynamicImage) -> Option<ColorTarget> {
+        let pixels = match im {
+            DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_)
+            | DynamicImage::ImageBgr8(_)
+            | DynamicImage::ImageBgra8(_) => im.to_rgba8(),
+            _ => return None,
+        };
+
+        let mut opaque = true;
+        for pixel in pixels.pixels() {
+            let [red, green, blue, alpha] = pixel.0;
+            if red != green || green != blue {
+                return None;
+            }
+            opaque &= alpha == u8::MAX;
+        }
+
+        Some(if opaque {
+            ColorTarget::Luma8
+        } else {
+            ColorTarget::LumaA8
+        })
+    }
+
+    /// Convert the image to this color type.
+    pub(crate) fn convert(self, im: &DynamicImage) -> DynamicImage {
+        match self {
+            ColorTarget::Luma8 => DynamicImage::ImageLuma8(im.to_luma8()),
+            ColorTarget::LumaA8 => DynamicImage::ImageLumaA8(im.to_luma_alpha8()),
+            ColorTarget::Rgb8 => DynamicImage::ImageRgb8(im.to_rgb8()),
+            ColorTarget::Rgba8 => DynamicImage::ImageRgba8(im.to_rgba8()),
+        }
+    }
+
+    /// Convert an image found by `ColorTarget::grayscale` to this
+    /// target by copying the red channel. `convert` would apply the
+    /// luma weights instead, which change the values of gray pixels.
+    pub(crate) fn convert_gray(self, im: &DynamicImage) -> DynamicImage {
+        let pixels = im.to_rgba8();
+        let (width, height) = pixels.dimensions();
+        match self {
+            ColorTarget::LumaA8 => {
+                DynamicImage::ImageLumaA8(ImageBuffer::from_fn(width, height, |x, y| {
+                    let [gray, _, _, alpha] = pixels.get_pixel(x, y).0;
+                    LumaA([gray, alpha])
+                }))
+            }
+            _ => DynamicImage::ImageLuma8(ImageBuffer::from_fn(width, height, |x, y| {
+                Luma([pixels.get_pixel(x, y).0[0]])
+            })),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Turning input images upright by their EXIF orientation.
+
+use image::DynamicImage;
+use std::io::Cursor;
+
+/// Read the EXIF orientation from the data of an image file, which is
+/// a value from 1 to 8. Images without one are upright, which is 1.
+pub(crate) fn read(data: &[u8]) -> u32 {
+    let exif = match exif::Reader::new().read_from_container(&mut Cursor::new(data)) {
+        Ok(exif) => exif,
+        Err(_) => return 1,
+    };
+
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .filter(|orientation| (1..=8).contains(orientation))
+        .unwrap_or(1)
+}
+
+/// Rotate and flip the image with the given EXIF orientation so that
+/// it is upright.
+pub(crate) fn apply(im: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => im.fliph(),
+        3 => im.rotate180(),
+        4 => im.flipv(),
+        5 => im.rotate90().fliph(),
+        6 => im.rotate90(),
+        7 => im.rotate270().fliph(),
+        8 => im.rotate270(),
+        _ => im,
+    }
+}
@@ -0,0 +1,133 @@
+//! Storing images with few colors as indexed color PNG data.
+
+use crate::png::{self, RawImage};
+use crate::{EncodeOptions, QuantizeOptions};
+use color_quant::NeuQuant;
+use image::{DynamicImage, RgbaImage};
+use std::collections::HashMap;
+
+/// Encode the image as an indexed color PNG. `None` is returned if
+/// the image is not a color image with 8 bits per channel, or if it
+/// has more colors than allowed and quantizing is not forced.
+pub(crate) fn encode_indexed(
+    im: &DynamicImage,
+    quantize: &QuantizeOptions,
+    options: &EncodeOptions,
+) -> Option<Vec<u8>> {
+    // Images with an alpha channel keep it so that they decode to the
+    // same color type, even if all of their pixels are opaque
+    let has_alpha = match im {
+        DynamicImage::ImageRgb8(_) | DynamicImage::ImageBgr8(_) => false,
+        DynamicImage::ImageRgba8(_) | DynamicImage::ImageBgra8(_) => true,
+        _ => return None,
+    };
+
+    let pixels = im.to_rgba8();
+    let max_colors = quantize.max_colors.clamp(2, 256) as usize;
+    let (palette, indices) = match exact_palette(&pixels, max_colors) {
+        Some(exact) => exact,
+        None if quantize.force => quantized_palette(&pixels, max_colors, quantize.dithering),
+        None => return None,
+    };
+
+    let bit_depth = match palette.len() {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    };
+    let raw = RawImage {
+        width: pixels.width(),
+        height: pixels.height(),
+        color_type: 3,
+        bit_depth,
+        channels: 1,
+        samples: indices,
+        palette,
+        palette_alpha: has_alpha,
+    };
+
+    Some(png::encode_raw(&raw, options, options.interlaced))
+}
+
+/// The palette and the indices of the pixels if the image has at most
+/// `max_colors` different colors.
+fn exact_palette(pixels: &RgbaImage, max_colors: usize) -> Option<(Vec<[u8; 4]>, Vec<u8>)> {
+    let mut palette: Vec<[u8; 4]> = vec![];
+    let mut lookup: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut indices: Vec<u8> = Vec::with_capacity(pixels.as_raw().len() / 4);
+
+    for pixel in pixels.pixels() {
+        let index = match lookup.get(&pixel.0) {
+            Some(&index) => index,
+            None if palette.len() == max_colors => return None,
+            None => {
+                let index = palette.len() as u8;
+                palette.push(pixel.0);
+                lookup.insert(pixel.0, index);
+                index
+            }
+        };
+        indices.push(index);
+    }
+
+    Some((palette, indices))
+}
+
+/// Reduce the colors of the image to a palette of `max_colors` colors,
+/// optionally spreading the error of every pixel to its neighbours with
+/// Floyd-Steinberg dithering.
+fn quantized_palette(
+    pixels: &RgbaImage,
+    max_colors: usize,
+    dithering: bool,
+) -> (Vec<[u8; 4]>, Vec<u8>) {
+    let quantizer = NeuQuant::new(10, max_colors, pixels.as_raw());
+    let palette: Vec<[u8; 4]> = quantizer
+        .color_map_rgba()
+        .chunks(4)
+        .map(|c| [c[0], c[1], c[2], c[3]])
+        .collect();
+
+    if !dithering {
+        let indices = pixels
+            .pixels()
+            .map(|pixel| quantizer.index_of(&pixel.0) as u8)
+            .collect();
+        return (palette, indices);
+    }
+
+    let (width, height) = (pixels.width() as usize, pixels.height() as usize);
+    let mut values: Vec<[i16; 4]> = pixels
+        .pixels()
+        .map(|pixel| pixel.0.map(i16::from))
+        .collect();
+    let mut indices: Vec<u8> = Vec::with_capacity(values.len());
+
+    for y in 0..height {
+        for x in 0..width {
+            let value = values[y * width + x].map(|v| v.clamp(0, 255) as u8);
+            let index = quantizer.index_of(&value);
+            indices.push(index as u8);
+
+            let chosen = palette[index];
+            let error: [i16; 4] = std::array::from_fn(|c| value[c] as i16 - chosen[c] as i16);
+            let mut spread = |dx: isize, dy: usize, weight: i16| {
+                let nx = x as isize + dx;
+                if nx < 0 || nx as usize >= width || y + dy >= height {
+                    return;
+                }
+                let neighbour = &mut values[(y + dy) * width + nx as usize];
+                for c in 0..4 {
+                    neighbour[c] += error[c] * weight / 16;
+                }
+            };
+            spread(1, 0, 7);
+            spread(-1, 1, 3);
+            spread(0, 1, 5);
+            spread(1, 1, 1);
+        }
+    }
+
+    (palette, indices)
+}
@@ -0,0 +1,262 @@
+//! Writing of PNG data that the `image` crate can not write, such as
+//! interlaced (Adam7) and indexed color images, or rows that are each
+//! filtered with the filter that suits them best.
+
+use crate::{CompressionLevel, EncodeOptions, PngFilter, SfaError, PNG_SIGNATURE};
+use deflate::Compression;
+use image::{DynamicImage, GenericImageView};
+
+/// Starting column, starting row, column step and row step of the seven passes.
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// The single pass of an image that is not interlaced.
+const NO_PASSES: [(u32, u32, u32, u32); 1] = [(0, 0, 1, 1)];
+
+/// Raw samples of an image along with how PNG stores them.
+pub(crate) struct RawImage {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) color_type: u8,
+    pub(crate) bit_depth: u8,
+    pub(crate) channels: usize,
+    /// The samples in the byte order used by PNG. Bit depths below
+    /// 8 still use one byte per sample, they are packed when written.
+    pub(crate) samples: Vec<u8>,
+    /// RGBA entries of the palette of indexed color images.
+    pub(crate) palette: Vec<[u8; 4]>,
+    /// Whether to store the alpha of the palette entries.
+    pub(crate) palette_alpha: bool,
+}
+
+/// Encode the image as PNG, Adam7 interlaced or not.
+pub(crate) fn encode_image(
+    im: &DynamicImage,
+    options: &EncodeOptions,
+    interlaced: bool,
+) -> Result<Vec<u8>, SfaError> {
+    let (width, height) = im.dimensions();
+    let (color_type, bit_depth, channels, samples) = png_samples(im);
+    let raw = RawImage {
+        width,
+        height,
+        color_type,
+        bit_depth,
+        channels,
+        samples,
+        palette: vec![],
+        palette_alpha: false,
+    };
+
+    Ok(encode_raw(&raw, options, interlaced))
+}
+
+/// Encode raw samples as PNG, interlaced or not.
+pub(crate) fn encode_raw(raw: &RawImage, options: &EncodeOptions, interlaced: bool) -> Vec<u8> {
+    let RawImage {
+        width,
+        height,
+        bit_depth,
+        ..
+    } = *raw;
+    // Samples below 8 bits are stored one per byte until they are packed
+    let pixel_size = raw.channels * (bit_depth as usize).max(8) / 8;
+    let row_stride = width as usize * pixel_size;
+    let bpp = (raw.channels * bit_depth as usize).div_ceil(8);
+    let passes: &[(u32, u32, u32, u32)] = if interlaced {
+        &ADAM7_PASSES
+    } else {
+        &NO_PASSES
+    };
+
+    let mut filtered: Vec<u8> = vec![];
+    for &(x0, y0, dx, dy) in passes {
+        if width <= x0 || height <= y0 {
+            continue;
+        }
+
+        // The first row of every pass is filtered against a row of zeros
+        let pass_width = (width - x0).div_ceil(dx) as usize;
+        let packed_width = (pass_width * raw.channels * bit_depth as usize).div_ceil(8);
+        let mut previous: Vec<u8> = vec![0; packed_width];
+        let mut row: Vec<u8> = Vec::with_capacity(pass_width * pixel_size);
+
+        for y in (y0..height).step_by(dy as usize) {
+            let line = &raw.samples[y as usize * row_stride..][..row_stride];
+            row.clear();
+            for x in (x0..width).step_by(dx as usize) {
+                row.extend_from_slice(&line[x as usize * pixel_size..][..pixel_size]);
+            }
+            if bit_depth < 8 {
+                row = pack_row(&row, bit_depth);
+            }
+
+            filter_row(options.filter, bpp, &row, &previous, &mut filtered);
+            std::mem::swap(&mut row, &mut previous);
+        }
+    }
+
+    let compression = match options.compression {
+        CompressionLevel::Fast => Compression::Fast,
+        CompressionLevel::Default => Compression::Default,
+        CompressionLevel::Best => Compression::Best,
+    };
+
+    let mut header: Vec<u8> = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth, color type, compression method, filter method and interlace method
+    header.extend_from_slice(&[bit_depth, raw.color_type, 0, 0, interlaced as u8]);
+
+    let mut png: Vec<u8> = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    if !raw.palette.is_empty() {
+        let colors: Vec<u8> = raw
+            .palette
+            .iter()
+            .flat_map(|c| [c[0], c[1], c[2]])
+            .collect();
+        write_chunk(&mut png, b"PLTE", &colors);
+    }
+    if raw.palette_alpha {
+        let alpha: Vec<u8> = raw.palette.iter().map(|c| c[3]).collect();
+        write_chunk(&mut png, b"tRNS", &alpha);
+    }
+    write_chunk(
+        &mut png,
+        b"IDAT",
+        &deflate::deflate_bytes_zlib_conf(&filtered, compression),
+    );
+    write_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+/// Pack samples of less than 8 bits, one per byte, into whole bytes
+/// with the first sample in the highest bits.
+fn pack_row(row: &[u8], bit_depth: u8) -> Vec<u8> {
+    let per_byte = 8 / bit_depth as usize;
+    row.chunks(per_byte)
+        .map(|samples| {
+            samples.iter().enumerate().fold(0, |byte, (i, &sample)| {
+                byte | sample << (8 - bit_depth as usize * (i + 1))
+            })
+        })
+        .collect()
+}
+
+/// Returns the PNG color type, bit depth, number of channels and the
+/// samples of the image in the byte order used by PNG.
+fn png_samples(im: &DynamicImage) -> (u8, u8, usize, Vec<u8>) {
+    match im {
+        DynamicImage::ImageLuma8(buffer) => (0, 8, 1, buffer.as_raw().clone()),
+        DynamicImage::ImageLumaA8(buffer) => (4, 8, 2, buffer.as_raw().clone()),
+        DynamicImage::ImageRgb8(buffer) => (2, 8, 3, buffer.as_raw().clone()),
+        DynamicImage::ImageRgba8(buffer) => (6, 8, 4, buffer.as_raw().clone()),
+        // PNG has no BGR color types
+        DynamicImage::ImageBgr8(_) => (2, 8, 3, im.to_rgb8().into_raw()),
+        DynamicImage::ImageBgra8(_) => (6, 8, 4, im.to_rgba8().into_raw()),
+        DynamicImage::ImageLuma16(buffer) => (0, 16, 1, to_big_endian(buffer.as_raw())),
+        DynamicImage::ImageLumaA16(buffer) => (4, 16, 2, to_big_endian(buffer.as_raw())),
+        DynamicImage::ImageRgb16(buffer) => (2, 16, 3, to_big_endian(buffer.as_raw())),
+        DynamicImage::ImageRgba16(buffer) => (6, 16, 4, to_big_endian(buffer.as_raw())),
+    }
+}
+
+pub(crate) fn to_big_endian(samples: &[u16]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|sample| sample.to_be_bytes())
+        .collect()
+}
+
+/// Filter a row and append it to the output with its filter type byte.
+fn filter_row(filter: PngFilter, bpp: usize, row: &[u8], previous: &[u8], output: &mut Vec<u8>) {
+    if filter != PngFilter::Adaptive {
+        apply_filter(filter, bpp, row, previous, output);
+        return;
+    }
+
+    // Use the filter with the smallest sum of absolute differences,
+    // which is the heuristic recommended by the PNG specification.
+    let mut best: Vec<u8> = vec![];
+    let mut best_score = u64::MAX;
+    let mut candidate: Vec<u8> = Vec::with_capacity(row.len() + 1);
+    for filter in [
+        PngFilter::None,
+        PngFilter::Sub,
+        PngFilter::Up,
+        PngFilter::Average,
+        PngFilter::Paeth,
+    ] {
+        candidate.clear();
+        apply_filter(filter, bpp, row, previous, &mut candidate);
+
+        let score = candidate[1..]
+            .iter()
+            .map(|&b| (b as i8).unsigned_abs() as u64)
+            .sum();
+        if score < best_score {
+            best_score = score;
+            std::mem::swap(&mut best, &mut candidate);
+        }
+    }
+
+    output.extend_from_slice(&best);
+}
+
+fn apply_filter(filter: PngFilter, bpp: usize, row: &[u8], previous: &[u8], output: &mut Vec<u8>) {
+    output.push(match filter {
+        PngFilter::None => 0,
+        PngFilter::Sub => 1,
+        PngFilter::Up => 2,
+        PngFilter::Average => 3,
+        PngFilter::Paeth | PngFilter::Adaptive => 4,
+    });
+
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+
+        let predicted = match filter {
+            PngFilter::None => 0,
+            PngFilter::Sub => left,
+            PngFilter::Up => up,
+            PngFilter::Average => ((left as u16 + up as u16) / 2) as u8,
+            PngFilter::Paeth | PngFilter::Adaptive => paeth(left, up, up_left),
+        };
+        output.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = left as i16 + up as i16 - up_left as i16;
+    let distance_left = (p - left as i16).abs();
+    let distance_up = (p - up as i16).abs();
+    let distance_up_left = (p - up_left as i16).abs();
+
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+pub(crate) fn write_chunk(output: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = output.len();
+    output.extend_from_slice(kind);
+    output.extend_from_slice(data);
+    let crc = crc32fast::hash(&output[start..]);
+    output.extend_from_slice(&crc.to_be_bytes());
+}
@@ -0,0 +1,573 @@
+//! Incremental reading of sfa files.
+
+use crate::delta;
+use crate::format::EntryFormat;
+use crate::layout::Layout;
+use crate::metadata::{self, EntryMetadata};
+use crate::toc::Positioned;
+use crate::{
+    invalid_alias, load_entry, parse_name, read_payload, skip_payload, with_dependents,
+    DecodeOptions, DuplicatePolicy, EntryHeader, ErrorPolicy, Headers, SfaError,
+};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::{BufRead, Read};
+
+/// A reader that decodes the entries of a sfa file one at a
+/// time instead of all at once. The headers are parsed
+/// incrementally as the entries are requested, so if only the
+/// first few entries are needed the rest of the file is never
+/// read or decoded.
+///
+/// The headers of the entries are read one byte at a time, so it is
+/// recommended to wrap unbuffered readers like `std::fs::File` in a
+/// `std::io::BufReader`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs;
+/// use std::io::BufReader;
+/// use sfa::SfaReader;
+///
+/// let file = BufReader::new(fs::File::open("sp.sfa").unwrap());
+/// let mut reader = SfaReader::new(file).unwrap();
+///
+/// // Only the first two entries are decoded
+/// for frame in reader.frames().take(2) {
+///     let (name, image) = frame.unwrap();
+///     println!("{} is {:?}", name, image.color());
+/// }
+/// ```
+pub struct SfaReader<R: Read> {
+    /// The reader, which keeps track of the offsets of the entries for
+    /// the errors.
+    reader: Positioned<R>,
+    layout: Layout,
+    /// The metadata of the file, which is read along with the magic.
+    metadata: HashMap<String, String>,
+    options: DecodeOptions,
+    headers: Headers<Positioned<R>>,
+    /// Size of the data of the entries read so far.
+    total: u64,
+    /// Offset of the data of the last entry that was returned.
+    offset: u64,
+    buffer: Vec<u8>,
+    /// Format, offset and data of the entries that aliases can refer to,
+    /// only kept for files of version 2 and later which can have aliases.
+    targets: AliasTargets<(EntryFormat, u64, Vec<u8>)>,
+    /// Image of the previous decoded entry, only kept for files of
+    /// version 3 and later which can have delta entries.
+    previous: Option<image::DynamicImage>,
+    /// Metadata of the last decoded entry, which the delta entries after
+    /// it share.
+    entry_metadata: EntryMetadata,
+    /// Name, format, offset and data of the entries that were skipped
+    /// since the previous decoded entry, for decoding the delta entries
+    /// after them. Only kept for files of version 3 and later.
+    skipped: Vec<(String, EntryFormat, u64, Vec<u8>)>,
+    /// Errors of the entries skipped with `ErrorPolicy::Skip`.
+    warnings: Vec<DecodeWarning>,
+    finished: bool,
+}
+
+/// An entry that was skipped because it could not be decoded, with
+/// `ErrorPolicy::Skip`.
+#[derive(Debug)]
+pub struct DecodeWarning {
+    /// Name of the entry.
+    pub name: String,
+    /// The error that decoding the entry failed with.
+    pub reason: SfaError,
+}
+
+/// The entries that aliases can refer to by name, which every function
+/// that decodes aliases keeps while it reads the entries in order. An
+/// alias refers to the last entry before it with the stored name that is
+/// neither an alias nor a delta entry, so a later entry with the same
+/// name replaces an earlier one even if it is skipped.
+pub(crate) struct AliasTargets<T> {
+    targets: HashMap<String, T>,
+}
+
+impl<T> AliasTargets<T> {
+    pub(crate) fn new() -> AliasTargets<T> {
+        AliasTargets {
+            targets: HashMap::new(),
+        }
+    }
+
+    /// Keep `target` for the aliases after the entry with the given
+    /// header. Nothing is kept for aliases and delta entries, which can
+    /// not be referred to.
+    pub(crate) fn add(&mut self, header: &EntryHeader, target: T) {
+        if !matches!(header.format, EntryFormat::Alias | EntryFormat::Delta) {
+            self.targets.insert(header.name.clone(), target);
+        }
+    }
+
+    /// The name of the entry that the alias with the given header and
+    /// data refers to, and what was kept for it.
+    pub(crate) fn resolve(
+        &self,
+        header: &EntryHeader,
+        data: &[u8],
+    ) -> Result<(String, &T), SfaError> {
+        let target = parse_name(data.to_vec())?;
+        match self.targets.get(&target) {
+            Some(kept) => Ok((target, kept)),
+            None => Err(invalid_alias(header.name.clone(), target)),
+        }
+    }
+}
+
+/// What `sfa::decode_with` does with an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryAction {
+    /// Decode the image of the entry.
+    Decode,
+    /// Read the data of the entry without decoding it.
+    Skip,
+}
+
+impl<R: Read> SfaReader<R> {
+    /// Create a new reader and validate the magic text identifier
+    /// of the sfa file. No entries are read yet. The entries are checked
+    /// against the limits of `DecodeOptions::default()` as they are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - An object that implements the trait `io::Read`.
+    ///
+    /// # Errors
+    ///
+    /// * Reading from the reader was unsuccessful. (`SfaError::Io`)
+    /// * The file does not start with the magic text.
+    ///   (`SfaError::MissingMagic`)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sfa::{SfaError, SfaReader};
+    ///
+    /// assert!(SfaReader::new(b"SFA;".as_ref()).is_ok());
+    /// assert!(matches!(SfaReader::new(b"PNG;".as_ref()), Err(SfaError::MissingMagic)));
+    /// ```
+    pub fn new(reader: R) -> Result<SfaReader<R>, SfaError> {
+        SfaReader::with_options(reader, DecodeOptions::default())
+    }
+
+    /// Create a new reader like `sfa::SfaReader::new` that checks the
+    /// entries against the limits of the options as they are read, and
+    /// scales the images down to `DecodeOptions::max_dimension`.
+    ///
+    /// # Errors
+    ///
+    /// * Reading from the reader was unsuccessful. (`SfaError::Io`)
+    /// * The file does not start with the magic text.
+    ///   (`SfaError::MissingMagic`)
+    pub fn with_options(reader: R, options: DecodeOptions) -> Result<SfaReader<R>, SfaError> {
+        let mut reader = Positioned::new(reader);
+        let layout = Layout::read(&mut reader)?;
+        let metadata = metadata::read_archive_metadata(&mut reader, layout)?;
+
+        Ok(SfaReader {
+            reader,
+            layout,
+            metadata,
+            headers: Headers::new(layout).strict(options.strict),
+            options,
+            total: 0,
+            offset: 0,
+            buffer: vec![],
+            targets: AliasTargets::new(),
+            previous: None,
+            entry_metadata: EntryMetadata::default(),
+            skipped: vec![],
+            warnings: vec![],
+            finished: false,
+        })
+    }
+
+    /// Create a new reader like `sfa::SfaReader::new` that reads the
+    /// headers of the entries with `BufRead::read_until` instead of one
+    /// byte at a time, which is faster for readers that are buffered.
+    ///
+    /// # Errors
+    ///
+    /// * Reading from the reader was unsuccessful. (`SfaError::Io`)
+    /// * The file does not start with the magic text.
+    ///   (`SfaError::MissingMagic`)
+    pub fn buffered(reader: R) -> Result<SfaReader<R>, SfaError>
+    where
+        R: BufRead,
+    {
+        let mut sfa_reader = SfaReader::new(reader)?;
+        sfa_reader.headers = Headers::buffered(sfa_reader.layout);
+
+        Ok(sfa_reader)
+    }
+
+    /// Read and decode the next entry. `None` is returned once all
+    /// the entries have been read. After an error has been returned,
+    /// `None` is returned for all following calls since the position
+    /// of the next entry can not be known.
+    ///
+    /// With `ErrorPolicy::Skip`, entries that can not be decoded are
+    /// skipped and the next entry is returned instead. Their errors are
+    /// kept until `SfaReader::take_warnings` is called.
+    ///
+    /// # Errors
+    ///
+    /// * Reading from the reader was unsuccessful. (`SfaError::Io`)
+    /// * The entry does not comply with the sfa format.
+    ///   (`SfaError::UnexpectedEof`, `SfaError::TruncatedHeader`,
+    ///   `SfaError::TrailingData`, `SfaError::InvalidSize` or
+    ///   `SfaError::InvalidName`)
+    /// * The format tag of the entry is not known.
+    ///   (`SfaError::UnknownFormatTag`)
+    /// * The entry is not a valid image. (`SfaError::DecodeEntry`, or
+    ///   `SfaError::BrokenDeltaBase` if delta entries follow it, whose
+    ///   headers are read to name them)
+    /// * The entry is an alias or delta entry that does not fit the entries
+    ///   before it. (`SfaError::InvalidAlias` or `SfaError::InvalidDelta`)
+    /// * The entry is beyond the limits of the options.
+    ///   (`SfaError::TooManyEntries`, `SfaError::EntryTooLarge` or
+    ///   `SfaError::TotalSizeTooLarge`)
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs;
+    /// use std::io::BufReader;
+    /// use sfa::SfaReader;
+    ///
+    /// let file = BufReader::new(fs::File::open("sp.sfa").unwrap());
+    /// let mut reader = SfaReader::new(file).unwrap();
+    ///
+    /// let first = reader.next_frame().unwrap();
+    /// ```
+    pub fn next_frame(&mut self) -> Result<Option<(String, image::DynamicImage)>, SfaError> {
+        match self.next_entry(|_| EntryAction::Decode)? {
+            Some((name, Some(im))) => Ok(Some((name, im))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Read the next entry and decode it if `action` returns
+    /// `EntryAction::Decode` for its name, like `sfa::SfaReader::next_frame`.
+    /// The image is `None` for skipped entries.
+    pub(crate) fn next_entry<F: FnMut(&str) -> EntryAction>(
+        &mut self,
+        mut action: F,
+    ) -> Result<Option<(String, Option<image::DynamicImage>)>, SfaError> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let entry = self.read_entry(&mut action);
+        if !matches!(entry, Ok(Some(_))) {
+            self.finished = true;
+        }
+
+        entry
+    }
+
+    /// Decode the remaining entries into a `HashMap`, keeping the ones
+    /// with the same name as `DecodeOptions::duplicates` says.
+    pub(crate) fn collect_frames(
+        &mut self,
+    ) -> Result<HashMap<String, image::DynamicImage>, SfaError> {
+        let duplicates = self.options.duplicates;
+        // Offsets and images of the entries by name
+        let mut results: HashMap<String, (u64, image::DynamicImage)> = HashMap::new();
+        loop {
+            let entry = self.next_entry(|name| match duplicates {
+                DuplicatePolicy::KeepFirst if results.contains_key(name) => EntryAction::Skip,
+                _ => EntryAction::Decode,
+            })?;
+            let (name, im) = match entry {
+                Some((name, Some(im))) => (name, im),
+                Some((_, None)) => continue,
+                None => break,
+            };
+
+            match (results.entry(name), duplicates) {
+                (Entry::Vacant(entry), _) => {
+                    entry.insert((self.offset, im));
+                }
+                (Entry::Occupied(entry), DuplicatePolicy::Error) => {
+                    return Err(SfaError::DuplicateEntry {
+                        name: entry.key().clone(),
+                        first: entry.get().0,
+                        second: self.offset,
+                    });
+                }
+                (Entry::Occupied(_), DuplicatePolicy::KeepFirst) => (),
+                (Entry::Occupied(mut entry), DuplicatePolicy::KeepLast) => {
+                    entry.insert((self.offset, im));
+                }
+            }
+        }
+
+        Ok(results
+            .into_iter()
+            .map(|(name, (_, im))| (name, im))
+            .collect())
+    }
+
+    /// Returns the metadata of the file, leaving it empty.
+    pub(crate) fn take_metadata(&mut self) -> HashMap<String, String> {
+        std::mem::take(&mut self.metadata)
+    }
+
+    /// The metadata of the entry that was returned last.
+    pub(crate) fn entry_metadata(&self) -> EntryMetadata {
+        self.entry_metadata
+    }
+
+    /// Returns the errors of the entries that were skipped with
+    /// `ErrorPolicy::Skip` since the last call, in the order of the file.
+    pub fn take_warnings(&mut self) -> Vec<DecodeWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Read the next entry that does not have to be skipped for
+    /// `DecodeOptions::on_error`.
+    fn read_entry<F: FnMut(&str) -> EntryAction>(
+        &mut self,
+        action: &mut F,
+    ) -> Result<Option<(String, Option<image::DynamicImage>)>, SfaError> {
+        loop {
+            let header = match self.headers.next(&mut self.reader)? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+
+            self.total = self.total.saturating_add(header.size as u64);
+            self.options
+                .check(self.headers.count, self.total, &header.name, header.size)?;
+            let action = action(&header.name);
+            let offset = self.reader.position();
+            // Only data that does not match its checksum can be skipped,
+            // data that is cut short ends the file
+            let entry = match read_payload(&mut self.reader, &header, &mut self.buffer) {
+                Ok(()) => self.decode_entry(&header, offset, action),
+                Err(e @ SfaError::ChecksumMismatch { .. }) => Err(e),
+                Err(e) => return Err(e),
+            };
+
+            match entry {
+                Ok(im) => {
+                    self.offset = offset;
+                    return Ok(Some((header.name, im)));
+                }
+                Err(e) if self.options.on_error == ErrorPolicy::Skip => {
+                    // Delta entries after it can not be decoded either
+                    self.previous = None;
+                    self.skipped.clear();
+                    self.warnings.push(DecodeWarning {
+                        name: header.name,
+                        reason: e,
+                    });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Decode the entry whose data was just read into the buffer, or
+    /// keep what is needed to decode the entries after it if `action`
+    /// is `EntryAction::Skip`. `offset` is the position of the data.
+    fn decode_entry(
+        &mut self,
+        header: &EntryHeader,
+        offset: u64,
+        action: EntryAction,
+    ) -> Result<Option<image::DynamicImage>, SfaError> {
+        let im = match (header.format, action) {
+            (EntryFormat::Alias, action) => {
+                let (target, (format, offset, data)) =
+                    self.targets.resolve(header, &self.buffer)?;
+                if action == EntryAction::Skip {
+                    if self.layout.knows(EntryFormat::Delta) {
+                        self.previous = None;
+                        self.skipped = vec![(target, *format, *offset, data.clone())];
+                    }
+                    return Ok(None);
+                }
+                let im = load_entry(&target, *offset, *format, data)?;
+                self.entry_metadata = EntryMetadata::read(*format, data)?;
+                im
+            }
+            (EntryFormat::Delta, EntryAction::Decode) => {
+                self.decode_skipped()?;
+                match &self.previous {
+                    Some(previous) => delta::apply(&header.name, offset, previous, &self.buffer)?,
+                    None => return Err(SfaError::InvalidDelta(header.name.clone())),
+                }
+            }
+            (EntryFormat::Delta, EntryAction::Skip) => {
+                if self.previous.is_none() && self.skipped.is_empty() {
+                    return Err(SfaError::InvalidDelta(header.name.clone()));
+                }
+                self.skipped.push((
+                    header.name.clone(),
+                    EntryFormat::Delta,
+                    offset,
+                    self.buffer.clone(),
+                ));
+                return Ok(None);
+            }
+            (format, action) => {
+                if self.layout.knows(EntryFormat::Alias) {
+                    self.targets
+                        .add(header, (format, offset, self.buffer.clone()));
+                }
+                if action == EntryAction::Skip {
+                    if self.layout.knows(EntryFormat::Delta) {
+                        self.previous = None;
+                        self.skipped =
+                            vec![(header.name.clone(), format, offset, self.buffer.clone())];
+                    }
+                    return Ok(None);
+                }
+                let im = match load_entry(&header.name, offset, format, &self.buffer) {
+                    Ok(im) => im,
+                    // The delta entries after it are skipped one by one
+                    Err(e) if self.options.on_error == ErrorPolicy::Skip => return Err(e),
+                    Err(e) => return Err(self.with_dependents(&header.name, e)),
+                };
+                self.entry_metadata = EntryMetadata::read(format, &self.buffer)?;
+                im
+            }
+        };
+
+        if self.layout.knows(EntryFormat::Delta) {
+            self.previous = Some(im.clone());
+            self.skipped.clear();
+        }
+
+        // Delta entries are based on the image before it was scaled down
+        Ok(Some(self.options.shrink(im)))
+    }
+
+    /// Decode the entries that were skipped since the previous decoded
+    /// entry, which a delta entry after them is based on.
+    fn decode_skipped(&mut self) -> Result<(), SfaError> {
+        for (name, format, offset, data) in self.skipped.drain(..) {
+            let im = match (format, &self.previous) {
+                (EntryFormat::Delta, Some(previous)) => {
+                    delta::apply(&name, offset, previous, &data)?
+                }
+                (EntryFormat::Delta, None) => return Err(SfaError::InvalidDelta(name)),
+                (format, _) => {
+                    let im = load_entry(&name, offset, format, &data)?;
+                    self.entry_metadata = EntryMetadata::read(format, &data)?;
+                    im
+                }
+            };
+            self.previous = Some(im);
+        }
+
+        Ok(())
+    }
+
+    /// Read the headers of the delta entries directly following an entry
+    /// that failed to decode, and name them in the error like the
+    /// functions that decode all the entries at once.
+    fn with_dependents(&mut self, name: &str, error: SfaError) -> SfaError {
+        let mut following: Vec<EntryHeader> = vec![];
+        while self.layout.knows(EntryFormat::Delta) {
+            match self.headers.next(&mut self.reader) {
+                Ok(Some(header)) if header.format == EntryFormat::Delta => {
+                    if skip_payload(&mut self.reader, header.size).is_err() {
+                        break;
+                    }
+                    following.push(header);
+                }
+                _ => break,
+            }
+        }
+
+        with_dependents(name, &following, error)
+    }
+
+    /// Returns an iterator over the remaining entries which
+    /// decodes one entry per call to `next`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// use image::DynamicImage;
+    /// use sfa::{encode_images, SfaReader};
+    ///
+    /// # let mut frames = HashMap::new();
+    /// # frames.insert(String::from("frame_1.png"), DynamicImage::new_rgba8(4, 4));
+    /// # frames.insert(String::from("frame_2.png"), DynamicImage::new_rgba8(4, 4));
+    /// # frames.insert(String::from("frame_3.png"), DynamicImage::new_rgba8(4, 4));
+    /// let path = std::env::temp_dir().join("sfa_reader_frames.sfa");
+    /// encode_images(&frames, &path).unwrap();
+    ///
+    /// let file = std::fs::File::open(&path).unwrap();
+    /// let mut reader = SfaReader::new(std::io::BufReader::new(file)).unwrap();
+    ///
+    /// let first_two: Vec<_> = reader.frames().take(2).collect();
+    /// assert_eq!(first_two.len(), 2);
+    /// assert_eq!(reader.frames().count(), 1);
+    /// ```
+    pub fn frames(&mut self) -> Frames<'_, R> {
+        Frames { reader: self }
+    }
+
+    /// Returns the underlying reader. It is positioned at the start of
+    /// the next entry that has not been read yet.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+/// Iterator over the entries of a `sfa::SfaReader`.
+/// It is created by `sfa::SfaReader::frames`.
+pub struct Frames<'a, R: Read> {
+    reader: &'a mut SfaReader<R>,
+}
+
+impl<'a, R: Read> Iterator for Frames<'a, R> {
+    type Item = Result<(String, image::DynamicImage), SfaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_frame().transpose()
+    }
+}
+
+/// Iterator over the entries of a sfa file that owns the reader, which
+/// decodes one entry per call to `next`. It is created by
+/// `sfa::entries_from_reader`. After an error has been returned, `None`
+/// is returned for all following calls.
+pub struct SfaEntries<R: Read> {
+    reader: SfaReader<R>,
+}
+
+impl<R: Read> SfaEntries<R> {
+    pub(crate) fn new(reader: SfaReader<R>) -> SfaEntries<R> {
+        SfaEntries { reader }
+    }
+
+    /// Returns the underlying reader. It is positioned at the start of
+    /// the next entry that has not been read yet.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+impl<R: Read> Iterator for SfaEntries<R> {
+    type Item = Result<(String, image::DynamicImage), SfaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_frame().transpose()
+    }
+}
+
+impl<R: Read> std::iter::FusedIterator for SfaEntries<R> {}
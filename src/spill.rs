@@ -0,0 +1,117 @@
+//! Buffering of encoded entries that may be too large for memory.
+
+use crate::SfaError;
+use std::fs;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Used to give every temporary file of the process a unique name.
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// A writer that keeps the data in memory until it grows larger
+/// than the limit, after which all of it is moved to a temporary
+/// file. The size of an entry has to be written before its data,
+/// so the data has to be stored somewhere until it is complete.
+pub(crate) struct SpillBuffer {
+    limit: usize,
+    memory: Vec<u8>,
+    file: Option<TemporaryFile>,
+    len: u64,
+    checksum: crc32fast::Hasher,
+}
+
+impl SpillBuffer {
+    pub(crate) fn new(limit: usize) -> SpillBuffer {
+        SpillBuffer {
+            limit,
+            memory: vec![],
+            file: None,
+            len: 0,
+            checksum: crc32fast::Hasher::new(),
+        }
+    }
+
+    /// Number of bytes written so far.
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// CRC32 of the data written so far.
+    pub(crate) fn checksum(&self) -> u32 {
+        self.checksum.clone().finalize()
+    }
+
+    /// Copy all the data written so far to the writer.
+    pub(crate) fn copy_to<W: Write>(&mut self, writer: &mut W) -> Result<(), SfaError> {
+        match &mut self.file {
+            Some(temporary) => {
+                temporary.file.seek(SeekFrom::Start(0))?;
+                io::copy(&mut temporary.file, writer)?;
+            }
+            None => writer.write_all(&self.memory)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for SpillBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.file.is_none() && self.memory.len() + buf.len() > self.limit {
+            let mut temporary = TemporaryFile::create()?;
+            temporary.file.write_all(&self.memory)?;
+            self.memory = vec![];
+            self.file = Some(temporary);
+        }
+
+        let written = match &mut self.file {
+            Some(temporary) => temporary.file.write(buf)?,
+            None => {
+                self.memory.extend_from_slice(buf);
+                buf.len()
+            }
+        };
+        self.len += written as u64;
+        self.checksum.update(&buf[..written]);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(temporary) => temporary.file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A file in the temporary directory that is removed when dropped.
+struct TemporaryFile {
+    path: PathBuf,
+    file: fs::File,
+}
+
+impl TemporaryFile {
+    fn create() -> io::Result<TemporaryFile> {
+        let path = std::env::temp_dir().join(format!(
+            "sfa-{}-{}.tmp",
+            std::process::id(),
+            TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(TemporaryFile { path, file })
+    }
+}
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        // Nothing can be done if removing fails, the OS cleans up eventually
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,196 @@
+//! The table of contents at the end of files whose layout has one.
+//!
+//! The table of contents is written after the last entry as an entry
+//! without a name with the tag `132`, so that reading the headers one
+//! after the other skips it like any other entry. Its data has the name,
+//! format tag, position, size and checksum of every entry, with zeros in
+//! place of the checksum in files without checksums. The position
+//! of its header follows the count of entries at the end of the file,
+//! which puts it in the last 8 bytes of the file, and lets random access
+//! readers find the entries without scanning the whole file.
+
+use crate::format::EntryFormat;
+use crate::layout::Layout;
+use crate::{read_header, read_payload, write_end, write_raw_entry, EntryHeader, SfaError};
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+/// A writer or reader that keeps track of the number of bytes written
+/// to or read from it, which is the position in the file if it starts
+/// at the start of the file.
+pub(crate) struct Positioned<T> {
+    inner: T,
+    position: u64,
+}
+
+impl<T> Positioned<T> {
+    pub(crate) fn new(inner: T) -> Positioned<T> {
+        Positioned::starting_at(inner, 0)
+    }
+
+    /// Wrap a writer or reader that is already at the given position.
+    pub(crate) fn starting_at(inner: T, position: u64) -> Positioned<T> {
+        Positioned { inner, position }
+    }
+
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub(crate) fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for Positioned<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Positioned<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for Positioned<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.inner.consume(amount);
+        self.position += amount as u64;
+    }
+}
+
+/// The entries written so far, for the table of contents and the count
+/// of entries at the end of the file.
+pub(crate) struct Toc {
+    layout: Layout,
+    count: usize,
+    /// Headers of the entries and the positions of their data. Only kept
+    /// for files that have a table of contents.
+    entries: Vec<(EntryHeader, u64)>,
+}
+
+impl Toc {
+    pub(crate) fn new(layout: Layout) -> Toc {
+        Toc {
+            layout,
+            count: 0,
+            entries: vec![],
+        }
+    }
+
+    /// Record an entry whose data ends at the given position.
+    pub(crate) fn add(&mut self, header: EntryHeader, end: u64) {
+        self.count += 1;
+        if self.layout.table_of_contents() {
+            let position = end - header.size as u64;
+            self.entries.push((header, position));
+        }
+    }
+
+    /// Write the table of contents and the count of entries if the
+    /// layout of the file has them.
+    pub(crate) fn finish<W: Write>(&self, writer: &mut Positioned<W>) -> Result<(), SfaError> {
+        if !self.layout.table_of_contents() {
+            return write_end(writer, self.layout, self.count, None);
+        }
+
+        let position = writer.position();
+        let mut data: Vec<u8> = vec![];
+        for (header, position) in &self.entries {
+            data.extend_from_slice(&(header.name.len() as u32).to_le_bytes());
+            data.extend_from_slice(header.name.as_bytes());
+            data.push(header.format.tag());
+            data.extend_from_slice(&position.to_le_bytes());
+            data.extend_from_slice(&(header.size as u64).to_le_bytes());
+            data.extend_from_slice(&header.checksum.unwrap_or(0).to_be_bytes());
+        }
+        write_raw_entry(writer, self.layout, "", &data, EntryFormat::Toc)?;
+        write_end(writer, self.layout, self.count, Some(position))
+    }
+}
+
+/// Read the table of contents of a file whose layout has one that
+/// starts at the given position of the reader. The positions of the data
+/// of the entries are returned from the start of the reader.
+pub(crate) fn read_toc<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    layout: Layout,
+) -> Result<Vec<(EntryHeader, u64)>, SfaError> {
+    reader.seek(SeekFrom::End(-8))?;
+    let mut position = [0u8; 8];
+    reader.read_exact(&mut position)?;
+    let position = offset(start, u64::from_le_bytes(position))?;
+    reader.seek(SeekFrom::Start(position))?;
+
+    let header = match read_header(reader, layout)? {
+        Some(header) if header.format == EntryFormat::Toc && header.name.is_empty() => header,
+        _ => return Err(SfaError::InvalidTableOfContents),
+    };
+    let mut data: Vec<u8> = vec![];
+    read_payload(reader, &header, &mut data)?;
+
+    let mut entries: Vec<(EntryHeader, u64)> = vec![];
+    let mut rest = data.as_slice();
+    while !rest.is_empty() {
+        let length = u32::from_le_bytes(take(&mut rest, 4)?.try_into().unwrap()) as usize;
+        let name = String::from_utf8(take(&mut rest, length)?.to_vec())
+            .map_err(|_| SfaError::InvalidTableOfContents)?;
+        let format = match EntryFormat::from_tag(take(&mut rest, 1)?[0]) {
+            Ok(EntryFormat::End) | Ok(EntryFormat::Toc) | Err(_) => {
+                return Err(SfaError::InvalidTableOfContents)
+            }
+            Ok(format) => format,
+        };
+        let position = u64::from_le_bytes(take(&mut rest, 8)?.try_into().unwrap());
+        let size = u64::from_le_bytes(take(&mut rest, 8)?.try_into().unwrap());
+        let size = usize::try_from(size).map_err(|_| SfaError::InvalidTableOfContents)?;
+        let checksum = u32::from_be_bytes(take(&mut rest, 4)?.try_into().unwrap());
+
+        // Files without checksums have zeros in their place
+        let header = EntryHeader {
+            name,
+            size,
+            format,
+            checksum: match layout.checksums() {
+                true => Some(checksum),
+                false => None,
+            },
+        };
+        entries.push((header, offset(start, position)?));
+    }
+
+    Ok(entries)
+}
+
+/// The position in the reader of a position in the table of contents,
+/// which counts from the start of the file.
+fn offset(start: u64, position: u64) -> Result<u64, SfaError> {
+    start
+        .checked_add(position)
+        .ok_or(SfaError::InvalidTableOfContents)
+}
+
+/// Take the given number of bytes from the start of the table of contents.
+fn take<'a>(rest: &mut &'a [u8], length: usize) -> Result<&'a [u8], SfaError> {
+    if rest.len() < length {
+        return Err(SfaError::InvalidTableOfContents);
+    }
+
+    let (taken, data) = rest.split_at(length);
+    *rest = data;
+    Ok(taken)
+}